@@ -1,26 +1,22 @@
-use crate::client::Client;
 use crate::opts::DecOpts;
-use crate::util::print_qr;
+use crate::util::{client, fail, print_qr};
 
 use futures::io::AllowStdIo;
 use indicatif::{ProgressBar, ProgressStyle};
 use inquire::{Select, Text};
-use pg_core::artifacts::UserSecretKey;
-use pg_core::client::rust::stream::UnsealerStreamConfig;
 use pg_core::identity::Attribute;
+use std::collections::BTreeMap;
 use std::fs::File;
 
-use pg_core::api::*;
-use pg_core::client::Unsealer;
-use pg_core::kem::cgw_kv::CGWKV;
-
 pub async fn exec(dec_opts: DecOpts) {
-    let DecOpts { input, pkg } = dec_opts;
-
-    let client = Client::new(&pkg).unwrap();
+    let DecOpts {
+        input,
+        pkg,
+        fingerprint,
+        workers,
+    } = dec_opts;
 
-    eprintln!("Retrieving signing public key");
-    let parameters_sign = client.signing_parameters().await.unwrap();
+    let client = client(&pkg, fingerprint.as_deref());
 
     eprintln!("Opening {}", input);
 
@@ -35,10 +31,11 @@ pub async fn exec(dec_opts: DecOpts) {
     let source = File::open(&input).unwrap();
     let mut async_read = AllowStdIo::new(&source);
 
-    let unsealer =
-        Unsealer::<_, UnsealerStreamConfig>::new(&mut async_read, &parameters_sign.public_key)
-            .await
-            .unwrap();
+    eprintln!("Retrieving signing public key");
+    let unsealer = client
+        .unsealer(&mut async_read)
+        .await
+        .unwrap_or_else(|e| fail(&client, e));
 
     eprintln!("PostGuard format version: {:#?}", unsealer.version);
     eprintln!(
@@ -46,47 +43,49 @@ pub async fn exec(dec_opts: DecOpts) {
         serde_json::to_string_pretty(&unsealer.header).unwrap()
     );
 
-    let hidden_policies = &unsealer.header.recipients;
-    let options: Vec<_> = hidden_policies.keys().cloned().collect();
-    let id = Select::new("What's your recipient identifier?", options)
-        .prompt()
-        .unwrap();
-
-    let rec_info = hidden_policies.get(&id).unwrap();
-    let mut reconstructed_policy = rec_info.policy.clone();
-    for attr in reconstructed_policy.con.iter_mut() {
-        attr.value = Text::new(&format!("Enter value for {}?", attr.atype))
-            .prompt()
-            .ok();
+    // Recipients are only told apart by the attribute types in their policy, the entry for the
+    // user is found using the retrieved key. Group them as `Header::identify` does.
+    let mut groups = BTreeMap::new();
+    for (id, r) in &unsealer.header.recipients {
+        let mut types: Vec<String> = r.policy.con.iter().map(|a| a.atype.clone()).collect();
+        types.sort();
+        groups
+            .entry((types, r.policy.timestamp, r.policy.not_before))
+            .or_insert_with(|| id.clone());
     }
-
-    let keyrequest = IrmaAuthRequest {
-        con: reconstructed_policy
-            .con
-            .iter()
-            .map(|attr| Attribute {
-                atype: attr.atype.clone(),
-                value: attr.value.clone(),
-            })
-            .collect(),
-        validity: None,
+    let groups: Vec<_> = groups.into_iter().collect();
+
+    let index = match groups.len() {
+        1 => 0,
+        _ => {
+            let options: Vec<_> = groups
+                .iter()
+                .map(|((types, _, _), _)| types.join(", "))
+                .collect();
+            Select::new("Which attributes do you want to prove?", options)
+                .raw_prompt()
+                .unwrap()
+                .index
+        }
     };
+    let ((types, _, _), id) = &groups[index];
 
-    eprintln!("Requesting key for {:?}", &keyrequest);
+    let con: Vec<Attribute> = types
+        .iter()
+        .map(|atype| Attribute {
+            atype: atype.clone(),
+            value: Text::new(&format!("Enter value for {atype}?"))
+                .prompt()
+                .ok(),
+        })
+        .collect();
 
-    let sd: irma::SessionData = client.request_start(&keyrequest).await.unwrap();
+    eprintln!("Requesting key for {:?}", &con);
 
-    eprintln!("Please scan the following QR-code with IRMA/Yivi:");
-    print_qr(&sd.session_ptr);
-
-    let key_resp: KeyResponse<UserSecretKey<CGWKV>> = client
-        .wait_on_decryption_key(&sd, rec_info.policy.timestamp)
-        .await
-        .unwrap();
-
-    let usk = key_resp.key.unwrap();
-
-    let destination = File::create(out_file_name).unwrap();
+    // Plaintext is written as it is decrypted, but only moved into place once all of it has been
+    // verified, so a truncated or forged file never ends up at the output path.
+    let part_file_name = format!("{out_file_name}.part");
+    let destination = File::create(&part_file_name).unwrap();
 
     let pb = ProgressBar::new(source.metadata().unwrap().len());
     pb.set_style(ProgressStyle::default_bar()
@@ -95,9 +94,25 @@ pub async fn exec(dec_opts: DecOpts) {
 
     let w = AllowStdIo::new(pb.wrap_write(destination));
 
-    eprintln!("Decrypting {}...", input);
+    let verified_policy = client
+        .decrypt_with_yivi(
+            unsealer.with_workers(workers),
+            id,
+            con,
+            |sd| {
+                eprintln!("Please scan the following QR-code with IRMA/Yivi:");
+                print_qr(&sd.session_ptr);
+                eprintln!("Decrypting {}...", input);
+            },
+            w,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            let _ = std::fs::remove_file(&part_file_name);
+            fail(&client, e)
+        });
 
-    let verified_policy = unsealer.unseal(&id, &usk, w).await.unwrap();
+    std::fs::rename(&part_file_name, out_file_name).unwrap();
 
     println!(
         "The message was signed using: {}",
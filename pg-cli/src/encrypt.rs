@@ -1,11 +1,10 @@
 use pg_core::api::{IrmaAuthRequest, SigningKeyRequest, SigningKeyResponse};
-use pg_core::client::rust::stream::SealerStreamConfig;
+use pg_core::client::rust::blocking::SealerBlockingConfig;
 use pg_core::client::Sealer;
-use pg_core::identity::{Attribute, Policy};
+use pg_core::identity::{round_timestamp, Attribute, Policy};
 
 use crate::opts::EncOpts;
-use crate::util::print_qr;
-use futures::io::AllowStdIo;
+use crate::util::{client, fail, hint_policy, print_qr};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::collections::BTreeMap;
 use std::fs::File;
@@ -28,9 +27,15 @@ pub async fn exec(enc_opts: EncOpts) {
         pub_sign_id: pub_sign_id_str,
         priv_sign_id,
         pkg,
+        epoch,
+        not_before,
+        anonymous,
+        private_sender,
+        fingerprint,
+        workers,
     } = enc_opts;
 
-    let timestamp = now();
+    let timestamp = round_timestamp(now(), epoch);
 
     let x: BTreeMap<String, Vec<Attribute>> = serde_json::from_str(&identity).unwrap();
     let identifiers: Vec<String> = x.keys().cloned().collect();
@@ -42,6 +47,7 @@ pub async fn exec(enc_opts: EncOpts) {
                 Policy {
                     timestamp,
                     con: con.clone(),
+                    not_before,
                 },
             )
         })
@@ -58,10 +64,19 @@ pub async fn exec(enc_opts: EncOpts) {
         None
     };
 
-    let client = crate::client::Client::new(&pkg).unwrap();
-    let parameters = client.parameters().await.unwrap();
+    let client = client(&pkg, fingerprint.as_deref());
+    let parameters = client
+        .parameters()
+        .await
+        .unwrap_or_else(|e| fail(&client, e));
 
     eprintln!("Fetched parameters from {}", pkg);
+    if let Some(key_validity) = parameters.key_validity {
+        eprintln!(
+            "The PKG only issues keys for {} days, recipients must decrypt before then",
+            key_validity / (24 * 60 * 60)
+        );
+    }
     eprintln!(
         "Encrypting for the following recipients:\n{:#?}\n using the following policies:\n{}",
         identifiers,
@@ -106,22 +121,31 @@ pub async fn exec(enc_opts: EncOpts) {
         .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} {binary_bytes_per_sec} ({eta} left)").unwrap()
         .progress_chars("#>-"));
 
-    let r = AllowStdIo::new(pb.wrap_read(source));
-    let w = AllowStdIo::new(destination);
+    let r = pb.wrap_read(source);
 
     eprintln!("Encrypting {}...", input);
 
-    let mut sealer = Sealer::<_, SealerStreamConfig>::new(
+    let mut sealer = Sealer::<_, SealerBlockingConfig>::new(
         &parameters.public_key,
         &policies,
         &pub_sign_key.expect("no public signing key"),
         &mut rng,
     )
-    .unwrap();
+    .unwrap()
+    .with_hint_policy(&hint_policy())
+    .with_workers(workers);
 
     if let Some(psk) = priv_sign_key {
         sealer = sealer.with_priv_signing_key(psk);
     };
 
-    sealer.seal(r, w).await.unwrap();
+    if anonymous {
+        sealer = sealer.with_anonymous_recipients();
+    }
+
+    if private_sender {
+        sealer = sealer.with_private_sender();
+    }
+
+    sealer.seal(r, destination).unwrap();
 }
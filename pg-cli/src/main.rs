@@ -1,4 +1,3 @@
-mod client;
 mod decrypt;
 mod encrypt;
 mod opts;
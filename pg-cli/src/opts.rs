@@ -41,6 +41,44 @@ pub struct EncOpts {
     /// Private key generator (PKG) server URL.
     #[clap(short, long, default_value = "https://stable.irmaseal-pkg.ihub.ru.nl", value_hint = ValueHint::Url)]
     pub pkg: String,
+
+    /// Round the timestamp of the policies down to an epoch of this many seconds.
+    ///
+    /// Recipients can then decrypt all files encrypted for them in the same epoch using a single
+    /// key, e.g., use 86400 for a day. The default of 1 uses the exact current time.
+    #[clap(long, default_value = "1")]
+    pub epoch: u64,
+
+    /// Embargo (UNIX time) before which the recipients cannot decrypt, e.g., 1798761600 for
+    /// 2027-01-01.
+    ///
+    /// The PKG refuses to issue keys for the files until its clock has passed this time.
+    #[clap(long)]
+    pub not_before: Option<u64>,
+
+    /// Replace the recipient identifiers in the header by random labels.
+    ///
+    /// Otherwise, anyone holding the encrypted file learns the identifiers of all recipients.
+    #[clap(long)]
+    pub anonymous: bool,
+
+    /// Only include the public signing identity in the encrypted payload.
+    ///
+    /// Otherwise, anyone holding the encrypted file can read the public signing identity.
+    #[clap(long)]
+    pub private_sender: bool,
+
+    /// Number of segments to encrypt concurrently.
+    #[clap(long, default_value = "1")]
+    pub workers: usize,
+
+    /// Fingerprint of the identity key of the PKG, to only accept parameters signed by it.
+    ///
+    /// Overrides the fingerprint pinned for the PKG in `$XDG_CONFIG_HOME/postguard/config.json`
+    /// (by default `~/.config/postguard/config.json`), which contains an object of the form
+    /// `{ "fingerprints": { "<PKG URL>": "<fingerprint>" } }`.
+    #[clap(long)]
+    pub fingerprint: Option<String>,
 }
 
 /// Encrypt a file.
@@ -54,4 +92,16 @@ pub struct DecOpts {
     /// Private key generator (PKG) server URL.
     #[clap(short, long, default_value = "https://stable.irmaseal-pkg.ihub.ru.nl", value_hint = ValueHint::Url)]
     pub pkg: String,
+
+    /// Fingerprint of the identity key of the PKG, to only accept parameters signed by it.
+    ///
+    /// Overrides the fingerprint pinned for the PKG in `$XDG_CONFIG_HOME/postguard/config.json`
+    /// (by default `~/.config/postguard/config.json`), which contains an object of the form
+    /// `{ "fingerprints": { "<PKG URL>": "<fingerprint>" } }`.
+    #[clap(long)]
+    pub fingerprint: Option<String>,
+
+    /// Number of segments to decrypt concurrently.
+    #[clap(long, default_value = "1")]
+    pub workers: usize,
 }
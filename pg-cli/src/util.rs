@@ -1,5 +1,103 @@
+use pg_client::{Client, Error};
+use pg_core::error::Error as CoreError;
+use pg_core::identity::HintPolicy;
 use qrcode::render::Pixel;
 use qrcode::Color;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The configuration, read from `config.json` in the configuration directory.
+#[derive(Debug, Default, Deserialize)]
+struct Config {
+    /// Pinned fingerprints of the identity keys of PKGs, by PKG URL.
+    #[serde(default)]
+    fingerprints: BTreeMap<String, String>,
+
+    /// The rules used to hide the attribute values of recipients in the header, e.g.,
+    /// `{ "rules": { "pbdf.sidn-pbdf.email.email": "revealDomain" }, "fallback": "hide" }`.
+    #[serde(default)]
+    hints: HintPolicy,
+}
+
+/// Returns the PostGuard directory in the XDG base directory `var`, or `fallback` in the home
+/// directory.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+        .map(|dir| dir.join("postguard"))
+}
+
+/// The directory in which the parameters of PKGs are cached.
+fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+fn config() -> Config {
+    let file = match xdg_dir("XDG_CONFIG_HOME", ".config") {
+        Some(dir) => dir.join("config.json"),
+        None => return Config::default(),
+    };
+
+    match std::fs::read(&file) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            eprintln!("error: invalid configuration in {}: {e}", file.display());
+            std::process::exit(1)
+        }),
+        Err(_) => Config::default(),
+    }
+}
+
+/// The configured hint rules for sealing.
+pub(crate) fn hint_policy() -> HintPolicy {
+    config().hints
+}
+
+pub(crate) fn client(pkg: &str, fingerprint: Option<&str>) -> Client {
+    let mut builder =
+        Client::builder(pkg).client_version(format!("unknown,unknown,cli,{PKG_VERSION}"));
+
+    if let Some(cache_dir) = cache_dir() {
+        builder = builder.cache_dir(cache_dir);
+    }
+
+    let pkg = pkg.trim_end_matches('/');
+    let pinned = config()
+        .fingerprints
+        .into_iter()
+        .find_map(|(url, fingerprint)| (url.trim_end_matches('/') == pkg).then_some(fingerprint));
+    if let Some(fingerprint) = fingerprint.map(String::from).or(pinned) {
+        builder = builder.identity_fingerprint(fingerprint);
+    }
+
+    builder.build().unwrap()
+}
+
+/// Reports an error and exits, loudly so if the parameters of the PKG cannot be trusted.
+pub(crate) fn fail(client: &Client, e: Error) -> ! {
+    if let Error::ParametersChanged { .. } = e {
+        eprintln!("\n!!! WARNING: THE PUBLIC KEY OF THE PKG HAS CHANGED !!!\n");
+        eprintln!("{e}.");
+        if let Some(file) = client.cache_file() {
+            eprintln!(
+                "If the PKG legitimately changed its keys, remove {} and try again.",
+                file.display()
+            );
+        }
+    } else if let Error::Core(e @ (CoreError::UntrustedIdentity(_) | CoreError::InconsistentLog)) =
+        e
+    {
+        eprintln!("\n!!! WARNING: THE PARAMETERS OF THE PKG CANNOT BE TRUSTED !!!\n");
+        eprintln!("{e}, the PKG may be compromised or impersonated.");
+    } else {
+        eprintln!("error: {e}");
+    }
+
+    std::process::exit(1)
+}
 
 pub(crate) fn print_qr(qr: &irma::Qr) {
     let code = qrcode::QrCode::new(serde_json::to_string(qr).unwrap()).unwrap();
@@ -0,0 +1,121 @@
+//! Cache of the public parameters of a PKG.
+//!
+//! Parameters are kept in memory and, if a cache directory is configured, in a JSON file per PKG
+//! URL. Cached parameters are revalidated using `If-None-Match` and `If-Modified-Since`, and are
+//! used as-is when the PKG cannot be reached.
+//!
+//! The first parameters seen for a PKG URL are trusted. If the PKG later serves a different public
+//! key, this is reported as [`Error::ParametersChanged`](crate::Error::ParametersChanged) and the
+//! cached parameters are kept, until they are explicitly forgotten.
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+/// Parameters as last received from the PKG, along with their validators.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CachedParameters {
+    /// The entity tag of the parameters.
+    pub etag: Option<String>,
+
+    /// The modification date of the parameters.
+    pub last_modified: Option<String>,
+
+    /// The raw parameters.
+    pub body: String,
+}
+
+impl CachedParameters {
+    /// Returns whether the public key in `body` differs from the cached public key.
+    pub fn public_key_differs(&self, body: &str) -> bool {
+        let public_key = |body: &str| {
+            serde_json::from_str::<serde_json::Value>(body)
+                .ok()
+                .map(|v| v["publicKey"].clone())
+        };
+
+        public_key(&self.body) != public_key(body)
+    }
+}
+
+/// The cached parameters of a single PKG.
+#[derive(Debug, Default)]
+pub(crate) struct ParametersCache {
+    file: Option<PathBuf>,
+    entries: BTreeMap<String, CachedParameters>,
+}
+
+/// Turns a URL into a file name.
+fn file_name(url: &str) -> String {
+    let name: String = url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    format!("{name}.json")
+}
+
+impl ParametersCache {
+    /// Loads the cache for the PKG at `url` from `dir`, if any.
+    pub fn load(dir: Option<&Path>, url: &str) -> Result<Self, Error> {
+        let file = match dir {
+            Some(dir) => dir.join(file_name(url)),
+            None => return Ok(Self::default()),
+        };
+
+        let entries = match std::fs::read(&file) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(Error::Cache(e)),
+        };
+
+        Ok(Self {
+            file: Some(file),
+            entries,
+        })
+    }
+
+    /// The file in which the cache is stored, if any.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Returns the cached parameters at `path`.
+    pub fn get(&self, path: &str) -> Option<CachedParameters> {
+        self.entries.get(path).cloned()
+    }
+
+    /// Stores parameters and writes the cache to disk.
+    pub fn insert(&mut self, path: &str, cached: CachedParameters) -> Result<(), Error> {
+        self.entries.insert(path.to_string(), cached);
+        self.persist()
+    }
+
+    /// Forgets all parameters, such that the next parameters are trusted.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.entries.clear();
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<(), Error> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir).map_err(Error::Cache)?;
+        }
+
+        // Write to a temporary file first, such that the cache is never left half-written.
+        let tmp = file.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.entries)?).map_err(Error::Cache)?;
+        std::fs::rename(&tmp, file).map_err(Error::Cache)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! PostGuard client errors.
+
+use irma::{ProofStatus, SessionStatus};
+
+/// A PostGuard client error.
+#[derive(Debug)]
+pub enum Error {
+    /// The URL of the PKG is invalid.
+    Url(url::ParseError),
+    /// The client version is not a valid header value.
+    InvalidClientVersion,
+    /// An HTTP request failed, timed out, or the PKG responded with an error status.
+    Http(reqwest::Error),
+    /// The PKG responded with an invalid body.
+    Json(serde_json::Error),
+    /// The session did not finish before the polling timeout.
+    Timeout,
+    /// The PKG does not know the session.
+    SessionNotFound,
+    /// The operation was cancelled.
+    Cancelled,
+    /// The session finished without the PKG issuing a key.
+    NoKey {
+        /// The status of the session.
+        status: SessionStatus,
+        /// The status of the IRMA proof.
+        proof_status: Option<ProofStatus>,
+    },
+    /// The PKG no longer issues keys for the timestamp of the message.
+    KeyExpired {
+        /// The timestamp of the policy of the recipient.
+        timestamp: u64,
+        /// The key validity window of the PKG (in seconds).
+        key_validity: u64,
+    },
+    /// The message is embargoed, the PKG does not issue a key for it yet.
+    Embargoed {
+        /// The embargo timestamp of the policy of the recipient.
+        not_before: u64,
+    },
+    /// The PKG serves a different public key than it did before.
+    ParametersChanged {
+        /// The URL of the PKG.
+        url: String,
+        /// The path of the parameters.
+        path: String,
+    },
+    /// The parameters cache could not be read or written.
+    Cache(std::io::Error),
+    /// The parameters cache is unusable, since a thread panicked while using it.
+    Poisoned,
+    /// An error from the core library.
+    Core(pg_core::error::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Url(e) => write!(f, "invalid PKG URL: {e}"),
+            Self::InvalidClientVersion => write!(f, "invalid client version"),
+            Self::Http(e) => write!(f, "HTTP error: {e}"),
+            Self::Json(e) => write!(f, "JSON error: {e}"),
+            Self::Timeout => write!(f, "the session did not finish in time"),
+            Self::SessionNotFound => write!(f, "the session was not found"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::NoKey {
+                status,
+                proof_status,
+            } => write!(
+                f,
+                "no key was issued (status: {status:?}, proof status: {proof_status:?})"
+            ),
+            Self::KeyExpired {
+                timestamp,
+                key_validity,
+            } => write!(
+                f,
+                "the PKG no longer issues keys for timestamp {timestamp}, \
+                 keys are only issued for timestamps up to {key_validity} seconds old"
+            ),
+            Self::Embargoed { not_before } => write!(
+                f,
+                "the message is embargoed, the PKG does not issue a key before {not_before}"
+            ),
+            Self::ParametersChanged { url, path } => write!(
+                f,
+                "the public key at {url}{path} differs from the one seen before, \
+                 the PKG may be compromised or impersonated"
+            ),
+            Self::Cache(e) => write!(f, "parameters cache error: {e}"),
+            Self::Poisoned => write!(f, "parameters cache poisoned"),
+            Self::Core(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<url::ParseError> for Error {
+    fn from(e: url::ParseError) -> Self {
+        Self::Url(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<pg_core::error::Error> for Error {
+    fn from(e: pg_core::error::Error) -> Self {
+        Self::Core(e)
+    }
+}
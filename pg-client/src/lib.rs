@@ -0,0 +1,1006 @@
+#![doc = include_str!("../README.md")]
+#![deny(
+    missing_debug_implementations,
+    rust_2018_idioms,
+    missing_docs,
+    rustdoc::broken_intra_doc_links,
+    unsafe_code
+)]
+
+mod cache;
+pub mod error;
+
+pub use error::Error;
+pub use tokio_util::sync::CancellationToken;
+
+use futures::io::{AsyncRead, AsyncWrite};
+use pg_core::api::*;
+use pg_core::artifacts::{PublicKey, UserSecretKey, VerifyingKey};
+use pg_core::client::rust::stream::UnsealerStreamConfig;
+use pg_core::client::{Unsealer, VerificationResult};
+use pg_core::identity::Attribute;
+use pg_core::kem::cgw_kv::CGWKV;
+use pg_core::kem::IBKEM;
+use pg_core::transparency::{ParametersLog, ParametersStatement, SignedParameters};
+
+use irma::{SessionData, SessionStatus, SessionToken};
+use reqwest::header::{
+    HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::time::{sleep, Instant};
+use zeroize::Zeroizing;
+
+use crate::cache::{CachedParameters, ParametersCache};
+
+/// The default interval between two polls of a session.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The default time to wait for a session to finish.
+pub const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The header in which clients report their version to the PKG.
+const CLIENT_VERSION_HEADER: &str = "X-Postguard-Client-Version";
+
+/// Builder for a [`Client`].
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    url: String,
+    client_version: String,
+    timeout: Option<Duration>,
+    poll_interval: Duration,
+    poll_timeout: Duration,
+    cancel: CancellationToken,
+    cache_dir: Option<PathBuf>,
+    fingerprint: Option<String>,
+}
+
+impl ClientBuilder {
+    /// Sets the version reported to the PKG, formatted as
+    /// `<host>,<host version>,<client>,<client version>`.
+    pub fn client_version(mut self, client_version: impl Into<String>) -> Self {
+        self.client_version = client_version.into();
+        self
+    }
+
+    /// Sets the timeout of individual requests. By default, requests do not time out.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the interval between two polls of a session.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Sets the time to wait for a session to finish.
+    pub fn poll_timeout(mut self, poll_timeout: Duration) -> Self {
+        self.poll_timeout = poll_timeout;
+        self
+    }
+
+    /// Uses a token to cancel all operations of the client, see [`Client::cancellation_token`].
+    pub fn cancellation_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Persists the parameters of the PKG in `cache_dir`, see [`Client::parameters`].
+    pub fn cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Pins the fingerprint of the identity key of the PKG.
+    ///
+    /// The public parameters are then only accepted if they are signed by this key, and
+    /// consistent with the log of public parameters, if the PKG keeps one. See
+    /// [`Client::signed_parameters`].
+    pub fn identity_fingerprint(mut self, fingerprint: impl Into<String>) -> Self {
+        self.fingerprint = Some(fingerprint.into());
+        self
+    }
+
+    /// Builds the [`Client`].
+    pub fn build(self) -> Result<Client, Error> {
+        // Make sure paths are joined to the base URL, even if it has a path itself.
+        let mut base = Url::parse(&self.url)?;
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CLIENT_VERSION_HEADER,
+            HeaderValue::from_str(&self.client_version)
+                .map_err(|_e| Error::InvalidClientVersion)?,
+        );
+
+        let mut builder = reqwest::ClientBuilder::new().default_headers(headers);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let cache = ParametersCache::load(self.cache_dir.as_deref(), base.as_str())?;
+
+        Ok(Client {
+            base,
+            client: builder.build()?,
+            poll_interval: self.poll_interval,
+            poll_timeout: self.poll_timeout,
+            cancel: self.cancel,
+            cache: Arc::new(Mutex::new(cache)),
+            fingerprint: self.fingerprint,
+        })
+    }
+}
+
+/// A client for the PostGuard Private Key Generator (PKG).
+///
+/// Cloning the client is cheap, the clones share their connection pool and cache.
+#[derive(Debug, Clone)]
+pub struct Client {
+    base: Url,
+    client: reqwest::Client,
+    poll_interval: Duration,
+    poll_timeout: Duration,
+    cancel: CancellationToken,
+    cache: Arc<Mutex<ParametersCache>>,
+    fingerprint: Option<String>,
+}
+
+impl Client {
+    /// Creates a [`ClientBuilder`] for the PKG at `url`.
+    pub fn builder(url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            url: url.into(),
+            client_version: format!("unknown,unknown,pg-client,{}", env!("CARGO_PKG_VERSION")),
+            timeout: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            poll_timeout: DEFAULT_POLL_TIMEOUT,
+            cancel: CancellationToken::new(),
+            cache_dir: None,
+            fingerprint: None,
+        }
+    }
+
+    /// Creates a client for the PKG at `url` using the default settings.
+    pub fn new(url: impl Into<String>) -> Result<Self, Error> {
+        Self::builder(url).build()
+    }
+
+    /// Returns the token that cancels all ongoing and future operations of this client.
+    ///
+    /// Single operations can also be cancelled by dropping their future.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    fn url(&self, path: &str) -> Result<Url, Error> {
+        Ok(self.base.join(path)?)
+    }
+
+    async fn cancellable<T>(
+        &self,
+        fut: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        tokio::select! {
+            res = fut => res,
+            _ = self.cancel.cancelled() => Err(Error::Cancelled),
+        }
+    }
+
+    /// Returns the file in which the parameters are persisted, if any.
+    pub fn cache_file(&self) -> Option<PathBuf> {
+        let cache = self.cache.lock().ok()?;
+        cache.file().map(PathBuf::from)
+    }
+
+    /// Forgets the cached parameters, such that the next parameters retrieved are trusted.
+    ///
+    /// Only use this after making sure that a change of the public key of the PKG is legitimate.
+    pub fn forget_parameters(&self) -> Result<(), Error> {
+        self.cache.lock().map_err(|_e| Error::Poisoned)?.clear()
+    }
+
+    /// Retrieves parameters, revalidating cached parameters.
+    ///
+    /// Fresh parameters are only cached once `validate` accepts them, given the cached ones.
+    async fn cached_parameters<T, F, Fut>(&self, path: &str, validate: F) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        F: FnOnce(Option<CachedParameters>, String) -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        let cached = self.cache.lock().map_err(|_e| Error::Poisoned)?.get(path);
+
+        let mut req = self.client.get(self.url(path)?);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                req = req.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                req = req.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let body = self
+            .cancellable(async {
+                let res = match (req.send().await, &cached) {
+                    // Fall back to the cached parameters if the PKG cannot be reached.
+                    (Err(e), Some(cached)) if e.is_connect() || e.is_timeout() => {
+                        return Ok(cached.body.clone())
+                    }
+                    (res, _) => res?,
+                };
+
+                if let (StatusCode::NOT_MODIFIED, Some(cached)) = (res.status(), &cached) {
+                    return Ok(cached.body.clone());
+                }
+
+                let res = res.error_for_status()?;
+                let header = |name| {
+                    res.headers()
+                        .get(name)
+                        .and_then(|v: &HeaderValue| v.to_str().ok())
+                        .map(String::from)
+                };
+                let etag = header(ETAG);
+                let last_modified = header(LAST_MODIFIED);
+                let body = res.text().await?;
+
+                validate(cached, body.clone()).await?;
+
+                let fresh = CachedParameters {
+                    etag,
+                    last_modified,
+                    body: body.clone(),
+                };
+                self.cache
+                    .lock()
+                    .map_err(|_e| Error::Poisoned)?
+                    .insert(path, fresh)?;
+
+                Ok(body)
+            })
+            .await?;
+
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Retrieves unsigned parameters, trusting the public key seen first.
+    async fn trusted_parameters<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        self.cached_parameters(path, |cached, body| async move {
+            // Trust on first use: the public key of a PKG is not supposed to change.
+            if cached.is_some_and(|cached| cached.public_key_differs(&body)) {
+                return Err(Error::ParametersChanged {
+                    url: self.base.to_string(),
+                    path: path.to_string(),
+                });
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Retrieves the public parameters for encryption.
+    ///
+    /// The parameters are cached, see [`ClientBuilder::cache_dir`]. If a fingerprint is pinned,
+    /// the parameters are taken from [`Client::signed_parameters`]. Otherwise, if the PKG serves a
+    /// different public key than before, [`Error::ParametersChanged`] is returned.
+    pub async fn parameters<K>(&self) -> Result<Parameters<PublicKey<K>>, Error>
+    where
+        K: IBKEM,
+        PublicKey<K>: DeserializeOwned,
+    {
+        match &self.fingerprint {
+            Some(fingerprint) => {
+                let statement = self.signed_parameters(fingerprint).await?;
+
+                // The statement only covers the current scheme, convert its public key.
+                Ok(Parameters {
+                    format_version: statement.format_version,
+                    public_key: serde_json::from_value(serde_json::to_value(
+                        statement.public_key,
+                    )?)?,
+                    key_validity: statement.key_validity,
+                })
+            }
+            None => self.trusted_parameters("v2/parameters").await,
+        }
+    }
+
+    /// Retrieves the public parameters for signature verification.
+    ///
+    /// The parameters are retrieved in the same way as [`Client::parameters`].
+    pub async fn signing_parameters(&self) -> Result<Parameters<VerifyingKey>, Error> {
+        match &self.fingerprint {
+            Some(fingerprint) => {
+                let statement = self.signed_parameters(fingerprint).await?;
+
+                Ok(Parameters {
+                    format_version: statement.format_version,
+                    public_key: statement.verifying_key,
+                    key_validity: None,
+                })
+            }
+            None => self.trusted_parameters("v2/sign/parameters").await,
+        }
+    }
+
+    /// Retrieves the public parameters signed by the identity key with `fingerprint`.
+    ///
+    /// If the PKG keeps a log of public parameters, the parameters must be its last entry, and
+    /// the log must extend the log of the parameters retrieved before. The parameters are cached
+    /// in the same way as [`Client::parameters`].
+    pub async fn signed_parameters(&self, fingerprint: &str) -> Result<ParametersStatement, Error> {
+        let signed: SignedParameters = self
+            .cached_parameters("v2/parameters/signed", |cached, body| async move {
+                let statement =
+                    serde_json::from_str::<SignedParameters>(&body)?.verify(Some(fingerprint))?;
+                let previous = cached
+                    .and_then(|cached| serde_json::from_str::<SignedParameters>(&cached.body).ok())
+                    .and_then(|signed| signed.verify(Some(fingerprint)).ok());
+
+                let log = match statement.log {
+                    Some(_) => Some(self.parameters_log().await?),
+                    None => None,
+                };
+
+                Ok(statement.check_log(log.as_ref(), previous.as_ref())?)
+            })
+            .await?;
+
+        // Cached parameters are used as-is, so verify them regardless.
+        Ok(signed.verify(Some(fingerprint))?)
+    }
+
+    /// Retrieves the log of public parameters of the PKG.
+    pub async fn parameters_log(&self) -> Result<ParametersLog, Error> {
+        let req = self.client.get(self.url("v2/parameters/log")?);
+
+        self.cancellable(async { Ok(req.send().await?.error_for_status()?.json().await?) })
+            .await
+    }
+
+    /// Retrieves the capabilities of the PKG.
+    pub async fn info(&self) -> Result<ServerInfo, Error> {
+        let req = self.client.get(self.url("v2/info")?);
+
+        self.cancellable(async { Ok(req.send().await?.error_for_status()?.json().await?) })
+            .await
+    }
+
+    /// Starts a session to authenticate using IRMA.
+    pub async fn request_start(&self, kr: &IrmaAuthRequest) -> Result<SessionData, Error> {
+        let req = self.client.post(self.url("v2/irma/start")?).json(kr);
+
+        self.cancellable(async { Ok(req.send().await?.error_for_status()?.json().await?) })
+            .await
+    }
+
+    /// Retrieves the signed result of a session.
+    pub async fn request_jwt(&self, token: &SessionToken) -> Result<String, Error> {
+        let req = self
+            .client
+            .get(self.url(&format!("v2/irma/jwt/{}", token.0))?);
+
+        self.cancellable(async { Ok(req.send().await?.error_for_status()?.text().await?) })
+            .await
+    }
+
+    /// Retrieves a decryption key for `timestamp` and embargo `not_before`, authenticated using a
+    /// signed session result.
+    pub async fn request_decryption_key<K>(
+        &self,
+        timestamp: u64,
+        not_before: Option<u64>,
+        auth: &str,
+    ) -> Result<KeyResponse<UserSecretKey<K>>, Error>
+    where
+        K: IBKEM,
+        KeyResponse<UserSecretKey<K>>: DeserializeOwned,
+    {
+        let mut req = self
+            .client
+            .get(self.url(&format!("v2/irma/key/{timestamp}"))?)
+            .bearer_auth(auth);
+
+        if let Some(not_before) = not_before {
+            req = req.query(&[("notBefore", not_before)]);
+        }
+
+        self.cancellable(async { Ok(req.send().await?.error_for_status()?.json().await?) })
+            .await
+    }
+
+    /// Retrieves signing keys, authenticated using a signed session result.
+    pub async fn request_signing_key(
+        &self,
+        auth: &str,
+        body: &SigningKeyRequest,
+    ) -> Result<SigningKeyResponse, Error> {
+        let req = self
+            .client
+            .post(self.url("v2/irma/sign/key")?)
+            .bearer_auth(auth)
+            .json(body);
+
+        self.cancellable(async { Ok(req.send().await?.error_for_status()?.json().await?) })
+            .await
+    }
+
+    /// Polls until `attempt` yields a result, or the polling timeout passes.
+    async fn poll<T, F, Fut>(&self, mut attempt: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Option<T>, Error>>,
+    {
+        let deadline = Instant::now() + self.poll_timeout;
+
+        loop {
+            if let Some(res) = attempt().await? {
+                return Ok(res);
+            }
+
+            if Instant::now() + self.poll_interval > deadline {
+                return Err(Error::Timeout);
+            }
+
+            self.cancellable(async {
+                sleep(self.poll_interval).await;
+                Ok(())
+            })
+            .await?;
+        }
+    }
+
+    /// Waits until the session has finished, using the long-polling endpoint of the PKG.
+    ///
+    /// Returns the signed session result, or `None` if the PKG does not support long-polling.
+    async fn wait_on_session(&self, token: &SessionToken) -> Result<Option<String>, Error> {
+        // PKGs that do not describe their capabilities do not support long-polling either.
+        let supported = match self.info().await {
+            Ok(info) => info.session_wait,
+            Err(Error::Http(e)) if e.status() == Some(StatusCode::NOT_FOUND) => false,
+            Err(e) => return Err(e),
+        };
+
+        if !supported {
+            return Ok(None);
+        }
+
+        let url = self.url(&format!("v2/irma/wait/{}", token.0))?;
+
+        let res = self
+            .poll(|| async {
+                let res =
+                    self.cancellable(async { Ok(self.client.get(url.clone()).send().await?) });
+                let res = res.await?;
+
+                if res.status() == StatusCode::NOT_FOUND {
+                    return Err(Error::SessionNotFound);
+                }
+
+                let wr: SessionWaitResponse = self
+                    .cancellable(async { Ok(res.error_for_status()?.json().await?) })
+                    .await?;
+
+                Ok(is_finished(&wr.status).then_some(wr))
+            })
+            .await?;
+
+        match res.jwt {
+            Some(jwt) => Ok(Some(jwt)),
+            None => self.request_jwt(token).await.map(Some),
+        }
+    }
+
+    /// Waits until the session has finished and retrieves a decryption key for `timestamp` and
+    /// embargo `not_before`.
+    ///
+    /// The key in the response is `None` if the session was cancelled, timed out or the proof
+    /// was invalid.
+    pub async fn wait_on_decryption_key(
+        &self,
+        sd: &SessionData,
+        timestamp: u64,
+        not_before: Option<u64>,
+    ) -> Result<KeyResponse<UserSecretKey<CGWKV>>, Error> {
+        if let Some(jwt) = self.wait_on_session(&sd.token).await? {
+            return self
+                .request_decryption_key(timestamp, not_before, &jwt)
+                .await;
+        }
+
+        self.poll(|| async {
+            let jwt = self.request_jwt(&sd.token).await?;
+            let kr = self
+                .request_decryption_key(timestamp, not_before, &jwt)
+                .await?;
+
+            Ok(is_finished(&kr.status).then_some(kr))
+        })
+        .await
+    }
+
+    /// Waits until the session has finished and retrieves signing keys.
+    ///
+    /// The keys in the response are `None` if the session was cancelled, timed out or the proof
+    /// was invalid.
+    pub async fn wait_on_signing_keys(
+        &self,
+        sd: &SessionData,
+        body: &SigningKeyRequest,
+    ) -> Result<SigningKeyResponse, Error> {
+        if let Some(jwt) = self.wait_on_session(&sd.token).await? {
+            return self.request_signing_key(&jwt, body).await;
+        }
+
+        self.poll(|| async {
+            let jwt = self.request_jwt(&sd.token).await?;
+            let kr = self.request_signing_key(&jwt, body).await?;
+
+            Ok(is_finished(&kr.status).then_some(kr))
+        })
+        .await
+    }
+
+    /// Reads the header of a sealed bytestream, verified using the signing parameters of the PKG.
+    pub async fn unsealer<R>(&self, r: R) -> Result<Unsealer<R, UnsealerStreamConfig>, Error>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let parameters = self.signing_parameters().await?;
+
+        self.cancellable(async {
+            Ok(Unsealer::<R, UnsealerStreamConfig>::new(r, &parameters.public_key).await?)
+        })
+        .await
+    }
+
+    /// Decrypts a sealed bytestream for recipient `ident`, using a Yivi session to prove
+    /// possession of the attributes in `con`.
+    ///
+    /// If the recipients are anonymous, `ident` can be any recipient with the same policy as the
+    /// user, the right one is found using the retrieved key.
+    ///
+    /// Once the session is started, `on_session` is called, e.g., to show the QR code to the
+    /// user. Returns the verified identity of the sender.
+    pub async fn decrypt_with_yivi<R, W, F>(
+        &self,
+        unsealer: Unsealer<R, UnsealerStreamConfig>,
+        ident: &str,
+        con: Vec<Attribute>,
+        on_session: F,
+        w: W,
+    ) -> Result<VerificationResult, Error>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+        F: FnOnce(&SessionData),
+    {
+        let policy = unsealer
+            .header
+            .recipients
+            .get(ident)
+            .ok_or_else(|| pg_core::error::Error::UnknownIdentifier(ident.to_string()))?
+            .policy
+            .clone();
+        let (timestamp, not_before) = (policy.timestamp, policy.not_before);
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        // Do not start a session if the PKG does not issue keys for this message (yet).
+        if let Some(not_before) = not_before.filter(|&not_before| not_before > now) {
+            return Err(Error::Embargoed { not_before });
+        }
+
+        if let Some(key_validity) = self.parameters::<CGWKV>().await?.key_validity {
+            if timestamp < now.saturating_sub(key_validity) {
+                return Err(Error::KeyExpired {
+                    timestamp,
+                    key_validity,
+                });
+            }
+        }
+
+        let sd = self
+            .request_start(&IrmaAuthRequest {
+                con,
+                validity: None,
+            })
+            .await?;
+
+        on_session(&sd);
+
+        let kr = self
+            .wait_on_decryption_key(&sd, timestamp, not_before)
+            .await?;
+        let usk = Zeroizing::new(kr.key.ok_or(Error::NoKey {
+            status: kr.status,
+            proof_status: kr.proof_status,
+        })?);
+
+        // With anonymous recipients, the key may belong to another recipient with the same policy.
+        let ident = unsealer.header.identify(&policy, &usk)?;
+
+        self.cancellable(async { Ok(unsealer.unseal(&ident, &usk, w).await?) })
+            .await
+    }
+}
+
+/// Whether a session will not change status anymore.
+fn is_finished(status: &SessionStatus) -> bool {
+    matches!(
+        status,
+        SessionStatus::Done | SessionStatus::Cancelled | SessionStatus::Timeout
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::dev::ServerHandle;
+    use actix_web::http::header::{EntityTag, IfNoneMatch};
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use pg_core::ibs::gg;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Serves `app` on a local port and returns its URL.
+    fn serve<F>(app: F) -> (String, ServerHandle)
+    where
+        F: Fn(&mut web::ServiceConfig) + Clone + Send + 'static,
+    {
+        let server = HttpServer::new(move || App::new().configure(app.clone()))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+
+        let url = format!("http://{}/pkg", server.addrs()[0]);
+        let server = server.run();
+        let handle = server.handle();
+        actix_rt::spawn(server);
+
+        (url, handle)
+    }
+
+    fn signing_parameters() -> Vec<u8> {
+        let (pk, _) = gg::setup(&mut rand::thread_rng());
+
+        serde_json::to_vec(&Parameters {
+            format_version: 0x00,
+            public_key: VerifyingKey(pk),
+            key_validity: None,
+        })
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_parameters_cache() {
+        let body = signing_parameters();
+        let full = Arc::new(AtomicUsize::new(0));
+        let counter = full.clone();
+
+        let (url, _) = serve(move |cfg| {
+            let body = body.clone();
+            let counter = counter.clone();
+
+            cfg.route(
+                "/pkg/v2/sign/parameters",
+                web::get().to(move |req: HttpRequest| {
+                    let body = body.clone();
+                    let counter = counter.clone();
+
+                    async move {
+                        let etag = EntityTag::new_strong("params".to_string());
+                        match actix_web::http::header::Header::parse(&req) {
+                            Ok(IfNoneMatch::Items(tags)) if tags.iter().any(|t| t == &etag) => {
+                                HttpResponse::NotModified().finish()
+                            }
+                            _ => {
+                                counter.fetch_add(1, Ordering::SeqCst);
+                                HttpResponse::Ok()
+                                    .insert_header(actix_web::http::header::ETag(etag))
+                                    .body(body)
+                            }
+                        }
+                    }
+                }),
+            );
+        });
+
+        let client = Client::new(url).unwrap();
+        let first = client.signing_parameters().await.unwrap();
+        let second = client.signing_parameters().await.unwrap();
+
+        // The second request is answered from the cache.
+        assert_eq!(full.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_parameters_persisted() {
+        let body = Arc::new(Mutex::new(signing_parameters()));
+        let served = body.clone();
+
+        let (url, handle) = serve(move |cfg| {
+            let served = served.clone();
+            cfg.route(
+                "/pkg/v2/sign/parameters",
+                web::get().to(move || {
+                    let served = served.clone();
+                    async move { served.lock().unwrap().clone() }
+                }),
+            );
+        });
+
+        let dir = std::env::temp_dir().join(format!("pg-client-test-{}", std::process::id()));
+        let client = || {
+            Client::builder(url.clone())
+                .cache_dir(&dir)
+                .build()
+                .unwrap()
+        };
+
+        let first = client().signing_parameters().await.unwrap();
+        assert!(client().cache_file().unwrap().exists());
+
+        // The PKG suddenly serves a different key, which a new client detects.
+        *body.lock().unwrap() = signing_parameters();
+        let changed = client();
+        assert!(matches!(
+            changed.signing_parameters().await,
+            Err(Error::ParametersChanged { .. })
+        ));
+
+        // Once forgotten, the new key is trusted.
+        changed.forget_parameters().unwrap();
+        let second = changed.signing_parameters().await.unwrap();
+        assert_ne!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+
+        // The cached parameters are used while the PKG is unreachable.
+        handle.stop(true).await;
+        let offline = client().signing_parameters().await.unwrap();
+        assert_eq!(
+            serde_json::to_string(&offline).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_poll_timeout_and_cancel() {
+        let (url, _) = serve(|cfg| {
+            cfg.route(
+                "/pkg/v2/irma/jwt/{token}",
+                web::get().to(|| async { "jwt" }),
+            )
+            .route(
+                "/pkg/v2/irma/key/{timestamp}",
+                web::get().to(|| async {
+                    HttpResponse::Ok().json(serde_json::json!({ "status": "CONNECTED" }))
+                }),
+            );
+        });
+
+        let sd: SessionData = serde_json::from_value(serde_json::json!({
+            "sessionPtr": { "u": "https://irma.invalid", "irmaqr": "disclosing" },
+            "token": "token",
+        }))
+        .unwrap();
+
+        let client = Client::builder(url.clone())
+            .poll_interval(Duration::from_millis(10))
+            .poll_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            client.wait_on_decryption_key(&sd, 0, None).await,
+            Err(Error::Timeout)
+        ));
+
+        let client = Client::builder(url)
+            .poll_interval(Duration::from_millis(10))
+            .build()
+            .unwrap();
+
+        let cancel = client.cancellation_token();
+        actix_rt::spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            cancel.cancel();
+        });
+
+        assert!(matches!(
+            client.wait_on_decryption_key(&sd, 0, None).await,
+            Err(Error::Cancelled)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_long_poll() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+
+        let (url, _) = serve(move |cfg| {
+            let counter = counter.clone();
+
+            cfg.route(
+                "/pkg/v2/info",
+                web::get().to(|| async {
+                    HttpResponse::Ok().json(serde_json::json!({
+                        "version": "0.0.0",
+                        "wireVersions": [],
+                        "modes": [],
+                        "algorithms": { "kem": "CGWKV", "ibs": "GG", "symmetric": "AES-128-GCM" },
+                        "authMethods": ["jwt"],
+                        "fingerprints": { "encryption": "", "signing": "" },
+                        "production": false,
+                        "sessionWait": true,
+                    }))
+                }),
+            )
+            .route(
+                "/pkg/v2/irma/wait/{token}",
+                web::get().to(move |token: web::Path<String>| {
+                    let status = match counter.fetch_add(1, Ordering::SeqCst) {
+                        _ if token.as_str() != "token" => None,
+                        0 => Some(serde_json::json!({ "status": "CONNECTED" })),
+                        _ => Some(serde_json::json!({ "status": "DONE", "jwt": "jwt" })),
+                    };
+
+                    async move {
+                        match status {
+                            Some(status) => HttpResponse::Ok().json(status),
+                            None => HttpResponse::NotFound().finish(),
+                        }
+                    }
+                }),
+            )
+            .route(
+                "/pkg/v2/irma/key/{timestamp}",
+                web::get().to(|req: HttpRequest| async move {
+                    match req.headers().get("Authorization") {
+                        Some(auth) if auth == "Bearer jwt" => HttpResponse::Ok().json(
+                            serde_json::json!({ "status": "DONE", "proofStatus": "INVALID" }),
+                        ),
+                        _ => HttpResponse::Unauthorized().finish(),
+                    }
+                }),
+            );
+        });
+
+        let sd: SessionData = serde_json::from_value(serde_json::json!({
+            "sessionPtr": { "u": "https://irma.invalid", "irmaqr": "disclosing" },
+            "token": "token",
+        }))
+        .unwrap();
+
+        let client = Client::builder(url)
+            .poll_interval(Duration::from_millis(10))
+            .build()
+            .unwrap();
+
+        let kr = client.wait_on_decryption_key(&sd, 0, None).await.unwrap();
+        assert_eq!(kr.status, SessionStatus::Done);
+        assert!(kr.key.is_none());
+        assert_eq!(polls.load(Ordering::SeqCst), 2);
+
+        // Sessions unknown to the PKG are not mistaken for a lack of long-polling support.
+        let unknown = SessionData {
+            token: SessionToken("unknown".to_string()),
+            ..sd
+        };
+        assert!(matches!(
+            client.wait_on_decryption_key(&unknown, 0, None).await,
+            Err(Error::SessionNotFound)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_signed_parameters() {
+        use pg_core::transparency::IdentitySecretKey;
+
+        let mut rng = rand::thread_rng();
+        let identity = IdentitySecretKey::generate(&mut rng);
+        let fingerprint = identity.public_key().fingerprint();
+
+        let mut statement = || ParametersStatement {
+            format_version: 0x00,
+            public_key: PublicKey(CGWKV::setup(&mut rng).0),
+            verifying_key: VerifyingKey(gg::setup(&mut rng).0),
+            key_validity: None,
+            log: None,
+        };
+
+        // Signs the last statement, after appending all statements to a fresh log.
+        let publish = |statements: &[&ParametersStatement]| {
+            let mut log = ParametersLog::default();
+            for statement in statements {
+                log.append(statement, 0).unwrap();
+            }
+
+            let mut last = statements[statements.len() - 1].clone();
+            last.log = log.head();
+
+            (
+                serde_json::to_string(&identity.sign(&last).unwrap()).unwrap(),
+                serde_json::to_string(&log).unwrap(),
+            )
+        };
+
+        let (first, second, other) = (statement(), statement(), statement());
+        let published = Arc::new(Mutex::new(publish(&[&first])));
+        let state = published.clone();
+
+        let (url, _) = serve(move |cfg| {
+            let (signed, log) = (state.clone(), state.clone());
+
+            cfg.route(
+                "/pkg/v2/parameters/signed",
+                web::get().to(move || {
+                    let body = signed.lock().unwrap().0.clone();
+                    async move { HttpResponse::Ok().body(body) }
+                }),
+            )
+            .route(
+                "/pkg/v2/parameters/log",
+                web::get().to(move || {
+                    let body = log.lock().unwrap().1.clone();
+                    async move { HttpResponse::Ok().body(body) }
+                }),
+            );
+        });
+
+        let client = Client::builder(url.clone())
+            .identity_fingerprint(fingerprint)
+            .build()
+            .unwrap();
+
+        let parameters = client.signing_parameters().await.unwrap();
+        assert_eq!(parameters.public_key.0, first.verifying_key.0);
+
+        // A different identity is not trusted.
+        let impostor = IdentitySecretKey::generate(&mut rand::thread_rng());
+        let client_other = Client::builder(url)
+            .identity_fingerprint(impostor.public_key().fingerprint())
+            .build()
+            .unwrap();
+        assert!(matches!(
+            client_other.signing_parameters().await,
+            Err(Error::Core(pg_core::error::Error::UntrustedIdentity(_)))
+        ));
+
+        // The log is extended with new parameters.
+        *published.lock().unwrap() = publish(&[&first, &second]);
+        let parameters = client.signing_parameters().await.unwrap();
+        assert_eq!(parameters.public_key.0, second.verifying_key.0);
+
+        // The log is rewritten.
+        *published.lock().unwrap() = publish(&[&other, &second]);
+        assert!(matches!(
+            client.signing_parameters().await,
+            Err(Error::Core(pg_core::error::Error::InconsistentLog))
+        ));
+    }
+}
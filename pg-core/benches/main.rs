@@ -1,5 +1,5 @@
-use pg_core::client::rust::stream::SealerStreamConfig;
-use pg_core::client::Sealer;
+use pg_core::client::rust::stream::{SealerStreamConfig, UnsealerStreamConfig};
+use pg_core::client::{Sealer, Unsealer};
 
 use futures::executor::block_on;
 use futures::io::AllowStdIo;
@@ -27,6 +27,40 @@ fn bench_seal<Rng: RngCore + CryptoRng>(plain: &[u8], rng: &mut Rng) {
     });
 }
 
+fn bench_unseal(ct: &[u8], setup: &TestSetup) {
+    let mut input = AllowStdIo::new(Cursor::new(ct));
+    let mut output = futures::io::sink();
+
+    block_on(async {
+        Unsealer::<_, UnsealerStreamConfig>::new(&mut input, &setup.ibs_pk)
+            .await
+            .unwrap()
+            .unseal("Bob", &setup.usks[2], &mut output)
+            .await
+            .unwrap();
+    });
+}
+
+fn seal<Rng: RngCore + CryptoRng>(plain: &[u8], setup: &TestSetup, rng: &mut Rng) -> Vec<u8> {
+    let mut input = AllowStdIo::new(Cursor::new(plain));
+    let mut output = AllowStdIo::new(Vec::new());
+
+    block_on(async {
+        Sealer::<_, SealerStreamConfig>::new(
+            &setup.ibe_pk,
+            &setup.policy,
+            &setup.signing_keys[0],
+            rng,
+        )
+        .unwrap()
+        .seal(&mut input, &mut output)
+        .await
+        .unwrap();
+    });
+
+    output.into_inner()
+}
+
 fn rand_vec(length: usize) -> Vec<u8> {
     (0..length).map(|_| rand::random::<u8>()).collect()
 }
@@ -46,6 +80,69 @@ fn bench(c: &mut Criterion) {
     }
 
     group.finish();
+
+    let setup = TestSetup::new(&mut rng);
+
+    let mut group = c.benchmark_group("throughput-unseal");
+    group.sample_size(10);
+
+    for blen in [10, 14, 18, 22, 26, 30] {
+        let input = rand_vec(1 << blen);
+        let ct = seal(&input, &setup, &mut rng);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(format!("unseal {} KiB", input.len() / 1024), |b| {
+            b.iter(|| bench_unseal(&ct, &setup))
+        });
+    }
+
+    group.finish();
+
+    #[cfg(feature = "parallel")]
+    {
+        let mut group = c.benchmark_group("throughput-parallel");
+        group.sample_size(10);
+
+        let input = rand_vec(1 << 26);
+        let ct = seal(&input, &setup, &mut rng);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+
+        for workers in [1, 2, 4, 8] {
+            group.bench_function(format!("seal {workers} workers"), |b| {
+                b.iter(|| {
+                    block_on(
+                        Sealer::<_, SealerStreamConfig>::new(
+                            &setup.ibe_pk,
+                            &setup.policy,
+                            &setup.signing_keys[0],
+                            &mut rng,
+                        )
+                        .unwrap()
+                        .with_workers(workers)
+                        .seal(AllowStdIo::new(Cursor::new(&input)), futures::io::sink()),
+                    )
+                    .unwrap()
+                })
+            });
+            group.bench_function(format!("unseal {workers} workers"), |b| {
+                b.iter(|| {
+                    block_on(async {
+                        Unsealer::<_, UnsealerStreamConfig>::new(
+                            AllowStdIo::new(Cursor::new(&ct)),
+                            &setup.ibs_pk,
+                        )
+                        .await
+                        .unwrap()
+                        .with_workers(workers)
+                        .unseal("Bob", &setup.usks[2], futures::io::sink())
+                        .await
+                        .unwrap()
+                    })
+                })
+            });
+        }
+
+        group.finish();
+    }
 }
 
 criterion_group!(benches, bench);
@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pg_core::client::rust::UnsealerMemoryConfig;
+use pg_core::client::Unsealer;
+use pg_core_fuzz::{memory_sealed, setup, RECIPIENT};
+
+// Parses the decrypted payload of in-memory sealed data: the header signature of a private
+// sender, the message and its signature.
+fuzz_target!(|data: &[u8]| {
+    let Some((&private_sender, plain)) = data.split_first() else {
+        return;
+    };
+
+    let setup = setup();
+    let sealed = memory_sealed(private_sender & 1 == 1, plain);
+
+    let unsealer = Unsealer::<_, UnsealerMemoryConfig>::new(sealed, &setup.ibs_pk).unwrap();
+    let _ = unsealer.unseal(RECIPIENT, &setup.usks[2]);
+});
@@ -0,0 +1,28 @@
+#![no_main]
+
+use futures::executor::block_on;
+use libfuzzer_sys::fuzz_target;
+use pg_core::client::rust::stream::UnsealerStreamConfig;
+use pg_core::client::Unsealer;
+use pg_core_fuzz::{setup, stream_sealed, RECIPIENT};
+
+// Parses the decrypted first segment of a sealed stream: the header signature of a private
+// sender, the policy of the sender and the final signature.
+fuzz_target!(|data: &[u8]| {
+    let Some((&private_sender, plain)) = data.split_first() else {
+        return;
+    };
+
+    let setup = setup();
+    let sealed = stream_sealed(private_sender & 1 == 1, plain);
+
+    let unsealer = block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+        &sealed[..],
+        &setup.ibs_pk,
+    ))
+    .unwrap();
+    let _ = unsealer
+        .start(RECIPIENT, &setup.usks[2])
+        .unwrap()
+        .finalize();
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pg_core::client::rust::UnsealerMemoryConfig;
+use pg_core::client::Unsealer;
+use pg_core_fuzz::{setup, RECIPIENT};
+
+// Parses the preamble, header and header signature of in-memory sealed data.
+fuzz_target!(|data: &[u8]| {
+    let setup = setup();
+
+    if let Ok(unsealer) = Unsealer::<_, UnsealerMemoryConfig>::new(data, &setup.ibs_pk) {
+        let _ = unsealer.unseal(RECIPIENT, &setup.usks[2]);
+    }
+});
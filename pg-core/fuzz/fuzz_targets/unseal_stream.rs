@@ -0,0 +1,26 @@
+#![no_main]
+
+use futures::executor::block_on;
+use libfuzzer_sys::fuzz_target;
+use pg_core::client::rust::stream::UnsealerStreamConfig;
+use pg_core::client::Unsealer;
+use pg_core_fuzz::{setup, RECIPIENT};
+
+// Parses the preamble, header and header signature of a sealed stream.
+fuzz_target!(|data: &[u8]| {
+    let setup = setup();
+    let mut rest = data;
+
+    let Ok(unsealer) = block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+        &mut rest,
+        &setup.ibs_pk,
+    )) else {
+        return;
+    };
+
+    if let Ok(mut unsealer) = unsealer.start(RECIPIENT, &setup.usks[2]) {
+        if unsealer.update(rest).is_ok() {
+            let _ = unsealer.finalize();
+        }
+    }
+});
@@ -0,0 +1,160 @@
+//! Shared setup of the fuzz targets.
+//!
+//! The targets that fuzz a payload encrypt it under the key of a valid header, so that the fuzzed
+//! bytes reach the parsers behind the AEAD.
+
+use aead::stream::{NewStream, StreamBE32, StreamPrimitive};
+use aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use hkdf::Hkdf;
+use pg_core::client::rust::stream::{SealerStreamConfig, UnsealerStreamConfig};
+use pg_core::client::rust::{SealerMemoryConfig, UnsealerMemoryConfig};
+use pg_core::client::{Algorithm, Sealer, Unsealer};
+use pg_core::consts::{COMMITMENT_SIZE, KEY_SIZE, PREAMBLE_SIZE, SIG_SIZE_SIZE, STREAM_NONCE_SIZE};
+use pg_core::test::TestSetup;
+use rand::{rngs::StdRng, SeedableRng};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+/// The recipient used by all targets.
+pub const RECIPIENT: &str = "Bob";
+
+/// A deterministic test setup, shared by all runs.
+pub fn setup() -> &'static TestSetup {
+    static SETUP: OnceLock<TestSetup> = OnceLock::new();
+    SETUP.get_or_init(|| TestSetup::new(&mut StdRng::seed_from_u64(0x5047)))
+}
+
+// The preamble, header, header signature and key commitment of sealed data, and the key and nonce
+// of its payload.
+struct Template {
+    prefix: Vec<u8>,
+    aead: Aes128Gcm,
+    iv: [u8; 12],
+}
+
+impl Template {
+    fn new(sealed: &[u8], private_sender: bool, iv: [u8; 12], ss: &[u8]) -> Self {
+        let header_len =
+            u32::from_be_bytes(sealed[PREAMBLE_SIZE - 4..PREAMBLE_SIZE].try_into().unwrap());
+        let header_end = PREAMBLE_SIZE + header_len as usize;
+
+        // A private sender omits the header signature and its length.
+        let sig_end = match private_sender {
+            true => header_end,
+            false => {
+                let sig_len = u32::from_be_bytes(
+                    sealed[header_end..header_end + SIG_SIZE_SIZE]
+                        .try_into()
+                        .unwrap(),
+                );
+                header_end + SIG_SIZE_SIZE + sig_len as usize
+            }
+        };
+        let end = sig_end + COMMITMENT_SIZE;
+
+        // The key schedule of the current version, see `pg_core::consts::VERSION_V7`.
+        let salt = Sha256::digest(&sealed[PREAMBLE_SIZE..header_end]);
+        let mut key = [0u8; KEY_SIZE];
+        Hkdf::<Sha256>::new(Some(&salt), ss)
+            .expand(b"postguard aead key", &mut key)
+            .unwrap();
+
+        Template {
+            prefix: sealed[..end].to_vec(),
+            aead: Aes128Gcm::new_from_slice(&key).unwrap(),
+            iv,
+        }
+    }
+}
+
+fn memory_template(private_sender: bool) -> Template {
+    let setup = setup();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let sealer = Sealer::<_, SealerMemoryConfig>::new(
+        &setup.ibe_pk,
+        &setup.policy,
+        &setup.signing_keys[0],
+        &mut rng,
+    )
+    .unwrap();
+    let sealed = match private_sender {
+        true => sealer.with_private_sender().seal(b""),
+        false => sealer.seal(b""),
+    }
+    .unwrap();
+
+    let unsealer = Unsealer::<_, UnsealerMemoryConfig>::new(&sealed, &setup.ibs_pk).unwrap();
+    let ss = unsealer.header.recipients[RECIPIENT]
+        .decaps(&setup.usks[2])
+        .unwrap();
+    let Algorithm::Aes128Gcm(iv) = unsealer.header.algo;
+
+    Template::new(&sealed, private_sender, iv.0, &ss.0)
+}
+
+fn stream_template(private_sender: bool) -> Template {
+    let setup = setup();
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut sealed = futures::io::Cursor::new(Vec::new());
+
+    let sealer = Sealer::<_, SealerStreamConfig>::new(
+        &setup.ibe_pk,
+        &setup.policy,
+        &setup.signing_keys[0],
+        &mut rng,
+    )
+    .unwrap();
+    let sealer = match private_sender {
+        true => sealer.with_private_sender(),
+        false => sealer,
+    };
+    futures::executor::block_on(sealer.seal(&b""[..], &mut sealed)).unwrap();
+    let sealed = sealed.into_inner();
+
+    let unsealer = futures::executor::block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+        &sealed[..],
+        &setup.ibs_pk,
+    ))
+    .unwrap();
+    let ss = unsealer.header.recipients[RECIPIENT]
+        .decaps(&setup.usks[2])
+        .unwrap();
+    let Algorithm::Aes128Gcm(iv) = unsealer.header.algo;
+
+    Template::new(&sealed, private_sender, iv.0, &ss.0)
+}
+
+/// Returns in-memory sealed data with a valid header, of which the payload decrypts to `plain`.
+///
+/// A private sender omits the header signature, which is then read from the payload.
+pub fn memory_sealed(private_sender: bool, plain: &[u8]) -> Vec<u8> {
+    static TEMPLATES: OnceLock<[Template; 2]> = OnceLock::new();
+    let templates = TEMPLATES.get_or_init(|| [memory_template(false), memory_template(true)]);
+    let t = &templates[private_sender as usize];
+
+    let mut out = t.prefix.clone();
+    out.extend(t.aead.encrypt(Nonce::from_slice(&t.iv), plain).unwrap());
+    out
+}
+
+/// Returns sealed stream data with a valid header, of which the single (final) segment decrypts
+/// to `plain`.
+///
+/// A private sender omits the header signature, which is then read from the first segment.
+pub fn stream_sealed(private_sender: bool, plain: &[u8]) -> Vec<u8> {
+    static TEMPLATES: OnceLock<[Template; 2]> = OnceLock::new();
+    let templates = TEMPLATES.get_or_init(|| [stream_template(false), stream_template(true)]);
+    let t = &templates[private_sender as usize];
+
+    let nonce: [u8; STREAM_NONCE_SIZE] = t.iv[..STREAM_NONCE_SIZE].try_into().unwrap();
+    let mut seg = plain.to_vec();
+    StreamBE32::from_aead(t.aead.clone(), &nonce.into())
+        .encrypt_in_place(0, true, b"", &mut seg)
+        .unwrap();
+
+    let mut out = t.prefix.clone();
+    out.extend(seg);
+    out
+}
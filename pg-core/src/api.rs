@@ -1,6 +1,7 @@
 //! Definitions of the PostGuard protocol REST API.
 
 use crate::{artifacts::SigningKeyExt, identity::Attribute};
+use alloc::string::String;
 use alloc::vec::Vec;
 use irma::{ProofStatus, SessionStatus};
 use serde::{Deserialize, Serialize};
@@ -14,6 +15,11 @@ pub struct Parameters<T> {
 
     /// The Master Public Key.
     pub public_key: T,
+
+    /// The maximum age (in seconds) of the timestamp of a policy for which the PKG issues user
+    /// secret keys, if limited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_validity: Option<u64>,
 }
 
 /// An authentication request for a IRMA identity.
@@ -26,6 +32,20 @@ pub struct IrmaAuthRequest {
     pub validity: Option<u64>,
 }
 
+/// The response of the Private Key Generator (PKG) when waiting on a session.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionWaitResponse {
+    /// The status of the session.
+    pub status: SessionStatus,
+
+    /// The signed session result, once the session is done.
+    ///
+    /// Only included if the PKG authenticates key requests using JWTs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt: Option<String>,
+}
+
 /// The key response from the Private Key Generator (PKG).
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -74,3 +94,66 @@ pub struct SigningKeyResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priv_sign_key: Option<SigningKeyExt>,
 }
+
+/// Information about the Private Key Generator (PKG), used by clients to negotiate capabilities.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    /// The version of the PKG.
+    pub version: String,
+
+    /// The supported wire format versions, see [`crate::consts`].
+    pub wire_versions: Vec<u16>,
+
+    /// The supported sealing modes, e.g., `"memory"` and `"stream"`.
+    pub modes: Vec<String>,
+
+    /// The algorithms used by the PKG.
+    pub algorithms: Algorithms,
+
+    /// The supported authentication methods, e.g., `"jwt"`.
+    pub auth_methods: Vec<String>,
+
+    /// Fingerprints of the current public parameters.
+    ///
+    /// These are equal to the ETag of the corresponding parameters endpoint.
+    pub fingerprints: Fingerprints,
+
+    /// Whether the PKG refuses to issue keys for demo attributes.
+    pub production: bool,
+
+    /// Whether clients can wait on sessions using the long-polling endpoint.
+    #[serde(default)]
+    pub session_wait: bool,
+}
+
+/// The algorithms used by the Private Key Generator (PKG).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Algorithms {
+    /// The identity-based key encapsulation mechanism.
+    pub kem: String,
+
+    /// The identity-based signature scheme.
+    pub ibs: String,
+
+    /// The symmetric authenticated encryption scheme.
+    pub symmetric: String,
+}
+
+/// Fingerprints of the public parameters of the Private Key Generator (PKG).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fingerprints {
+    /// Fingerprint of the master public key used for encryption.
+    pub encryption: String,
+
+    /// Fingerprint of the master public key used for signature verification.
+    pub signing: String,
+
+    /// Fingerprint of the identity key that signs the public parameters, if any.
+    ///
+    /// See [`crate::transparency`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
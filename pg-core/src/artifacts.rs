@@ -17,6 +17,7 @@ use ibe::{
 };
 use serde::de::{Error, SeqAccess, Visitor};
 use serde::{ser::SerializeTuple, Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 // Computes the byte length of raw bytes encoded in (padded) b64.
 // We use this to preallocate a buffer to encode into.
@@ -34,7 +35,7 @@ where
     T: AsRef<[u8]>,
 {
     if serializer.is_human_readable() {
-        let mut enc_buf = vec![0u8; b64len(val.as_ref().len())];
+        let mut enc_buf = Zeroizing::new(vec![0u8; b64len(val.as_ref().len())]);
         let encoded = Base64::encode(val.as_ref(), &mut enc_buf)
             .map_err(|e| serde::ser::Error::custom(format!("base64ct serialization error: {e}")))?;
         serializer.serialize_str(encoded)
@@ -112,10 +113,16 @@ pub(crate) fn deserialize_bin_or_b64<'de, D: Deserializer<'de>>(
 pub struct PublicKey<K: IBKEM>(pub K::Pk);
 
 /// Secret keys.
+///
+/// Since these can be copied, they are not wiped when dropped. Hold them in
+/// [`Zeroizing`] or call [`Zeroize::zeroize`] instead.
 #[derive(Debug, Clone, Copy)]
 pub struct SecretKey<K: IBKEM>(pub K::Sk);
 
 /// User secret keys.
+///
+/// The inner key can be copied, so these are not wiped when dropped. Hold them in
+/// [`Zeroizing`] or call [`Zeroize::zeroize`] instead.
 #[derive(Debug, Clone)]
 pub struct UserSecretKey<K: IBKEM>(pub K::Usk);
 
@@ -138,14 +145,14 @@ macro_rules! impl_serialize {
     ($type: ty, $inner: ty) => {
         impl Serialize for $type {
             fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-                serialize_bin_or_b64(&self.0.to_bytes(), serializer)
+                serialize_bin_or_b64(&*Zeroizing::new(self.0.to_bytes()), serializer)
             }
         }
 
         impl<'de> Deserialize<'de> for $type {
             fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-                let mut buf = [0u8; <$inner as Compress>::OUTPUT_SIZE];
-                deserialize_bin_or_b64(&mut buf, deserializer)?;
+                let mut buf = Zeroizing::new([0u8; <$inner as Compress>::OUTPUT_SIZE]);
+                deserialize_bin_or_b64(&mut *buf, deserializer)?;
 
                 let artifact = open_ct(<$inner as Compress>::from_bytes(&buf)).ok_or(
                     serde::de::Error::custom(format!("not a valid {}", stringify!($type))),
@@ -163,6 +170,44 @@ impl_serialize!(UserSecretKey<CGWKV>, <CGWKV as IBKEM>::Usk);
 impl_serialize!(Ciphertext<CGWKV>, <CGWKV as IBKEM>::Ct);
 impl_serialize!(MultiRecipientCiphertext<CGWKV>, MkemCt<CGWKV>);
 
+/// Implements [`Zeroize`] for secret key wrapper types.
+///
+/// The keys of the KEM do not implement [`Zeroize`] themselves and are [`Copy`], so instead the key
+/// is overwritten by a blank key of the same type that is decoded from `$blank`.
+macro_rules! impl_zeroize {
+    ($type: ty, $inner: ty, $blank: expr) => {
+        impl Zeroize for $type {
+            fn zeroize(&mut self) {
+                if let Some(blank) = open_ct(<$inner as Compress>::from_bytes(&$blank)) {
+                    self.0 = blank;
+                }
+
+                // Keep the overwrite from being optimized away.
+                core::hint::black_box(&mut self.0);
+            }
+        }
+    };
+}
+
+// Every scalar of the blank master secret key is zero.
+const BLANK_SK: [u8; <CGWKV as IBKEM>::SK_BYTES] = [0u8; <CGWKV as IBKEM>::SK_BYTES];
+
+// Every element of the blank user secret key is the (compressed) identity of G2.
+const BLANK_USK: [u8; <CGWKV as IBKEM>::USK_BYTES] = {
+    const G2_BYTES: usize = 96;
+
+    let mut blank = [0u8; <CGWKV as IBKEM>::USK_BYTES];
+    let mut i = 0;
+    while i < blank.len() {
+        blank[i] = 0xc0;
+        i += G2_BYTES;
+    }
+    blank
+};
+
+impl_zeroize!(SecretKey<CGWKV>, <CGWKV as IBKEM>::Sk, BLANK_SK);
+impl_zeroize!(UserSecretKey<CGWKV>, <CGWKV as IBKEM>::Usk, BLANK_USK);
+
 /// Identity-based signing key including its claims.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SigningKeyExt {
@@ -177,30 +222,40 @@ pub struct SigningKeyExt {
 }
 
 /// Identity-based signing keys.
+///
+/// The key is wiped from memory when dropped.
 #[derive(Debug, Clone)]
 pub struct SigningKey(pub ibs::gg::UserSecretKey);
 
+impl Zeroize for SigningKey {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SigningKey {}
+
 impl Serialize for SigningKey {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let bytes = bincode::serialize(&self.0).map_err(|e| {
+        let bytes = Zeroizing::new(bincode::serialize(&self.0).map_err(|e| {
             serde::ser::Error::custom(format!("could not serialize signing key: {e}"))
-        })?;
+        })?);
 
         debug_assert_eq!(bytes.len(), ibs::gg::USK_BYTES);
 
-        serialize_bin_or_b64(&bytes, serializer)
+        serialize_bin_or_b64(&*bytes, serializer)
     }
 }
 
 impl<'de> Deserialize<'de> for SigningKey {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        let mut buf = [0u8; ibs::gg::USK_BYTES];
-        deserialize_bin_or_b64(&mut buf, deserializer)?;
+        let mut buf = Zeroizing::new([0u8; ibs::gg::USK_BYTES]);
+        deserialize_bin_or_b64(&mut *buf, deserializer)?;
 
-        let usk = bincode::deserialize(&buf).map_err(|e| {
+        let usk = bincode::deserialize(&*buf).map_err(|e| {
             serde::de::Error::custom(format!("could not deserialize signing key: {e}"))
         })?;
 
@@ -394,6 +449,20 @@ mod tests {
             assert_eq!(&wrapped_mct.0.to_bytes(), &mct_decoded.0.to_bytes());
         }
 
+        #[test]
+        fn test_zeroize_keys() {
+            let KEMSetup { sk, usk, .. } = default_encryption_setup::<CGWKV>();
+
+            let mut wrapped_sk = SecretKey::<CGWKV>(sk);
+            let mut wrapped_usk = UserSecretKey::<CGWKV>(usk);
+            wrapped_sk.zeroize();
+            wrapped_usk.zeroize();
+
+            assert_eq!(wrapped_sk.0.to_bytes(), BLANK_SK);
+            assert_eq!(wrapped_usk.0.to_bytes(), BLANK_USK);
+            assert_ne!(wrapped_usk.0, usk);
+        }
+
         #[test]
         fn test_kem_regression_json() {
             let pk ="\"g2sPj5dg1SH15TwQ9k0YsFrpUJbLgxK67mGezYZeB6Zl22AnTnkmHVXv06E44Ev1qd4nsj6SK3l6O21N/J/0/zM36vZhEF56/Kyt2qd93ovZJHqPqCMmYY3pi2d2DP9vt9w/Y0T7LXOKJZLzFzyoXR+ca/quRbQrJRrvz1YZdz36ehJ1CFO63HbqZhUIE21XjL9KKdx3S91cclj337f/FXNF5uPb+6E/6oKh0VniGPArspFhQ5ca/h+k6DsVgpYXgRohU5jqT3FYtx82OewAMo+GGUEmejGgnJwF/V69y6tH16ohZ24RVetn8F8qKkeYhLfMmnWIsjAoD/TIkznRyuQOBhR6bQhNdFP3WM2Z6a6bt/y8NVqDgGhr+hBrD8los9RZAlMlNYLHQgxiSuj3k84FHdX22QpEiUpXWJttwmzLKJyP1lhhmO98+T5Em347hsvLellKAEUABl3lr+Z5Pu6/RKz0ZOsD3fxeGzbPDtxuzqc3uXbrMe0jpG5kwR8B1lLaSs/aychKaSJ5znt9zllzzXjPIuE/+UTXcjmYmO837UVMajj5pZrPOcuf9mLEBoq82irr21UBzHJawgNAStpdWVz5Ie22dDKytdeJv1S1Se4tiZfbJTpRM0FmIX8ZEyfXwehUWFS5GCnwkwkhWyzWMGeDXb2AT+OLDSH7MqWneh2KqjtW6bBEs0XcyvSLEtApi/NDfJk3OZ0/SAJb3tH2c7HN1X0l+HJxuHtT1sDzg3JVizwJwd53mKwgFAnWDkYRScKdAEv+EM0upC5YVhnUYYLfJ8YhC8RTU4W5cvg7Q9rfKonDmZLnJTr2aRo3D9CpfjUffuxBUoARSwJlH14KlI0Id91I8z/Fy1jo22x1dP7gHSl/fto+tGnbpYvj\"";
@@ -414,6 +483,7 @@ mod tests {
         use super::super::{SigningKey, VerifyingKey};
         use ibs::gg::*;
         use rand::Rng;
+        use zeroize::{Zeroize, ZeroizeOnDrop};
 
         struct SignSetup {
             pk: PublicKey,
@@ -444,6 +514,27 @@ mod tests {
             let _sk: SecretKey = bincode::deserialize(&sk).unwrap();
         }
 
+        fn assert_wiped(usk: &UserSecretKey) {
+            let bytes = bincode::serialize(usk).unwrap();
+            assert!(bytes.iter().all(|&b| b == 0));
+        }
+
+        #[test]
+        fn test_zeroize_signing_key() {
+            let mut usk = SigningKey(default_signing_setup().usk);
+            usk.zeroize();
+
+            assert_wiped(&usk.0);
+        }
+
+        #[test]
+        fn test_signing_key_drop() {
+            // Dropping a `SigningKey` wipes it using the same `zeroize` as tested above.
+            fn zeroize_on_drop<T: ZeroizeOnDrop>() {}
+
+            zeroize_on_drop::<SigningKey>();
+        }
+
         macro_rules! test_serialize {
             ($name: ident, $setup: ident, $type: tt, $ser: path, $de: path, $member: tt) => {
                 #[test]
@@ -5,6 +5,7 @@ use crate::artifacts::{MultiRecipientCiphertext, PublicKey, UserSecretKey};
 use crate::consts::*;
 use crate::error::Error;
 use crate::identity::{EncryptionPolicy, HiddenPolicy, Policy};
+use crate::util::hex;
 
 use ibe::kem::cgw_kv::CGWKV;
 use ibe::kem::mkem::MultiRecipient;
@@ -111,6 +112,11 @@ pub struct Header {
     /// The encryption mode.
     #[serde(default)]
     pub mode: Mode,
+
+    /// Whether the sender is private, in which case the header signature is only included in the
+    /// encrypted payload.
+    #[serde(default)]
+    pub private_sender: bool,
 }
 
 /// Contains header data specific to _one_ recipient.
@@ -168,6 +174,7 @@ impl Header {
                 recipients: recipient_info,
                 algo: Algorithm::new_aes128_gcm(rng),
                 mode: Mode::default(),
+                private_sender: false,
             },
             ss,
         ))
@@ -184,6 +191,65 @@ impl Header {
         self.algo = algo;
         self
     }
+
+    /// Returns the identifiers of the recipients whose hidden policy could be `policy`, i.e.,
+    /// that have the same timestamp, embargo and attribute types.
+    pub fn candidates<'a>(&'a self, policy: &HiddenPolicy) -> impl Iterator<Item = &'a str> {
+        let types = |p: &HiddenPolicy| {
+            let mut types: Vec<String> = p.con.iter().map(|a| a.atype.clone()).collect();
+            types.sort();
+            types
+        };
+        let (timestamp, not_before, atypes) = (policy.timestamp, policy.not_before, types(policy));
+
+        self.recipients
+            .iter()
+            .filter(move |(_, r)| {
+                r.policy.timestamp == timestamp
+                    && r.policy.not_before == not_before
+                    && types(&r.policy) == atypes
+            })
+            .map(|(rid, _)| rid.as_str())
+    }
+
+    /// Finds the recipient for which `usk`, a user secret key for `policy`, decapsulates.
+    ///
+    /// Only the [candidates][`Header::candidates`] for `policy` are tried. This is used to unseal
+    /// a header with [anonymous recipients][`crate::client::Sealer::with_anonymous_recipients`].
+    pub fn identify(
+        &self,
+        policy: &HiddenPolicy,
+        usk: &UserSecretKey<CGWKV>,
+    ) -> Result<String, Error> {
+        self.candidates(policy)
+            .find(|rid| self.recipients[*rid].decaps(usk).is_ok())
+            .map(String::from)
+            .ok_or(Error::NoMatchingRecipient)
+    }
+
+    // Replaces the recipient identifiers by random labels, and returns the mapping.
+    pub(crate) fn anonymize<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> BTreeMap<String, String> {
+        let mut labels = BTreeMap::new();
+
+        for (rid, r) in core::mem::take(&mut self.recipients) {
+            let label = loop {
+                let mut buf = [0u8; 16];
+                rng.fill_bytes(&mut buf);
+                let label = hex(&buf);
+                if !self.recipients.contains_key(&label) {
+                    break label;
+                }
+            };
+
+            self.recipients.insert(label.clone(), r);
+            labels.insert(rid, label);
+        }
+
+        labels
+    }
 }
 
 /// An IBS signature, extended with the identity claims.
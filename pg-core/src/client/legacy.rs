@@ -0,0 +1,228 @@
+//! Decoding of data sealed using earlier versions.
+//!
+//! Since version 3, policies carry an optional embargo, and since version 4, headers state whether
+//! the sender is private. Both change the bincode layout. Data sealed using an earlier version is
+//! therefore decoded using the types in this module, which follow the layout of that version.
+
+use super::header::{Algorithm, Header, Mode, RecipientHeader, SignatureExt};
+use super::MessageAndSignature;
+use crate::artifacts::MultiRecipientCiphertext;
+use crate::consts::{VERSION_V3, VERSION_V4};
+use crate::error::Error;
+use crate::identity::{Attribute, HiddenPolicy, Policy};
+use crate::util::deserialize_bounded;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use ibe::kem::cgw_kv::CGWKV;
+use ibs::gg::Signature;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+/// Types of which the encoding depends on the version.
+pub(crate) trait Versioned: DeserializeOwned {
+    /// The same type, in the layout of [`VERSION_V3`].
+    type V3: DeserializeOwned + Into<Self>;
+
+    /// The same type, in the layout of [`VERSION_V4`].
+    type V4: DeserializeOwned + Into<Self>;
+}
+
+/// Deserializes untrusted bincode in the layout of `version`, refusing to read more than `limit`
+/// bytes.
+pub(crate) fn deserialize_versioned<T: Versioned>(
+    bytes: &[u8],
+    version: u16,
+    limit: usize,
+) -> Result<T, Error> {
+    match version {
+        VERSION_V3 => deserialize_bounded::<T::V3>(bytes, limit).map(Into::into),
+        VERSION_V4 => deserialize_bounded::<T::V4>(bytes, limit).map(Into::into),
+        _ => deserialize_bounded(bytes, limit),
+    }
+}
+
+/// A [`Policy`] without an embargo.
+#[derive(Deserialize)]
+pub(crate) struct LegacyPolicy {
+    timestamp: u64,
+    con: Vec<Attribute>,
+}
+
+/// A [`HiddenPolicy`] without an embargo.
+#[derive(Deserialize)]
+pub(crate) struct LegacyHiddenPolicy {
+    timestamp: u64,
+    con: Vec<Attribute>,
+}
+
+/// A [`RecipientHeader`] with a [`LegacyHiddenPolicy`].
+#[derive(Deserialize)]
+pub(crate) struct LegacyRecipientHeader {
+    policy: LegacyHiddenPolicy,
+    ct: MultiRecipientCiphertext<CGWKV>,
+}
+
+/// A [`Header`] with [`LegacyRecipientHeader`]s.
+#[derive(Deserialize)]
+pub(crate) struct LegacyHeader {
+    recipients: BTreeMap<String, LegacyRecipientHeader>,
+    algo: Algorithm,
+    mode: Mode,
+}
+
+/// A [`Header`] without the private sender flag.
+#[derive(Deserialize)]
+pub(crate) struct PublicSenderHeader {
+    recipients: BTreeMap<String, RecipientHeader>,
+    algo: Algorithm,
+    mode: Mode,
+}
+
+/// A [`SignatureExt`] with a [`LegacyPolicy`].
+#[derive(Deserialize)]
+pub(crate) struct LegacySignatureExt {
+    sig: Signature,
+    pol: LegacyPolicy,
+}
+
+/// A [`MessageAndSignature`] with a [`LegacySignatureExt`].
+#[derive(Deserialize)]
+pub(crate) struct LegacyMessageAndSignature {
+    message: Vec<u8>,
+    sig: LegacySignatureExt,
+}
+
+impl From<LegacyPolicy> for Policy {
+    fn from(p: LegacyPolicy) -> Self {
+        Policy {
+            timestamp: p.timestamp,
+            con: p.con,
+            not_before: None,
+        }
+    }
+}
+
+impl From<LegacyHiddenPolicy> for HiddenPolicy {
+    fn from(p: LegacyHiddenPolicy) -> Self {
+        HiddenPolicy {
+            timestamp: p.timestamp,
+            con: p.con,
+            not_before: None,
+        }
+    }
+}
+
+impl From<LegacyRecipientHeader> for RecipientHeader {
+    fn from(r: LegacyRecipientHeader) -> Self {
+        RecipientHeader {
+            policy: r.policy.into(),
+            ct: r.ct,
+        }
+    }
+}
+
+impl From<LegacyHeader> for Header {
+    fn from(h: LegacyHeader) -> Self {
+        Header {
+            recipients: h
+                .recipients
+                .into_iter()
+                .map(|(rid, r)| (rid, r.into()))
+                .collect(),
+            algo: h.algo,
+            mode: h.mode,
+            private_sender: false,
+        }
+    }
+}
+
+impl From<PublicSenderHeader> for Header {
+    fn from(h: PublicSenderHeader) -> Self {
+        Header {
+            recipients: h.recipients,
+            algo: h.algo,
+            mode: h.mode,
+            private_sender: false,
+        }
+    }
+}
+
+impl From<LegacySignatureExt> for SignatureExt {
+    fn from(s: LegacySignatureExt) -> Self {
+        SignatureExt {
+            sig: s.sig,
+            pol: s.pol.into(),
+        }
+    }
+}
+
+impl From<LegacyMessageAndSignature> for MessageAndSignature {
+    fn from(m: LegacyMessageAndSignature) -> Self {
+        MessageAndSignature {
+            message: m.message,
+            sig: m.sig.into(),
+        }
+    }
+}
+
+impl Versioned for Policy {
+    type V3 = LegacyPolicy;
+    type V4 = Self;
+}
+
+impl Versioned for Header {
+    type V3 = LegacyHeader;
+    type V4 = PublicSenderHeader;
+}
+
+impl Versioned for SignatureExt {
+    type V3 = LegacySignatureExt;
+    type V4 = Self;
+}
+
+impl Versioned for MessageAndSignature {
+    type V3 = LegacyMessageAndSignature;
+    type V4 = Self;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{MAX_HEADER_SIZE, VERSION_V5};
+    use crate::test::TestSetup;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_legacy_header() {
+        // A header for Bob and Charlie, encoded before policies carried an embargo.
+        let bytes = include_bytes!("../../testdata/header_v3.bin");
+        let setup = TestSetup::new(&mut StdRng::seed_from_u64(0x5047));
+
+        assert!(bincode::deserialize::<Header>(bytes).is_err());
+
+        let header: Header = deserialize_versioned(bytes, VERSION_V3, MAX_HEADER_SIZE).unwrap();
+        for (rid, policy) in &setup.policy {
+            assert_eq!(header.recipients[rid].policy, policy.to_hidden());
+        }
+
+        assert!(header.recipients["Bob"].decaps(&setup.usks[2]).is_ok());
+    }
+
+    #[test]
+    fn test_public_sender_header() {
+        let setup = TestSetup::new(&mut rand::thread_rng());
+        let (header, _) =
+            Header::new(&setup.ibe_pk, &setup.policy, &mut rand::thread_rng()).unwrap();
+
+        // A header of version 3 lacks the trailing private sender flag.
+        let mut bytes = bincode::serialize(&header).unwrap();
+        assert_eq!(bytes.pop(), Some(0));
+
+        let decoded: Header = deserialize_versioned(&bytes, VERSION_V4, MAX_HEADER_SIZE).unwrap();
+        assert!(!decoded.private_sender);
+        assert_eq!(decoded.recipients.len(), header.recipients.len());
+        assert!(deserialize_versioned::<Header>(&bytes, VERSION_V5, MAX_HEADER_SIZE).is_err());
+    }
+}
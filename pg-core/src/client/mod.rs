@@ -5,6 +5,7 @@
 //! - Decrypting, verifying, unpacking metadata (*unsealing*).
 
 mod header;
+mod legacy;
 
 pub use header::{Algorithm, Header, Mode, RecipientHeader};
 
@@ -15,12 +16,25 @@ pub mod rust;
 pub mod web;
 
 use crate::artifacts::VerifyingKey;
-use crate::identity::Policy;
+use crate::error::Error;
+use crate::identity::{EncryptionPolicy, HintPolicy, Policy};
 use crate::util::*;
 use crate::{artifacts::SigningKeyExt, consts::*};
+use alloc::string::ToString;
+use alloc::vec::Vec;
 use header::SignatureExt;
+use hkdf::Hkdf;
+use ibe::kem::SS_BYTES;
 use ibs::gg::Verifier;
+use legacy::deserialize_versioned;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+// The bytes of a shared secret, which are wiped when dropped.
+type SharedSecretBytes = Zeroizing<[u8; SS_BYTES]>;
 
 /// A Sealer is used to encrypt and sign data using PostGuard.
 #[derive(Debug)]
@@ -28,6 +42,9 @@ pub struct Sealer<'r, R, C> {
     // The prebuilt header.
     header: Header,
 
+    // The recipient policies, used to rebuild the hidden policies in the header.
+    policies: EncryptionPolicy,
+
     // An exclusive reference to a random number generator.
     rng: &'r mut R,
 
@@ -41,6 +58,9 @@ pub struct Sealer<'r, R, C> {
     // An optional private signing key.
     // The signature and claims are encrypted and not visible to outsiders.
     priv_sign_key: Option<SigningKeyExt>,
+
+    // Whether to refuse attributes from the demo scheme.
+    production: bool,
 }
 
 impl<'r, R, C> Sealer<'r, R, C> {
@@ -51,6 +71,71 @@ impl<'r, R, C> Sealer<'r, R, C> {
         self.priv_sign_key = Some(priv_sign_key);
         self
     }
+
+    /// Set the rules used to hide the attribute values of the recipient policies in the header.
+    ///
+    /// Defaults to [`HintPolicy::default`].
+    pub fn with_hint_policy(mut self, hints: &HintPolicy) -> Self {
+        for (rid, policy) in self.policies.iter() {
+            if let Some(r) = self.header.recipients.get_mut(rid) {
+                r.policy = policy.to_hidden_with(hints);
+            }
+        }
+        self
+    }
+
+    /// Replace the recipient identifiers in the header by random labels.
+    ///
+    /// Otherwise, anyone holding the sealed data learns the identifiers, which are usually email
+    /// addresses. Recipients can find their entry using [`Header::identify`].
+    pub fn with_anonymous_recipients(mut self) -> Self
+    where
+        R: RngCore + CryptoRng,
+    {
+        let labels = self.header.anonymize(self.rng);
+        self.policies = core::mem::take(&mut self.policies)
+            .into_iter()
+            .map(|(rid, policy)| (labels[&rid].clone(), policy))
+            .collect();
+        self
+    }
+
+    /// Only include the header signature in the encrypted payload.
+    ///
+    /// By default, the header signature, including the public signing policy, is readable by
+    /// anyone holding the sealed data. With a private sender, no sender attributes are readable
+    /// without a user secret key, at the cost of only authenticating the header after decryption.
+    pub fn with_private_sender(mut self) -> Self {
+        self.header.private_sender = true;
+        self
+    }
+
+    /// Set production mode.
+    ///
+    /// In production mode, sealing fails with [`Error::DemoAttributeNotAllowed`] if any of the
+    /// recipient policies or signing policies contain attributes from the demo scheme.
+    pub fn with_production(mut self, production: bool) -> Self {
+        self.production = production;
+        self
+    }
+
+    // Checks the recipient and signing policies if production mode is enabled.
+    fn production_checked(&self) -> Result<(), Error> {
+        if !self.production {
+            return Ok(());
+        }
+
+        let recipients = self
+            .header
+            .recipients
+            .values()
+            .flat_map(|r| r.policy.con.iter());
+        let signers = core::iter::once(&self.pub_sign_key)
+            .chain(self.priv_sign_key.as_ref())
+            .flat_map(|k| k.policy.con.iter());
+
+        production_checked(recipients.chain(signers))
+    }
 }
 
 /// An Unsealer is used to decrypt and verify data using PostGuard.
@@ -71,7 +156,10 @@ pub struct Unsealer<R, C: UnsealerConfig> {
     pub header: Header,
 
     /// The verified public identity which was used to sign the header.
-    pub pub_id: Policy,
+    ///
+    /// This is `None` if the sender is [private][`Sealer::with_private_sender`], in which case the
+    /// public identity is only known after unsealing.
+    pub pub_id: Option<Policy>,
 
     // The input.
     r: R,
@@ -84,12 +172,67 @@ pub struct Unsealer<R, C: UnsealerConfig> {
 
     // The message verifier key.
     vk: VerifyingKey,
+
+    // Whether to refuse attributes from the demo scheme.
+    production: bool,
+
+    // The key commitment, if the version derives the AEAD key using the key schedule.
+    commitment: Option<KeyCommitment>,
+}
+
+impl<R, C: UnsealerConfig> Unsealer<R, C> {
+    /// Set production mode.
+    ///
+    /// In production mode, unsealing fails with [`Error::DemoAttributeNotAllowed`] if the
+    /// recipient policy or the sender's identity contains attributes from the demo scheme.
+    pub fn with_production(mut self, production: bool) -> Self {
+        self.production = production;
+        self
+    }
+
+    // Checks the recipient policy and the public sender identity if production mode is enabled.
+    fn production_checked(&self, ident: &str) -> Result<(), Error> {
+        if !self.production {
+            return Ok(());
+        }
+
+        let recipient = self
+            .header
+            .recipients
+            .get(ident)
+            .into_iter()
+            .flat_map(|r| r.policy.con.iter());
+
+        production_checked(recipient.chain(self.pub_id.iter().flat_map(|p| p.con.iter())))
+    }
+
+    // Derives the AEAD key from the shared secret of the recipient.
+    //
+    // Since version 6, the key commitment must match, so that all recipients decrypt the payload
+    // using the same key. Before, the key is the start of the shared secret.
+    fn payload_key(&self, ss: &SharedSecretBytes) -> Result<Zeroizing<[u8; KEY_SIZE]>, Error> {
+        let Some(commitment) = &self.commitment else {
+            let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+            key.copy_from_slice(&ss[..KEY_SIZE]);
+            return Ok(key);
+        };
+
+        let (key, tag) = key_schedule(ss, &commitment.header_hash)?;
+        if !bool::from(tag.ct_eq(&commitment.tag)) {
+            return Err(Error::IncorrectCommitment);
+        }
+
+        Ok(key)
+    }
 }
 
 /// Sender verification result.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct VerificationResult {
     /// The public signing verified claims.
+    ///
+    /// Unless the sender is [private][`Sealer::with_private_sender`], these were readable by
+    /// anyone holding the sealed data.
     pub public: Policy,
 
     /// The private signing verified claims.
@@ -97,6 +240,187 @@ pub struct VerificationResult {
     pub private: Option<Policy>,
 }
 
+// The encrypted payload of the in-memory mode.
+#[derive(Debug, Serialize, Deserialize)]
+struct MessageAndSignature {
+    message: Vec<u8>,
+    sig: SignatureExt,
+}
+
+impl VerificationResult {
+    // Combines the public identity and the identity that signed the message.
+    fn new(public: Policy, signer: Policy) -> Self {
+        let private = if public == signer { None } else { Some(signer) };
+
+        Self { public, private }
+    }
+}
+
+// Verifies a serialized header signature, returning the public identity of the sender.
+fn verified_header_sig(
+    version: u16,
+    verifier: &Verifier,
+    vk: &VerifyingKey,
+    h_sig_bytes: &[u8],
+) -> Result<Policy, Error> {
+    let h_sig_ext: SignatureExt = deserialize_versioned(h_sig_bytes, version, MAX_SIG_SIZE)?;
+    let id = h_sig_ext.pol.derive_ibs()?;
+
+    if !verifier.clone().verify(&vk.0, &h_sig_ext.sig, &id) {
+        return Err(Error::IncorrectSignature);
+    }
+
+    Ok(h_sig_ext.pol)
+}
+
+// Reads and verifies the length-prefixed header signature at the start of the decrypted payload
+// of a private sender. Returns the public identity of the sender and the number of bytes read.
+fn payload_header_sig(
+    version: u16,
+    plain: &[u8],
+    verifier: &Verifier,
+    vk: &VerifyingKey,
+    production: bool,
+) -> Result<(Policy, usize), Error> {
+    let missing = || Error::FormatViolation("header signature".to_string());
+
+    let len_bytes = plain.get(..SIG_SIZE_SIZE).ok_or_else(missing)?;
+    let len = u32::from_be_bytes(len_bytes.try_into()?) as usize;
+    if len > MAX_SIG_SIZE {
+        return Err(Error::ConstraintViolation);
+    }
+
+    let end = SIG_SIZE_SIZE.checked_add(len).ok_or_else(missing)?;
+    let h_sig_bytes = plain.get(SIG_SIZE_SIZE..end).ok_or_else(missing)?;
+
+    let pub_id = verified_header_sig(version, verifier, vk, h_sig_bytes)?;
+
+    if production {
+        production_checked(&pub_id.con)?;
+    }
+
+    Ok((pub_id, end))
+}
+
+// The HKDF info of the AEAD key.
+const KEY_INFO: &[u8] = b"postguard aead key";
+
+// The HKDF info of the key commitment.
+const COMMITMENT_INFO: &[u8] = b"postguard key commitment";
+
+// The key commitment found after the header signature, or after the header of a private sender,
+// since version 6.
+#[derive(Debug, Clone)]
+struct KeyCommitment {
+    // The hash of the raw header, which salts the key schedule.
+    header_hash: [u8; 32],
+
+    // The commitment to the shared secret.
+    tag: [u8; COMMITMENT_SIZE],
+}
+
+impl KeyCommitment {
+    fn new(header_raw: &[u8], tag: &[u8]) -> Result<Self, Error> {
+        Ok(Self {
+            header_hash: header_hash(header_raw),
+            tag: tag.try_into()?,
+        })
+    }
+}
+
+// Hashes the raw header to salt the key schedule.
+fn header_hash(header_raw: &[u8]) -> [u8; 32] {
+    Sha256::digest(header_raw).into()
+}
+
+// Derives the AEAD key and the key commitment from the shared secret (since version 6).
+//
+// Both are bound to the header through its hash. Since the commitment is derived using a separate
+// label, it reveals nothing about the AEAD key.
+fn key_schedule(
+    ss: &SharedSecretBytes,
+    header_hash: &[u8; 32],
+) -> Result<(Zeroizing<[u8; KEY_SIZE]>, [u8; COMMITMENT_SIZE]), Error> {
+    let hk = Hkdf::<Sha256>::new(Some(header_hash), &ss[..]);
+
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    let mut tag = [0u8; COMMITMENT_SIZE];
+    hk.expand(KEY_INFO, &mut key[..])
+        .and_then(|_| hk.expand(COMMITMENT_INFO, &mut tag))
+        .map_err(|_| Error::ConstraintViolation)?;
+
+    Ok((key, tag))
+}
+
+// The version, header, raw header, raw header signature of a public sender, key commitment and
+// ciphertext of sealed in-memory data.
+type SealedParts<'a> = (
+    u16,
+    Header,
+    &'a [u8],
+    Option<&'a [u8]>,
+    Option<KeyCommitment>,
+    &'a [u8],
+);
+
+// Splits sealed in-memory data into its parts.
+//
+// None of the lengths in the data are trusted, so this errors instead of reading out of bounds.
+fn split_sealed<'a>(b: &'a [u8], versions: &[u16]) -> Result<SealedParts<'a>, Error> {
+    let missing = |what: &str| Error::FormatViolation(what.to_string());
+
+    let (preamble, b) = b
+        .split_at_checked(PREAMBLE_SIZE)
+        .ok_or(Error::NotPostGuard)?;
+    let (version, header_len) = preamble_checked(preamble, versions)?;
+
+    let (header_bytes, b) = b
+        .split_at_checked(header_len)
+        .ok_or_else(|| missing("header"))?;
+    let header: Header = deserialize_versioned(header_bytes, version, MAX_HEADER_SIZE)?;
+
+    // A private sender only includes the header signature in the payload.
+    let (h_sig, b) = match header.private_sender {
+        true => (None, b),
+        false => {
+            let (h_sig_len, b) = b
+                .split_at_checked(SIG_SIZE_SIZE)
+                .ok_or_else(|| missing("no header signature length"))?;
+
+            let h_sig_len = u32::from_be_bytes(h_sig_len.try_into()?) as usize;
+            if h_sig_len > MAX_SIG_SIZE {
+                return Err(Error::ConstraintViolation);
+            }
+
+            let (h_sig, b) = b
+                .split_at_checked(h_sig_len)
+                .ok_or_else(|| missing("header signature"))?;
+            (Some(h_sig), b)
+        }
+    };
+
+    let (commitment, ct) = match version {
+        VERSION_V7 => {
+            let (tag, ct) = b
+                .split_at_checked(COMMITMENT_SIZE)
+                .ok_or_else(|| missing("key commitment"))?;
+            (Some(KeyCommitment::new(header_bytes, tag)?), ct)
+        }
+        _ => (None, b),
+    };
+
+    Ok((version, header, header_bytes, h_sig, commitment, ct))
+}
+
+// Prefixes `bytes` with their length.
+fn length_prefixed(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(SIG_SIZE_SIZE + bytes.len());
+    out.extend_from_slice(&u32::try_from(bytes.len())?.to_be_bytes());
+    out.extend_from_slice(bytes);
+
+    Ok(out)
+}
+
 /// Sealer configuration.
 ///
 /// This trait is sealed, you cannot implement it yourself.
@@ -0,0 +1,275 @@
+//! Blocking streaming mode over [`std::io`].
+//!
+//! This produces the same wire format as [`stream`][`super::stream`], but reads from a
+//! [`Read`] and writes into a [`Write`]. No async runtime is required.
+
+use crate::artifacts::{PublicKey, SigningKeyExt, UserSecretKey, VerifyingKey};
+use crate::client::rust::stream::{SealerStreamConfig, UnsealerStreamConfig, Verified};
+use crate::client::*;
+use crate::error::Error;
+use crate::identity::EncryptionPolicy;
+use ibe::kem::cgw_kv::CGWKV;
+
+use futures::executor::block_on;
+use futures::io::AllowStdIo;
+use rand::{CryptoRng, RngCore};
+use std::io::{Read, Seek, Write};
+
+/// Configures a [`Sealer`] to process a payload from a [`Read`].
+#[derive(Debug)]
+pub struct SealerBlockingConfig {
+    inner: SealerStreamConfig,
+}
+
+/// Configures an [`Unsealer`] to process a payload from a [`Read`].
+#[derive(Debug)]
+pub struct UnsealerBlockingConfig {
+    inner: UnsealerStreamConfig,
+}
+
+impl SealerConfig for SealerBlockingConfig {}
+impl UnsealerConfig for UnsealerBlockingConfig {}
+impl crate::client::sealed::SealerConfig for SealerBlockingConfig {}
+impl crate::client::sealed::UnsealerConfig for UnsealerBlockingConfig {}
+
+// Swaps the configuration of a sealer, keeping all other settings.
+fn with_config<'r, Rng, C, D>(s: Sealer<'r, Rng, C>, f: impl FnOnce(C) -> D) -> Sealer<'r, Rng, D> {
+    Sealer {
+        header: s.header,
+        policies: s.policies,
+        rng: s.rng,
+        config: f(s.config),
+        pub_sign_key: s.pub_sign_key,
+        priv_sign_key: s.priv_sign_key,
+        production: s.production,
+    }
+}
+
+impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, SealerBlockingConfig> {
+    /// Construct a new [`Sealer`] that can process payloads from a [`Read`].
+    pub fn new(
+        pk: &PublicKey<CGWKV>,
+        policies: &EncryptionPolicy,
+        pub_sign_key: &SigningKeyExt,
+        rng: &'r mut Rng,
+    ) -> Result<Self, Error> {
+        let sealer = Sealer::<Rng, SealerStreamConfig>::new(pk, policies, pub_sign_key, rng)?;
+
+        Ok(with_config(sealer, |inner| SealerBlockingConfig { inner }))
+    }
+
+    /// Optional: Add a size hint.
+    ///
+    /// This can help the receiver save some reallocations.
+    pub fn with_size_hint(self, size_hint: (u64, Option<u64>)) -> Self {
+        let sealer = with_config(self, |c| c.inner).with_size_hint(size_hint);
+        with_config(sealer, |inner| SealerBlockingConfig { inner })
+    }
+
+    /// Optional: Encrypt up to `workers` segments concurrently, each on a separate thread.
+    ///
+    /// See [`Sealer::with_workers`][`Sealer<Rng, SealerStreamConfig>::with_workers`].
+    #[cfg(feature = "parallel")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "parallel")))]
+    pub fn with_workers(self, workers: usize) -> Self {
+        let sealer = with_config(self, |c| c.inner).with_workers(workers);
+        with_config(sealer, |inner| SealerBlockingConfig { inner })
+    }
+
+    /// Seals payload data from a [`Read`] into a [`Write`].
+    pub fn seal<R: Read, W: Write>(self, r: R, w: W) -> Result<(), Error> {
+        let sealer = with_config(self, |c| c.inner);
+
+        block_on(sealer.seal(AllowStdIo::new(r), AllowStdIo::new(w)))
+    }
+}
+
+impl<R: Read> Unsealer<R, UnsealerBlockingConfig> {
+    /// Create a new [`Unsealer`] that starts reading from a [`Read`].
+    ///
+    /// Errors if the bytestream is not a legitimate PostGuard bytestream.
+    pub fn new(r: R, vk: &VerifyingKey) -> Result<Self, Error> {
+        let u = block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+            AllowStdIo::new(r),
+            vk,
+        ))?;
+
+        Ok(Self::from_stream(u))
+    }
+
+    /// Optional: Decrypt up to `workers` segments concurrently, each on a separate thread.
+    ///
+    /// See [`Unsealer::with_workers`][`Unsealer<R, UnsealerStreamConfig>::with_workers`].
+    #[cfg(feature = "parallel")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "parallel")))]
+    pub fn with_workers(self, workers: usize) -> Self {
+        Self::from_stream(self.into_stream().with_workers(workers))
+    }
+
+    /// Unseal the remaining data (which is now only payload) into a [`Write`].
+    ///
+    /// See [`Unsealer::unseal`][`Unsealer<R, UnsealerStreamConfig>::unseal`] for when `w` can
+    /// receive unverified plaintext.
+    pub fn unseal<W: Write>(
+        self,
+        ident: &str,
+        usk: &UserSecretKey<CGWKV>,
+        w: W,
+    ) -> Result<VerificationResult, Error> {
+        block_on(self.into_stream().unseal(ident, usk, AllowStdIo::new(w)))
+    }
+
+    fn from_stream(u: Unsealer<AllowStdIo<R>, UnsealerStreamConfig>) -> Self {
+        Unsealer {
+            version: u.version,
+            header: u.header,
+            pub_id: u.pub_id,
+            r: u.r.into_inner(),
+            config: UnsealerBlockingConfig { inner: u.config },
+            verifier: u.verifier,
+            vk: u.vk,
+            production: u.production,
+            commitment: u.commitment,
+        }
+    }
+
+    fn into_stream(self) -> Unsealer<AllowStdIo<R>, UnsealerStreamConfig> {
+        Unsealer {
+            version: self.version,
+            header: self.header,
+            pub_id: self.pub_id,
+            r: AllowStdIo::new(self.r),
+            config: self.config.inner,
+            verifier: self.verifier,
+            vk: self.vk,
+            production: self.production,
+            commitment: self.commitment,
+        }
+    }
+}
+
+impl<R: Read + Seek> Unsealer<R, UnsealerBlockingConfig> {
+    /// Unseal the remaining data (which is now only payload) into a [`Write`], writing nothing
+    /// before the whole payload has been verified.
+    ///
+    /// See [`Unsealer::unseal_verified`][`Unsealer<R, UnsealerStreamConfig>::unseal_verified`].
+    pub fn unseal_verified<W: Write>(
+        self,
+        ident: &str,
+        usk: &UserSecretKey<CGWKV>,
+        w: W,
+    ) -> Result<Verified, Error> {
+        block_on(
+            self.into_stream()
+                .unseal_verified(ident, usk, AllowStdIo::new(w)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SealerBlockingConfig, UnsealerBlockingConfig};
+    use crate::client::rust::stream::{SealerStreamConfig, UnsealerStreamConfig};
+    use crate::client::{Sealer, Unsealer};
+    use crate::test::TestSetup;
+    use crate::SYMMETRIC_CRYPTO_DEFAULT_CHUNK;
+    use alloc::vec::Vec;
+    use futures::{executor::block_on, io::AllowStdIo};
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use std::io::Cursor;
+
+    fn seal_blocking(setup: &TestSetup, plain: &[u8], seed: u64) -> Vec<u8> {
+        let mut ct = Vec::new();
+        Sealer::<_, SealerBlockingConfig>::new(
+            &setup.ibe_pk,
+            &setup.policy,
+            &setup.signing_keys[0],
+            &mut StdRng::seed_from_u64(seed),
+        )
+        .unwrap()
+        .seal(plain, &mut ct)
+        .unwrap();
+
+        ct
+    }
+
+    fn seal_async(setup: &TestSetup, plain: &[u8], seed: u64) -> Vec<u8> {
+        let mut ct = AllowStdIo::new(Vec::new());
+        block_on(
+            Sealer::<_, SealerStreamConfig>::new(
+                &setup.ibe_pk,
+                &setup.policy,
+                &setup.signing_keys[0],
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .unwrap()
+            .seal(AllowStdIo::new(Cursor::new(plain)), &mut ct),
+        )
+        .unwrap();
+
+        ct.into_inner()
+    }
+
+    #[test]
+    fn test_interop() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        for l in [1, SYMMETRIC_CRYPTO_DEFAULT_CHUNK + 3] {
+            let mut plain = vec![0u8; l as usize];
+            rng.fill_bytes(&mut plain);
+
+            // Both produce the exact same wire format.
+            let ct = seal_blocking(&setup, &plain, 42);
+            assert_eq!(ct, seal_async(&setup, &plain, 42));
+
+            // Blocking unsealing of a stream sealed by the async sealer.
+            let ct = seal_async(&setup, &plain, 43);
+            let mut plain2 = Vec::new();
+            let vr = Unsealer::<_, UnsealerBlockingConfig>::new(&ct[..], &setup.ibs_pk)
+                .unwrap()
+                .unseal("Bob", &setup.usks[2], &mut plain2)
+                .unwrap();
+            assert_eq!(plain2, plain);
+            assert_eq!(vr.public, setup.signing_keys[0].policy);
+
+            // Async unsealing of a stream sealed by the blocking sealer.
+            let ct = seal_blocking(&setup, &plain, 44);
+            let mut plain2 = AllowStdIo::new(Vec::new());
+            block_on(async {
+                Unsealer::<_, UnsealerStreamConfig>::new(
+                    AllowStdIo::new(Cursor::new(&ct)),
+                    &setup.ibs_pk,
+                )
+                .await
+                .unwrap()
+                .unseal("Charlie", &setup.usks[3], &mut plain2)
+                .await
+                .unwrap()
+            });
+            assert_eq!(plain2.into_inner(), plain);
+        }
+    }
+
+    #[test]
+    fn test_corrupt_blocking() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let mut ct = seal_blocking(&setup, b"SECRET DATA", 42);
+        let len = ct.len();
+        ct[len - 5] = !ct[len - 5];
+
+        let res = Unsealer::<_, UnsealerBlockingConfig>::new(&ct[..], &setup.ibs_pk)
+            .unwrap()
+            .unseal("Bob", &setup.usks[2], std::io::sink());
+        assert!(res.is_err());
+
+        let mut output = Vec::new();
+        let res = Unsealer::<_, UnsealerBlockingConfig>::new(Cursor::new(&ct), &setup.ibs_pk)
+            .unwrap()
+            .unseal_verified("Bob", &setup.usks[2], &mut output);
+        assert!(res.is_err());
+        assert!(output.is_empty());
+    }
+}
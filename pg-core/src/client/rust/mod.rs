@@ -21,10 +21,14 @@ use rand::{CryptoRng, RngCore};
 #[cfg(feature = "stream")]
 pub mod stream;
 
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod blocking;
+
 /// In-memory configuration for a [`Sealer`].
 #[derive(Debug)]
 pub struct SealerMemoryConfig {
-    key: [u8; KEY_SIZE],
+    ss: SharedSecretBytes,
     nonce: [u8; IV_SIZE],
 }
 
@@ -52,12 +56,6 @@ impl From<aes_gcm::aes::cipher::InvalidLength> for Error {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MessageAndSignature {
-    message: Vec<u8>,
-    sig: SignatureExt,
-}
-
 impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
     /// Create a new [`Sealer`].
     pub fn new(
@@ -69,26 +67,31 @@ impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
         let (header, ss) = Header::new(mpk, policies, rng)?;
         let Algorithm::Aes128Gcm(iv) = header.algo;
 
-        let mut key = [0u8; KEY_SIZE];
         let mut nonce = [0u8; IV_SIZE];
-        key.copy_from_slice(&ss.0[..KEY_SIZE]);
         nonce.copy_from_slice(&iv.0[..IV_SIZE]);
 
         Ok(Self {
             rng,
             header,
+            policies: policies.clone(),
             pub_sign_key: pub_sign_key.clone(),
             priv_sign_key: None,
-            config: SealerMemoryConfig { key, nonce },
+            production: false,
+            config: SealerMemoryConfig {
+                ss: SharedSecretBytes::new(ss.0),
+                nonce,
+            },
         })
     }
 
     /// Seals the entire payload.
     pub fn seal(mut self, message: impl AsRef<[u8]>) -> Result<Vec<u8>, Error> {
+        self.production_checked()?;
+
         let mut out = Vec::with_capacity(message.as_ref().len() + 1024);
 
         out.extend_from_slice(&PRELUDE);
-        out.extend_from_slice(&VERSION_V3.to_be_bytes());
+        out.extend_from_slice(&VERSION_V7.to_be_bytes());
 
         self.header = self.header.with_mode(Mode::InMemory {
             size: message.as_ref().len().try_into()?,
@@ -98,7 +101,7 @@ impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
         out.extend_from_slice(&u32::try_from(header_buf.len())?.to_be_bytes());
         out.extend_from_slice(&header_buf);
 
-        let signer = Signer::new().chain(header_buf);
+        let signer = Signer::new().chain(&header_buf);
         let h_sig = signer.clone().sign(&self.pub_sign_key.key.0, self.rng);
 
         let h_sig_ext = SignatureExt {
@@ -106,23 +109,35 @@ impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
             pol: self.pub_sign_key.policy.clone(),
         };
 
-        let h_sig_ext_bytes = bincode::serialize(&h_sig_ext)?;
-        out.extend_from_slice(&u32::try_from(h_sig_ext_bytes.len())?.to_be_bytes());
-        out.extend_from_slice(&h_sig_ext_bytes);
+        // A private sender only includes the header signature in the payload.
+        let h_sig_ext_bytes = length_prefixed(&bincode::serialize(&h_sig_ext)?)?;
+        let mut enc_input = match self.header.private_sender {
+            true => h_sig_ext_bytes,
+            false => {
+                out.extend_from_slice(&h_sig_ext_bytes);
+                Vec::new()
+            }
+        };
+
+        let (key, commitment) = key_schedule(&self.config.ss, &header_hash(&header_buf))?;
+        out.extend_from_slice(&commitment);
 
         let m_sig_key = self.priv_sign_key.unwrap_or(self.pub_sign_key);
         let m_sig = signer.chain(&message).sign(&m_sig_key.key.0, self.rng);
 
-        let aead = Aes128Gcm::new_from_slice(&self.config.key)?;
+        let aead = Aes128Gcm::new_from_slice(&key[..])?;
         let nonce = Nonce::from_slice(&self.config.nonce);
 
-        let enc_input = bincode::serialize(&MessageAndSignature {
-            message: message.as_ref().to_vec(),
-            sig: SignatureExt {
-                sig: m_sig,
-                pol: m_sig_key.policy,
+        bincode::serialize_into(
+            &mut enc_input,
+            &MessageAndSignature {
+                message: message.as_ref().to_vec(),
+                sig: SignatureExt {
+                    sig: m_sig,
+                    pol: m_sig_key.policy,
+                },
             },
-        })?;
+        )?;
 
         let ciphertext = aead.encrypt(nonce, enc_input.as_ref())?;
 
@@ -135,25 +150,18 @@ impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
 impl Unsealer<Vec<u8>, UnsealerMemoryConfig> {
     /// Create a new [`Unsealer`].
     pub fn new(input: impl AsRef<[u8]>, vk: &VerifyingKey) -> Result<Self, Error> {
-        let b = input.as_ref();
-        let (preamble_bytes, b) = b.split_at(PREAMBLE_SIZE);
-        let (version, header_len) = preamble_checked(preamble_bytes)?;
-
-        let (header_bytes, b) = b.split_at(header_len);
-        let (h_sig_len_bytes, b) = b.split_at(SIG_SIZE_SIZE);
-        let h_sig_len = u32::from_be_bytes(h_sig_len_bytes.try_into()?);
-        let (h_sig_bytes, ct) = b.split_at(h_sig_len as usize);
-
-        let h_sig_ext: SignatureExt = bincode::deserialize(h_sig_bytes)?;
-        let id = h_sig_ext.pol.derive_ibs()?;
+        let (version, header, header_bytes, h_sig_bytes, commitment, ct) = split_sealed(
+            input.as_ref(),
+            &[VERSION_V3, VERSION_V4, VERSION_V5, VERSION_V7],
+        )?;
 
         let verifier = Verifier::default().chain(header_bytes);
 
-        if !verifier.clone().verify(&vk.0, &h_sig_ext.sig, &id) {
-            return Err(Error::IncorrectSignature);
-        }
+        // A private sender only includes the header signature in the payload.
+        let pub_id = h_sig_bytes
+            .map(|h_sig_bytes| verified_header_sig(version, &verifier, vk, h_sig_bytes))
+            .transpose()?;
 
-        let header: Header = bincode::deserialize(header_bytes)?;
         let message_len = match header.mode {
             Mode::InMemory { size } => size as usize,
             _ => return Err(Error::ModeNotSupported(header.mode)),
@@ -162,10 +170,12 @@ impl Unsealer<Vec<u8>, UnsealerMemoryConfig> {
         Ok(Self {
             version,
             header,
-            pub_id: h_sig_ext.pol,
+            pub_id,
             r: ct.to_vec(),
             verifier,
             vk: vk.clone(),
+            production: false,
+            commitment,
             config: UnsealerMemoryConfig { message_len },
         })
     }
@@ -176,23 +186,37 @@ impl Unsealer<Vec<u8>, UnsealerMemoryConfig> {
         ident: &str,
         usk: &UserSecretKey<CGWKV>,
     ) -> Result<(Vec<u8>, VerificationResult), Error> {
+        self.production_checked(ident)?;
+
         let rec_info = self
             .header
             .recipients
             .get(ident)
             .ok_or_else(|| Error::UnknownIdentifier(ident.to_string()))?;
 
-        let ss = rec_info.decaps(usk)?;
-        let key = &ss.0[..KEY_SIZE];
+        let ss = SharedSecretBytes::new(rec_info.decaps(usk)?.0);
+        let key = self.payload_key(&ss)?;
 
         let Algorithm::Aes128Gcm(iv) = self.header.algo;
 
-        let aead = Aes128Gcm::new_from_slice(key)?;
+        let aead = Aes128Gcm::new_from_slice(&key[..])?;
         let nonce = Nonce::from_slice(&iv.0);
 
         let plain = aead.decrypt(nonce, &*self.r)?;
 
-        let msg: MessageAndSignature = bincode::deserialize(&plain)?;
+        let (pub_id, start) = match self.pub_id {
+            Some(pub_id) => (pub_id, 0),
+            None => payload_header_sig(
+                self.version,
+                &plain,
+                &self.verifier,
+                &self.vk,
+                self.production,
+            )?,
+        };
+
+        let msg: MessageAndSignature =
+            deserialize_versioned(&plain[start..], self.version, plain.len())?;
         let id = msg.sig.pol.derive_ibs()?;
 
         if !self
@@ -205,26 +229,56 @@ impl Unsealer<Vec<u8>, UnsealerMemoryConfig> {
 
         debug_assert_eq!(self.config.message_len, msg.message.len());
 
-        let private = if self.pub_id == msg.sig.pol {
-            None
-        } else {
-            Some(msg.sig.pol)
-        };
+        if self.production {
+            production_checked(&msg.sig.pol.con)?;
+        }
 
-        Ok((
-            msg.message,
-            VerificationResult {
-                public: self.pub_id,
-                private,
-            },
-        ))
+        Ok((msg.message, VerificationResult::new(pub_id, msg.sig.pol)))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::identity::{Attribute, HintPolicy, HintRule};
     use crate::test::TestSetup;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn seal_helper(setup: &TestSetup, private_sender: bool) -> Vec<u8> {
+        let mut rng = rand::thread_rng();
+        let sealer = Sealer::<_, SealerMemoryConfig>::new(
+            &setup.ibe_pk,
+            &setup.policy,
+            &setup.signing_keys[0],
+            &mut rng,
+        )
+        .unwrap();
+
+        match private_sender {
+            true => sealer.with_private_sender().seal(b"SECRET DATA"),
+            false => sealer.seal(b"SECRET DATA"),
+        }
+        .unwrap()
+    }
+
+    // Returns the offset of the key commitment.
+    fn commitment_offset(sealed: &[u8]) -> usize {
+        let header_len =
+            u32::from_be_bytes(sealed[PREAMBLE_SIZE - 4..PREAMBLE_SIZE].try_into().unwrap());
+        let sig_len_offset = PREAMBLE_SIZE + header_len as usize;
+        let header: Header = bincode::deserialize(&sealed[PREAMBLE_SIZE..sig_len_offset]).unwrap();
+        if header.private_sender {
+            return sig_len_offset;
+        }
+
+        let h_sig_len = u32::from_be_bytes(
+            sealed[sig_len_offset..sig_len_offset + SIG_SIZE_SIZE]
+                .try_into()
+                .unwrap(),
+        );
+
+        sig_len_offset + SIG_SIZE_SIZE + h_sig_len as usize
+    }
 
     #[test]
     fn test_seal_memory() {
@@ -266,6 +320,115 @@ mod tests {
         assert_eq!(&verified_policy, &expected);
     }
 
+    #[test]
+    fn test_seal_hint_policy() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let hints =
+            HintPolicy::default().with_rule("pbdf.sidn-pbdf.email.email", HintRule::RevealDomain);
+
+        let input = b"SECRET DATA";
+        let sealed = Sealer::<_, SealerMemoryConfig>::new(
+            &setup.ibe_pk,
+            &setup.policy,
+            &setup.signing_keys[0],
+            &mut rng,
+        )
+        .unwrap()
+        .with_hint_policy(&hints)
+        .seal(input)
+        .unwrap();
+
+        let unsealer = Unsealer::<_, UnsealerMemoryConfig>::new(sealed, &setup.ibs_pk).unwrap();
+
+        for (rid, policy) in setup.policy.iter() {
+            assert_eq!(
+                unsealer.header.recipients[rid].policy,
+                policy.to_hidden_with(&hints)
+            );
+        }
+
+        // The hints do not affect the identity of the recipient.
+        let (original, _) = unsealer.unseal("Bob", &setup.usks[2]).unwrap();
+        assert_eq!(&input.to_vec(), &original);
+    }
+
+    #[test]
+    fn test_seal_anonymous() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let input = b"SECRET DATA";
+        let sealed = Sealer::<_, SealerMemoryConfig>::new(
+            &setup.ibe_pk,
+            &setup.policy,
+            &setup.signing_keys[0],
+            &mut rng,
+        )
+        .unwrap()
+        .with_anonymous_recipients()
+        .with_hint_policy(&HintPolicy::default())
+        .seal(input)
+        .unwrap();
+
+        let unsealer = Unsealer::<_, UnsealerMemoryConfig>::new(sealed, &setup.ibs_pk).unwrap();
+        assert_eq!(unsealer.header.recipients.len(), 2);
+        assert!(!unsealer.header.recipients.contains_key("Bob"));
+        assert!(!unsealer.header.recipients.contains_key("Charlie"));
+
+        // Bob and Charlie both have a name and email, so both are candidates.
+        let hidden = setup.policy["Bob"].to_hidden();
+        assert_eq!(unsealer.header.candidates(&hidden).count(), 2);
+
+        // Charlie's USK for only name matches no recipient.
+        assert!(matches!(
+            unsealer.header.identify(&hidden, &setup.usks[4]),
+            Err(Error::NoMatchingRecipient)
+        ));
+
+        let label = unsealer.header.identify(&hidden, &setup.usks[2]).unwrap();
+        let (original, _) = unsealer.unseal(&label, &setup.usks[2]).unwrap();
+        assert_eq!(&input.to_vec(), &original);
+    }
+
+    #[test]
+    fn test_seal_private_sender() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let input = b"SECRET DATA";
+        let sealed = Sealer::<_, SealerMemoryConfig>::new(
+            &setup.ibe_pk,
+            &setup.policy,
+            &setup.signing_keys[0],
+            &mut rng,
+        )
+        .unwrap()
+        .with_priv_signing_key(setup.signing_keys[1].clone())
+        .with_private_sender()
+        .seal(input)
+        .unwrap();
+
+        // The public identity of the sender is not readable without a key.
+        assert!(!sealed.windows(17).any(|w| w == b"alice@example.com"));
+
+        let unsealer = Unsealer::<_, UnsealerMemoryConfig>::new(sealed, &setup.ibs_pk).unwrap();
+        assert_eq!(unsealer.version, VERSION_V7);
+        assert!(unsealer.header.private_sender);
+        assert_eq!(unsealer.pub_id, None);
+
+        let (original, verified_policy) = unsealer.unseal("Bob", &setup.usks[2]).unwrap();
+        assert_eq!(&input.to_vec(), &original);
+        assert_eq!(
+            verified_policy,
+            VerificationResult {
+                public: setup.policies[0].clone(),
+                private: Some(setup.policies[1].clone()),
+            }
+        );
+    }
+
     #[test]
     fn test_seal_unseal_wrong_usk() {
         let mut rng = rand::thread_rng();
@@ -322,4 +485,172 @@ mod tests {
 
         assert!(matches!(res, Err(Error::UnknownIdentifier(_))));
     }
+
+    #[test]
+    fn test_unseal_malformed() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let sealed = Sealer::<_, SealerMemoryConfig>::new(
+            &setup.ibe_pk,
+            &setup.policy,
+            &setup.signing_keys[0],
+            &mut rng,
+        )
+        .unwrap()
+        .seal(b"SECRET DATA")
+        .unwrap();
+
+        let header_len =
+            u32::from_be_bytes(sealed[PREAMBLE_SIZE - 4..PREAMBLE_SIZE].try_into().unwrap());
+        let sig_len_offset = PREAMBLE_SIZE + header_len as usize;
+        let ct_offset = commitment_offset(&sealed) + COMMITMENT_SIZE;
+
+        // Truncated data is rejected instead of read out of bounds.
+        for len in 0..ct_offset {
+            let res = Unsealer::<_, UnsealerMemoryConfig>::new(&sealed[..len], &setup.ibs_pk);
+            assert!(res.is_err());
+        }
+
+        for len in [ct_offset, sealed.len() - 1] {
+            let res = Unsealer::<_, UnsealerMemoryConfig>::new(&sealed[..len], &setup.ibs_pk)
+                .unwrap()
+                .unseal("Bob", &setup.usks[2]);
+            assert!(matches!(res, Err(Error::Symmetric)));
+        }
+
+        // Lengths beyond the maxima are refused.
+        let mut oversized = sealed.clone();
+        oversized[sig_len_offset..sig_len_offset + SIG_SIZE_SIZE]
+            .copy_from_slice(&u32::MAX.to_be_bytes());
+        let res = Unsealer::<_, UnsealerMemoryConfig>::new(&oversized, &setup.ibs_pk);
+        assert!(matches!(res, Err(Error::ConstraintViolation)));
+
+        let mut oversized = sealed.clone();
+        oversized[PREAMBLE_SIZE - 4..PREAMBLE_SIZE].copy_from_slice(&u32::MAX.to_be_bytes());
+        let res = Unsealer::<_, UnsealerMemoryConfig>::new(&oversized, &setup.ibs_pk);
+        assert!(matches!(res, Err(Error::ConstraintViolation)));
+    }
+
+    #[test]
+    fn test_legacy_version() {
+        // Sealed by the code from before policies carried an embargo, using version 2, in which the
+        // AEAD key is taken from the shared secret directly.
+        let sealed = include_bytes!("../../../testdata/memory_v3.bin");
+        let setup = TestSetup::new(&mut StdRng::seed_from_u64(0x5047));
+
+        let unsealer = Unsealer::<_, UnsealerMemoryConfig>::new(sealed, &setup.ibs_pk).unwrap();
+        assert_eq!(unsealer.version, VERSION_V3);
+
+        let (plain, verified) = unsealer.unseal("Bob", &setup.usks[2]).unwrap();
+        assert_eq!(
+            &plain[..],
+            b"This message was sealed using the version 2 wire format."
+        );
+        assert_eq!(verified.public, setup.signing_keys[0].policy);
+
+        // New data uses the new version.
+        let sealed = seal_helper(&setup, false);
+        assert_eq!(
+            sealed[PREAMBLE_SIZE - 6..PREAMBLE_SIZE - 4],
+            VERSION_V7.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn test_key_commitment() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let sealed = seal_helper(&setup, false);
+        let offset = commitment_offset(&sealed);
+
+        // The key is checked against the commitment before decrypting.
+        let mut tampered = sealed.clone();
+        tampered[offset] ^= 0x01;
+        let res = Unsealer::<_, UnsealerMemoryConfig>::new(&tampered, &setup.ibs_pk)
+            .unwrap()
+            .unseal("Bob", &setup.usks[2]);
+        assert!(matches!(res, Err(Error::IncorrectCommitment)));
+
+        // A sender that encapsulates a different shared secret for one of the recipients cannot
+        // produce a commitment for both. With a private sender, the header is only authenticated
+        // after decryption, so the forged header itself is accepted up front.
+        let sealed = seal_helper(&setup, true);
+        let other = seal_helper(&setup, true);
+
+        let mut forged = Unsealer::<_, UnsealerMemoryConfig>::new(&sealed, &setup.ibs_pk)
+            .unwrap()
+            .header;
+        let other_header = Unsealer::<_, UnsealerMemoryConfig>::new(&other, &setup.ibs_pk)
+            .unwrap()
+            .header;
+        forged
+            .recipients
+            .insert("Charlie".into(), other_header.recipients["Charlie"].clone());
+
+        let header_buf = bincode::serialize(&forged).unwrap();
+        let offset = commitment_offset(&sealed);
+        let mut out = sealed[..PREAMBLE_SIZE - 4].to_vec();
+        out.extend_from_slice(&u32::try_from(header_buf.len()).unwrap().to_be_bytes());
+        out.extend_from_slice(&header_buf);
+
+        let ss = SharedSecretBytes::new(forged.recipients["Bob"].decaps(&setup.usks[2]).unwrap().0);
+        let (_, commitment) = key_schedule(&ss, &header_hash(&header_buf)).unwrap();
+        out.extend_from_slice(&commitment);
+        out.extend_from_slice(&sealed[offset + COMMITMENT_SIZE..]);
+
+        let res = Unsealer::<_, UnsealerMemoryConfig>::new(&out, &setup.ibs_pk)
+            .unwrap()
+            .unseal("Charlie", &setup.usks[3]);
+        assert!(matches!(res, Err(Error::IncorrectCommitment)));
+
+        // Bob passes the commitment, but the key no longer matches the original header.
+        let res = Unsealer::<_, UnsealerMemoryConfig>::new(&out, &setup.ibs_pk)
+            .unwrap()
+            .unseal("Bob", &setup.usks[2]);
+        assert!(matches!(res, Err(Error::Symmetric)));
+    }
+
+    #[test]
+    fn test_seal_production() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let pub_sign_key = &setup.signing_keys[0];
+
+        let demo = Attribute::new("irma-demo.gemeente.personalData.fullname", Some("Bob"));
+        let mut policy = setup.policy.clone();
+        policy.get_mut("Bob").unwrap().con.push(demo);
+
+        let res =
+            Sealer::<_, SealerMemoryConfig>::new(&setup.ibe_pk, &policy, pub_sign_key, &mut rng)
+                .unwrap()
+                .with_production(true)
+                .seal(b"SECRET DATA");
+
+        assert!(matches!(res, Err(Error::DemoAttributeNotAllowed(_))));
+
+        // Without production mode, demo attributes are accepted by both sides.
+        let sealed =
+            Sealer::<_, SealerMemoryConfig>::new(&setup.ibe_pk, &policy, pub_sign_key, &mut rng)
+                .unwrap()
+                .seal(b"SECRET DATA")
+                .unwrap();
+
+        let res = Unsealer::<_, UnsealerMemoryConfig>::new(&sealed, &setup.ibs_pk)
+            .unwrap()
+            .with_production(true)
+            .unseal("Bob", &setup.usks[2]);
+
+        assert!(matches!(res, Err(Error::DemoAttributeNotAllowed(_))));
+
+        // Charlie's policy does not contain demo attributes.
+        let res = Unsealer::<_, UnsealerMemoryConfig>::new(&sealed, &setup.ibs_pk)
+            .unwrap()
+            .with_production(true)
+            .unseal("Charlie", &setup.usks[3]);
+
+        assert!(res.is_ok());
+    }
 }
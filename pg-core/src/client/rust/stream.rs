@@ -9,37 +9,172 @@ use crate::identity::{EncryptionPolicy, Policy};
 use ibe::kem::cgw_kv::CGWKV;
 use ibs::gg::{Identity, Signature, Signer, Verifier, SIG_BYTES};
 
-use aead::stream::{DecryptorBE32, EncryptorBE32};
+use aead::stream::{NewStream, StreamBE32, StreamPrimitive};
 use aead::KeyInit;
 use aes_gcm::Aes128Gcm;
 use alloc::vec::Vec;
-use futures::io::{AsyncRead, AsyncWrite};
-use futures::io::{AsyncReadExt, AsyncWriteExt};
-use futures::TryFutureExt;
+use core::fmt;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures::io::{AsyncRead, AsyncSeek, AsyncWrite, SeekFrom};
+use futures::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use futures::{ready, Sink, SinkExt, TryFutureExt};
 use rand::{CryptoRng, RngCore};
 
+#[cfg(feature = "parallel")]
+mod parallel;
+
 /// Configures an [`Sealer`] to process a payload stream.
 #[derive(Debug)]
 pub struct SealerStreamConfig {
     /// Segment size.
     segment_size: u32,
-    /// AEAD key.
-    key: [u8; KEY_SIZE],
+    /// KEM shared secret, from which the AEAD key is derived.
+    ss: SharedSecretBytes,
     /// AEAD nonce.
     nonce: [u8; STREAM_NONCE_SIZE],
+    /// Number of segments processed concurrently.
+    #[cfg(feature = "parallel")]
+    workers: usize,
 }
 
 /// Configures an [`Unsealer`] to process a payload stream.
 #[derive(Debug)]
 pub struct UnsealerStreamConfig {
     segment_size: u32,
+    #[cfg(feature = "parallel")]
+    workers: usize,
 }
 
+/// The result of [`Unsealer::unseal_verified`][`Unsealer<R,
+/// UnsealerStreamConfig>::unseal_verified`].
+///
+/// Unlike the result of [`Unsealer::unseal`][`Unsealer<R, UnsealerStreamConfig>::unseal`], this
+/// guarantees that no plaintext was written before the whole payload had been verified.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Verified(pub VerificationResult);
+
 impl SealerConfig for SealerStreamConfig {}
 impl UnsealerConfig for UnsealerStreamConfig {}
 impl crate::client::sealed::SealerConfig for SealerStreamConfig {}
 impl crate::client::sealed::UnsealerConfig for UnsealerStreamConfig {}
 
+/// Seals a payload that is pushed in chunks, instead of pulled from an [`AsyncRead`].
+///
+/// Created by [`Sealer::start`][`Sealer<Rng, SealerStreamConfig>::start`]. The concatenation of
+/// all returned bytes is exactly the output of [`Sealer::seal`][`Sealer<Rng,
+/// SealerStreamConfig>::seal`].
+pub struct IncrementalSealer<'r, Rng> {
+    // An exclusive reference to a random number generator.
+    rng: &'r mut Rng,
+
+    // The preamble, header and header signature, until they are returned.
+    header: Vec<u8>,
+
+    // Hashes the header and all plaintext.
+    signer: Signer,
+
+    // The key used to sign the final segment.
+    signing_key: SigningKeyExt,
+
+    aead: Aes128Gcm,
+    nonce: [u8; STREAM_NONCE_SIZE],
+    segment_size: usize,
+
+    // The current segment and the start of its plaintext, behind the prefix of the first segment.
+    buf: Vec<u8>,
+    start: usize,
+
+    // The position of the current segment.
+    counter: u32,
+}
+
+/// Unseals a payload that is pushed in chunks, instead of pulled from an [`AsyncRead`].
+///
+/// Created by [`Unsealer::start`][`Unsealer<R, UnsealerStreamConfig>::start`].
+///
+/// Every byte returned by [`update`][`Self::update`] is authenticated by the AEAD, but since
+/// anyone can encrypt for a recipient, only plaintext covered by the signature of the sender is
+/// known to come from the sender. These are the first [`verified_len`][`Self::verified_len`]
+/// bytes. All plaintext is verified only once [`finalize`][`Self::finalize`] succeeds.
+///
+/// Decrypted segments are wiped from memory before they are dropped, but the returned plaintext
+/// is owned by the caller.
+///
+/// After an error, all further calls fail.
+#[derive(Clone)]
+pub struct IncrementalUnsealer {
+    version: u16,
+    pub_id: Option<Policy>,
+    verifier: Verifier,
+    vk: VerifyingKey,
+    production: bool,
+
+    aead: Aes128Gcm,
+    nonce: [u8; STREAM_NONCE_SIZE],
+
+    // The size of the buffer and of a non-final encrypted segment.
+    //
+    // Since version 5 only the final segment is signed. A full buffer then holds one segment and
+    // the start of the next, because the final segment is always shorter than the buffer.
+    bufsize: usize,
+    ct_size: usize,
+
+    buf: Vec<u8>,
+
+    // The position of the next segment to decrypt.
+    counter: u32,
+
+    // The private policy of the sender and the identity derived from it.
+    pol_id: Option<(Policy, Identity)>,
+
+    // The number of plaintext bytes released, and the number of those covered by a signature.
+    released: u64,
+    verified: u64,
+
+    failed: bool,
+
+    // The number of segments decrypted concurrently by [`Unsealer::unseal`].
+    #[cfg(feature = "parallel")]
+    workers: usize,
+}
+
+/// A [`Sink`] that seals all chunks sent into it and sends the sealed bytes into another sink.
+///
+/// Created by [`IncrementalSealer::into_sink`]. Closing this sink finalizes the payload.
+#[derive(Debug)]
+pub struct SealerSink<'r, Rng, Si> {
+    sealer: Option<IncrementalSealer<'r, Rng>>,
+    inner: Si,
+}
+
+impl<Rng> fmt::Debug for IncrementalSealer<'_, Rng> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncrementalSealer")
+            .field("segment_size", &self.segment_size)
+            .field("counter", &self.counter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for IncrementalUnsealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncrementalUnsealer")
+            .field("version", &self.version)
+            .field("pub_id", &self.pub_id)
+            .field("counter", &self.counter)
+            .field("released", &self.released)
+            .field("verified", &self.verified)
+            .finish_non_exhaustive()
+    }
+}
+
+// The STREAM nonce of a segment only depends on its position, so each segment can be processed
+// on its own.
+fn stream(aead: &Aes128Gcm, nonce: &[u8; STREAM_NONCE_SIZE]) -> StreamBE32<Aes128Gcm> {
+    StreamBE32::from_aead(aead.clone(), nonce.into())
+}
+
 impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, SealerStreamConfig> {
     /// Construct a new [`Sealer`] that can process streaming payloads.
     pub fn new(
@@ -53,21 +188,22 @@ impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, SealerStreamConfig> {
         let (segment_size, _) = stream_mode_checked(&header)?;
         let Algorithm::Aes128Gcm(iv) = header.algo;
 
-        let mut key = [0u8; KEY_SIZE];
         let mut nonce = [0u8; STREAM_NONCE_SIZE];
-
-        key.copy_from_slice(&ss.0[..KEY_SIZE]);
         nonce.copy_from_slice(&iv.0[..STREAM_NONCE_SIZE]);
 
         Ok(Sealer {
             rng,
             header,
+            policies: policies.clone(),
             pub_sign_key: pub_sign_key.clone(),
             priv_sign_key: None,
+            production: false,
             config: SealerStreamConfig {
                 segment_size,
-                key,
+                ss: SharedSecretBytes::new(ss.0),
                 nonce,
+                #[cfg(feature = "parallel")]
+                workers: 1,
             },
         })
     }
@@ -84,105 +220,239 @@ impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, SealerStreamConfig> {
         self
     }
 
-    /// Seals payload data from an [`AsyncRead`] into an [`AsyncWrite`].
-    pub async fn seal<R, W>(self, mut r: R, mut w: W) -> Result<(), Error>
-    where
-        R: AsyncRead + Unpin,
-        W: AsyncWrite + Unpin,
-    {
-        w.write_all(&PRELUDE).await?;
-        w.write_all(&VERSION_V3.to_be_bytes()).await?;
+    /// Optional: Encrypt up to `workers` segments concurrently, using as many threads.
+    ///
+    /// The threads are started once per payload. The wire format and the order of the output are
+    /// unchanged. Since at most `workers` segments are in flight, memory usage stays proportional
+    /// to `workers` times the segment size.
+    #[cfg(feature = "parallel")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "parallel")))]
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.config.workers = workers.max(1);
+        self
+    }
+
+    /// Starts sealing a payload that is pushed in chunks.
+    ///
+    /// The payload is sealed sequentially, regardless of the number of workers.
+    pub fn start(self) -> Result<IncrementalSealer<'r, Rng>, Error> {
+        self.production_checked()?;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&PRELUDE);
+        header.extend_from_slice(&VERSION_V7.to_be_bytes());
 
         let header_vec = bincode::serialize(&self.header)?;
-        w.write_all(&u32::try_from(header_vec.len())?.to_be_bytes())
-            .await?;
-        w.write_all(&header_vec).await?;
+        header.extend_from_slice(&u32::try_from(header_vec.len())?.to_be_bytes());
+        header.extend_from_slice(&header_vec);
 
-        let mut signer = Signer::default().chain(&header_vec);
+        let signer = Signer::default().chain(&header_vec);
         let header_sig = signer.clone().sign(&self.pub_sign_key.key.0, self.rng);
         let header_sig_ext = SignatureExt {
             sig: header_sig,
             pol: self.pub_sign_key.policy.clone(),
         };
-        let header_sig_bytes = bincode::serialize(&header_sig_ext)?;
+        let header_sig_bytes = length_prefixed(&bincode::serialize(&header_sig_ext)?)?;
+
+        // A private sender only includes the header signature in the first segment.
+        let header_sig_private = match self.header.private_sender {
+            true => header_sig_bytes,
+            false => {
+                header.extend_from_slice(&header_sig_bytes);
+                Vec::new()
+            }
+        };
 
-        w.write_all(&u32::try_from(header_sig_bytes.len())?.to_be_bytes())
-            .await?;
-        w.write_all(&header_sig_bytes).await?;
+        let (key, commitment) = key_schedule(&self.config.ss, &header_hash(&header_vec))?;
+        header.extend_from_slice(&commitment);
 
-        let aead = Aes128Gcm::new_from_slice(&self.config.key)?;
-        let mut enc = EncryptorBE32::from_aead(aead, &self.config.nonce.into());
+        let aead = Aes128Gcm::new_from_slice(&key[..])?;
+        let segment_size = self.config.segment_size as usize;
 
         // Check for a private signing key, otherwise fall back to the public one.
         let signing_key = self.priv_sign_key.unwrap_or(self.pub_sign_key);
 
-        let pol_bytes = bincode::serialize(&signing_key.policy)?;
-        let pol_len = pol_bytes.len();
+        // First segment: DEM.K ([h_sig_len || h_sig ||] pol_len || pol || m_0)
+        // Other segments: DEM.K (m_i)
+        // Final segment: DEM.K (m_n || sig)
+        //
+        // The STREAM construction binds each segment to its position, so only the final segment
+        // carries a signature, over the header and all plaintext.
+        let mut buf = header_sig_private;
+        buf.extend_from_slice(&length_prefixed(&bincode::serialize(&signing_key.policy)?)?);
 
-        if pol_len + POL_SIZE_SIZE > self.config.segment_size as usize {
+        if buf.len() > segment_size {
             return Err(Error::ConstraintViolation);
         }
 
-        let mut buf = vec![0; self.config.segment_size as usize + TAG_SIZE];
+        buf.reserve(segment_size + SIG_BYTES + TAG_SIZE - buf.len());
+        let start = buf.len();
 
-        buf[..POL_SIZE_SIZE].copy_from_slice(&u32::try_from(pol_len)?.to_be_bytes());
-        buf[POL_SIZE_SIZE..POL_SIZE_SIZE + pol_len].copy_from_slice(&pol_bytes);
+        Ok(IncrementalSealer {
+            rng: self.rng,
+            header,
+            signer,
+            signing_key,
+            aead,
+            nonce: self.config.nonce,
+            segment_size,
+            buf,
+            start,
+            counter: 0,
+        })
+    }
 
-        let mut buf_tail = POL_SIZE_SIZE + pol_len;
-        let mut start = buf_tail;
+    /// Seals payload data from an [`AsyncRead`] into an [`AsyncWrite`].
+    pub async fn seal<R, W>(self, r: R, mut w: W) -> Result<(), Error>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        #[cfg(feature = "parallel")]
+        let workers = self.config.workers;
+        let sealer = self.start()?;
+
+        #[cfg(feature = "parallel")]
+        match workers {
+            1 => seal_segments(sealer, r, &mut w).await?,
+            _ => parallel::seal_segments(sealer, workers, r, &mut w).await?,
+        };
+        #[cfg(not(feature = "parallel"))]
+        seal_segments(sealer, r, &mut w).await?;
 
-        // First segment: DEM.K (pol_len || pol || m_0 || sig_0 )
-        // Other segments: DEM.K (m_i || sig_0)
+        w.flush().await?;
+        w.close().await?;
 
-        let mut counter: u32 = 0;
+        Ok(())
+    }
+}
 
-        loop {
-            let read = r
-                .read(&mut buf[buf_tail..self.config.segment_size as usize])
-                .await?;
-            buf_tail += read;
-
-            if buf_tail == self.config.segment_size as usize {
-                buf.truncate(buf_tail);
-
-                signer.update(&buf[start..]);
-                let sig = signer
-                    .clone()
-                    .chain(&counter.to_be_bytes())
-                    .chain(&[0x00])
-                    .sign(&signing_key.key.0, self.rng);
-                bincode::serialize_into(&mut buf, &sig)?;
-
-                enc.encrypt_next_in_place(b"", &mut buf)?;
-
-                w.write_all(&buf).await?;
-
-                buf_tail = 0;
-                start = 0;
-                counter = counter.checked_add(1).unwrap(); // cannot fail, otherwise
-                                                           // encrypt_next_in_place would have
-                                                           // failed too.                                                // encrypt_next_in_place not failing
-            } else if read == 0 {
-                buf.truncate(buf_tail);
-
-                signer.update(&buf[start..]);
-                let sig_final = signer
-                    .chain(&counter.to_be_bytes())
-                    .chain(&[0x01])
-                    .sign(&signing_key.key.0, self.rng);
-                bincode::serialize_into(&mut buf, &sig_final)?;
-
-                enc.encrypt_last_in_place(b"", &mut buf)?;
-
-                w.write_all(&buf).await?;
-                break;
-            }
+// Reads, encrypts and writes all segments in order.
+async fn seal_segments<Rng, R, W>(
+    mut sealer: IncrementalSealer<'_, Rng>,
+    mut r: R,
+    w: &mut W,
+) -> Result<(), Error>
+where
+    Rng: RngCore + CryptoRng,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut chunk = vec![0u8; sealer.segment_size];
+
+    loop {
+        let read = r.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(w.write_all(&sealer.finalize()?).await?);
         }
 
-        w.flush().await?;
-        w.close().await?;
+        w.write_all(&sealer.update(&chunk[..read])?).await?;
+    }
+}
 
-        Ok(())
+impl<'r, Rng: RngCore + CryptoRng> IncrementalSealer<'r, Rng> {
+    /// Seals the next chunk of the payload.
+    ///
+    /// Returns the sealed bytes that are complete so far, which may be empty.
+    pub fn update(&mut self, mut data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut out = core::mem::take(&mut self.header);
+
+        while let Some((position, mut seg)) = self.next_segment(&mut data)? {
+            stream(&self.aead, &self.nonce).encrypt_in_place(position, false, b"", &mut seg)?;
+            out.extend_from_slice(&seg);
+        }
+
+        Ok(out)
+    }
+
+    /// Signs and seals the final segment.
+    ///
+    /// Returns the remaining sealed bytes.
+    pub fn finalize(mut self) -> Result<Vec<u8>, Error> {
+        let mut out = core::mem::take(&mut self.header);
+        let mut seg = core::mem::take(&mut self.buf);
+
+        self.signer.update(&seg[self.start..]);
+        let sig_final = self
+            .signer
+            .chain(self.counter.to_be_bytes())
+            .chain([0x01])
+            .sign(&self.signing_key.key.0, self.rng);
+        bincode::serialize_into(&mut seg, &sig_final)?;
+
+        stream(&self.aead, &self.nonce).encrypt_in_place(self.counter, true, b"", &mut seg)?;
+        out.extend_from_slice(&seg);
+
+        Ok(out)
+    }
+
+    /// Turns this sealer into a [`Sink`] that sends the sealed bytes into `inner`.
+    pub fn into_sink<Si>(self, inner: Si) -> SealerSink<'r, Rng, Si> {
+        SealerSink {
+            sealer: Some(self),
+            inner,
+        }
+    }
+
+    // Buffers `data` until a full non-final segment is available, which is then hashed and
+    // returned with its position, ready to be encrypted.
+    fn next_segment(&mut self, data: &mut &[u8]) -> Result<Option<(u32, Vec<u8>)>, Error> {
+        let n = (self.segment_size - self.buf.len()).min(data.len());
+        self.buf.extend_from_slice(&data[..n]);
+        *data = &data[n..];
+
+        if self.buf.len() < self.segment_size {
+            return Ok(None);
+        }
+
+        self.signer.update(&self.buf[self.start..]);
+        self.start = 0;
+
+        let next = Vec::with_capacity(self.segment_size + SIG_BYTES + TAG_SIZE);
+        let seg = core::mem::replace(&mut self.buf, next);
+
+        let position = self.counter;
+        self.counter = self.counter.checked_add(1).ok_or(Error::Symmetric)?;
+
+        Ok(Some((position, seg)))
+    }
+}
+
+impl<Rng, Si, T> Sink<T> for SealerSink<'_, Rng, Si>
+where
+    Rng: RngCore + CryptoRng,
+    Si: Sink<Vec<u8>> + Unpin,
+    Error: From<Si::Error>,
+    T: AsRef<[u8]>,
+{
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(ready!(self.get_mut().inner.poll_ready_unpin(cx))?))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Error> {
+        let this = self.get_mut();
+        let sealer = this.sealer.as_mut().ok_or(Error::ConstraintViolation)?;
+
+        Ok(this.inner.start_send_unpin(sealer.update(item.as_ref())?)?)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(ready!(self.get_mut().inner.poll_flush_unpin(cx))?))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let this = self.get_mut();
+
+        if this.sealer.is_some() {
+            ready!(this.inner.poll_ready_unpin(cx))?;
+
+            let sealer = this.sealer.take().ok_or(Error::ConstraintViolation)?;
+            this.inner.start_send_unpin(sealer.finalize()?)?;
+        }
+
+        Poll::Ready(Ok(ready!(this.inner.poll_close_unpin(cx))?))
     }
 }
 
@@ -199,7 +469,10 @@ where
             .map_err(|_e| Error::NotPostGuard)
             .await?;
 
-        let (version, header_len) = preamble_checked(&preamble)?;
+        let (version, header_len) = preamble_checked(
+            &preamble,
+            &[VERSION_V3, VERSION_V4, VERSION_V5, VERSION_V6, VERSION_V7],
+        )?;
         let mut header_raw = Vec::with_capacity(header_len);
 
         // Limit reader to not read past header
@@ -209,169 +482,413 @@ where
             .map_err(|_e| Error::ConstraintViolation)
             .await?;
 
-        let mut r = r.into_inner();
+        if header_raw.len() != header_len {
+            return Err(Error::FormatViolation("header".to_string()));
+        }
 
-        let mut header_sig_len_bytes = [0u8; SIG_SIZE_SIZE];
-        r.read_exact(&mut header_sig_len_bytes)
-            .map_err(|_e| Error::FormatViolation("no header signature length".to_string()))
-            .await?;
-        let header_sig_len = u32::from_be_bytes(header_sig_len_bytes);
+        let mut r = r.into_inner();
 
-        let mut header_sig_raw = Vec::with_capacity(header_sig_len as usize);
-        let mut r = r.take(header_sig_len as u64);
+        let header: Header = deserialize_versioned(&header_raw, version, MAX_HEADER_SIZE)?;
+        let (segment_size, _) = stream_mode_checked(&header)?;
+        let verifier = Verifier::default().chain(&header_raw);
 
-        r.read_to_end(&mut header_sig_raw).await?;
+        // A private sender only includes the header signature in the payload.
+        let pub_id = match header.private_sender {
+            true => None,
+            false => {
+                let mut header_sig_len_bytes = [0u8; SIG_SIZE_SIZE];
+                r.read_exact(&mut header_sig_len_bytes)
+                    .map_err(|_e| Error::FormatViolation("no header signature length".to_string()))
+                    .await?;
+                let header_sig_len = u32::from_be_bytes(header_sig_len_bytes) as usize;
+
+                if header_sig_len > MAX_SIG_SIZE {
+                    return Err(Error::ConstraintViolation);
+                }
 
-        let h_sig_ext: SignatureExt = bincode::deserialize(&header_sig_raw)?;
+                let mut header_sig_raw = Vec::with_capacity(header_sig_len);
+                (&mut r)
+                    .take(header_sig_len as u64)
+                    .read_to_end(&mut header_sig_raw)
+                    .await?;
 
-        let verifier = Verifier::default().chain(&header_raw);
-        let pub_id = h_sig_ext.pol.derive_ibs()?;
+                if header_sig_raw.len() != header_sig_len {
+                    return Err(Error::FormatViolation("header signature".to_string()));
+                }
 
-        if !verifier.clone().verify(&pk.0, &h_sig_ext.sig, &pub_id) {
-            return Err(Error::IncorrectSignature);
-        }
+                Some(verified_header_sig(
+                    version,
+                    &verifier,
+                    pk,
+                    &header_sig_raw,
+                )?)
+            }
+        };
 
-        let header: Header = bincode::deserialize(&header_raw)?;
-        let (segment_size, _) = stream_mode_checked(&header)?;
+        let commitment = match version {
+            VERSION_V7 => {
+                let mut tag = [0u8; COMMITMENT_SIZE];
+                r.read_exact(&mut tag)
+                    .map_err(|_e| Error::FormatViolation("key commitment".to_string()))
+                    .await?;
+                Some(KeyCommitment::new(&header_raw, &tag)?)
+            }
+            _ => None,
+        };
 
         Ok(Unsealer {
             version,
             header,
-            pub_id: h_sig_ext.pol,
-            config: UnsealerStreamConfig { segment_size },
-            r: r.into_inner(), // This (new) reader is locked to the payload.
+            pub_id,
+            config: UnsealerStreamConfig {
+                segment_size,
+                #[cfg(feature = "parallel")]
+                workers: 1,
+            },
+            r, // This reader is now at the start of the payload.
             verifier,
             vk: pk.clone(),
+            production: false,
+            commitment,
         })
     }
 
+    /// Optional: Decrypt up to `workers` segments concurrently, using as many threads.
+    ///
+    /// The threads are started once per payload. The order of the output is unchanged. Since at
+    /// most `workers` segments are in flight, memory usage stays proportional to `workers` times
+    /// the segment size.
+    #[cfg(feature = "parallel")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "parallel")))]
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.config.workers = workers.max(1);
+        self
+    }
+
+    /// Starts unsealing a payload that is pushed in chunks.
+    ///
+    /// Since [`Unsealer::new`] reads no further than the header, all bytes after the header must
+    /// be pushed into the result. For example, a header that is already in memory can be read
+    /// from a `&mut &[u8]`, which afterwards holds the start of the payload.
+    pub fn start(
+        self,
+        ident: &str,
+        usk: &UserSecretKey<CGWKV>,
+    ) -> Result<IncrementalUnsealer, Error> {
+        Ok(self.incremental(ident, usk)?.0)
+    }
+
     /// Unseal the remaining data (which is now only payload) into an [`AsyncWrite`].
+    ///
+    /// Plaintext is written as soon as its segment has been decrypted. It is authenticated by the
+    /// AEAD, but since anyone can encrypt for a recipient, it is only known to come from the
+    /// sender once unsealing succeeds. If unsealing fails, for example because the payload is
+    /// truncated, `w` holds unverified plaintext. If the input is seekable, use
+    /// [`unseal_verified`][`Self::unseal_verified`] instead to avoid this.
     pub async fn unseal<W: AsyncWrite + Unpin>(
-        mut self,
+        self,
         ident: &str,
         usk: &UserSecretKey<CGWKV>,
         mut w: W,
     ) -> Result<VerificationResult, Error> {
+        let (mut unsealer, mut r) = self.incremental(ident, usk)?;
+        unseal_segments(&mut unsealer, &mut r, &mut w).await?;
+
+        finalize_into(unsealer, &mut w).await
+    }
+
+    // Sets up decryption for the recipient, handing back the reader.
+    fn incremental(
+        self,
+        ident: &str,
+        usk: &UserSecretKey<CGWKV>,
+    ) -> Result<(IncrementalUnsealer, R), Error> {
+        self.production_checked(ident)?;
+
         let rec_info = self
             .header
             .recipients
             .get(ident)
             .ok_or_else(|| Error::UnknownIdentifier(ident.to_string()))?;
 
-        let ss = rec_info.decaps(usk)?;
-        let key = &ss.0[..KEY_SIZE];
-        let aead = Aes128Gcm::new_from_slice(key)?;
+        let ss = SharedSecretBytes::new(rec_info.decaps(usk)?.0);
+        let key = self.payload_key(&ss)?;
+        let aead = Aes128Gcm::new_from_slice(&key[..])?;
 
         let Algorithm::Aes128Gcm(iv) = self.header.algo;
-        let nonce = &iv.0[..STREAM_NONCE_SIZE];
+        let mut nonce = [0u8; STREAM_NONCE_SIZE];
+        nonce.copy_from_slice(&iv.0[..STREAM_NONCE_SIZE]);
 
-        let mut dec = DecryptorBE32::from_aead(aead, nonce.into());
+        let bufsize = self.config.segment_size as usize + SIG_BYTES + TAG_SIZE;
+        let ct_size = match self.version {
+            VERSION_V3 | VERSION_V4 | VERSION_V5 => bufsize,
+            _ => bufsize - SIG_BYTES,
+        };
 
-        let bufsize: usize = self.config.segment_size as usize + SIG_BYTES + TAG_SIZE;
-        let mut buf = vec![0u8; bufsize];
-        let mut buf_tail = 0;
-        let mut counter: u32 = 0;
-        let mut pol_id: Option<(Policy, Identity)> = None;
+        let unsealer = IncrementalUnsealer {
+            version: self.version,
+            pub_id: self.pub_id,
+            verifier: self.verifier,
+            vk: self.vk,
+            production: self.production,
+            aead,
+            nonce,
+            bufsize,
+            ct_size,
+            buf: Vec::with_capacity(bufsize),
+            counter: 0,
+            pol_id: None,
+            released: 0,
+            verified: 0,
+            failed: false,
+            #[cfg(feature = "parallel")]
+            workers: self.config.workers,
+        };
 
-        fn extract_policy(buf: &mut Vec<u8>) -> Result<Option<(Policy, Identity)>, Error> {
-            let pol_len = u32::from_be_bytes(buf[..POL_SIZE_SIZE].try_into()?) as usize;
-            let pol_bytes = &buf[POL_SIZE_SIZE..POL_SIZE_SIZE + pol_len];
-            let pol: Policy = bincode::deserialize(pol_bytes)?;
-            let id = pol.derive_ibs()?;
+        Ok((unsealer, self.r))
+    }
+}
 
-            buf.drain(..POL_SIZE_SIZE + pol_len);
+impl<R> Unsealer<R, UnsealerStreamConfig>
+where
+    R: AsyncRead + AsyncSeek + Unpin,
+{
+    /// Unseal the remaining data (which is now only payload) into an [`AsyncWrite`], writing
+    /// nothing before the whole payload has been verified.
+    ///
+    /// The payload is read twice: first it is decrypted to verify the signature, then the reader
+    /// is rewound and the payload is decrypted into `w`. Only a few segments are held in memory
+    /// at a time. If the first pass fails, nothing has been written. The second pass is verified
+    /// again, so should the payload change in between, unsealing still fails.
+    pub async fn unseal_verified<W: AsyncWrite + Unpin>(
+        self,
+        ident: &str,
+        usk: &UserSecretKey<CGWKV>,
+        mut w: W,
+    ) -> Result<Verified, Error> {
+        let (mut unsealer, mut r) = self.incremental(ident, usk)?;
+        let start = r.seek(SeekFrom::Current(0)).await?;
+
+        let mut check = unsealer.clone();
+        unseal_segments(&mut check, &mut r, &mut futures::io::sink()).await?;
+        check.finalize()?;
 
-            Ok(Some((pol, id)))
+        r.seek(SeekFrom::Start(start)).await?;
+        unseal_segments(&mut unsealer, &mut r, &mut w).await?;
+
+        Ok(Verified(finalize_into(unsealer, &mut w).await?))
+    }
+}
+
+// Writes the final segment and closes the output.
+async fn finalize_into<W: AsyncWrite + Unpin>(
+    unsealer: IncrementalUnsealer,
+    w: &mut W,
+) -> Result<VerificationResult, Error> {
+    let (m, res) = unsealer.finalize()?;
+    w.write_all(&Zeroizing::new(m)).await?;
+    w.close().await?;
+
+    Ok(res)
+}
+
+// Reads, decrypts and writes all non-final segments in order.
+async fn unseal_segments<R, W>(
+    unsealer: &mut IncrementalUnsealer,
+    r: &mut R,
+    w: &mut W,
+) -> Result<(), Error>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    #[cfg(feature = "parallel")]
+    if unsealer.workers > 1 {
+        return parallel::unseal_segments(unsealer, r, w).await;
+    }
+
+    let mut chunk = vec![0u8; unsealer.bufsize];
+
+    loop {
+        let read = r.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
         }
 
-        fn verify_segment<'a>(
-            seg: &'a [u8],
-            verifier: &mut Verifier,
-            vk: &VerifyingKey,
-            id: &Identity,
-            counter: u32,
-            is_last: bool,
-        ) -> Result<&'a [u8], Error> {
-            debug_assert!(seg.len() > SIG_BYTES);
-
-            let (m, sig_bytes) = seg.split_at(seg.len() - SIG_BYTES);
-            let sig: Signature = bincode::deserialize(sig_bytes)?;
-            verifier.update(m);
-
-            if !verifier
-                .clone()
-                .chain(&counter.to_be_bytes())
-                .chain(&[is_last as u8])
-                .verify(&vk.0, &sig, id)
-            {
-                return Err(Error::IncorrectSignature);
+        let m = Zeroizing::new(unsealer.update(&chunk[..read])?);
+        w.write_all(&m).await?;
+    }
+}
+
+impl IncrementalUnsealer {
+    /// Unseals the next chunk of the payload.
+    ///
+    /// Returns the plaintext of all segments completed so far, which may be empty. Only the first
+    /// [`verified_len`][`Self::verified_len`] bytes of all returned plaintext are verified.
+    pub fn update(&mut self, mut data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.checked(|u| {
+            // The plaintext is never longer than the ciphertext, so reserving up front avoids
+            // leaving copies of it behind in reallocated memory.
+            let mut out = Vec::with_capacity(u.buf.len() + data.len());
+
+            while let Some((position, seg)) = u.next_segment(&mut data)? {
+                let mut seg = Zeroizing::new(seg);
+                stream(&u.aead, &u.nonce).decrypt_in_place(position, false, b"", &mut *seg)?;
+                out.extend_from_slice(u.open_segment(&mut seg, position, false)?);
             }
 
-            Ok(m)
+            Ok(out)
+        })
+    }
+
+    /// Unseals the final segment and verifies the signature over the whole payload.
+    ///
+    /// Returns the remaining plaintext. Only if this succeeds is all plaintext verified.
+    pub fn finalize(mut self) -> Result<(Vec<u8>, VerificationResult), Error> {
+        let m = self.checked(|u| {
+            let mut seg = Zeroizing::new(core::mem::take(&mut u.buf));
+            stream(&u.aead, &u.nonce).decrypt_in_place(u.counter, true, b"", &mut *seg)?;
+
+            Ok(u.open_segment(&mut seg, u.counter, true)?.to_vec())
+        })?;
+
+        let pub_id = self.pub_id.ok_or(Error::IncorrectSignature)?;
+        let (pol, _) = self.pol_id.ok_or(Error::IncorrectSignature)?;
+
+        Ok((m, VerificationResult::new(pub_id, pol)))
+    }
+
+    /// Returns the number of plaintext bytes returned so far that are signed by the sender.
+    ///
+    /// Since version 5 only the final segment carries a signature, so this stays zero until
+    /// [`finalize`][`Self::finalize`].
+    pub fn verified_len(&self) -> u64 {
+        self.verified
+    }
+
+    // Refuses to continue after an error, so that no segment can be skipped.
+    fn checked<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        if self.failed {
+            return Err(Error::ConstraintViolation);
         }
 
-        loop {
-            let read = self.r.read(&mut buf[buf_tail..bufsize]).await?;
-            buf_tail += read;
+        let res = f(self);
+        self.failed = res.is_err();
 
-            if buf_tail == bufsize {
-                dec.decrypt_next_in_place(b"", &mut buf)?;
+        res
+    }
 
-                if counter == 0 {
-                    pol_id = extract_policy(&mut buf)?;
-                }
+    // Buffers `data` until a full non-final segment is available, which is then returned with its
+    // position, ready to be decrypted.
+    fn next_segment(&mut self, data: &mut &[u8]) -> Result<Option<(u32, Vec<u8>)>, Error> {
+        let n = (self.bufsize - self.buf.len()).min(data.len());
+        self.buf.extend_from_slice(&data[..n]);
+        *data = &data[n..];
 
-                let m = verify_segment(
-                    &buf,
-                    &mut self.verifier,
-                    &self.vk,
-                    &pol_id.as_ref().unwrap().1,
-                    counter,
-                    false,
-                )?;
-
-                w.write_all(m).await?;
-
-                buf_tail = 0;
-                buf.resize(bufsize, 0);
-                counter += 1;
-            } else if read == 0 {
-                buf.truncate(buf_tail);
-                dec.decrypt_last_in_place(b"", &mut buf)?;
-
-                if counter == 0 {
-                    pol_id = extract_policy(&mut buf)?;
-                }
+        if self.buf.len() < self.bufsize {
+            return Ok(None);
+        }
 
-                let m = verify_segment(
-                    &buf,
-                    &mut self.verifier,
-                    &self.vk,
-                    &pol_id.as_ref().unwrap().1,
-                    counter,
-                    true,
-                )?;
+        let mut next = Vec::with_capacity(self.bufsize);
+        next.extend_from_slice(&self.buf[self.ct_size..]);
 
-                w.write_all(m).await?;
+        let mut seg = core::mem::replace(&mut self.buf, next);
+        seg.truncate(self.ct_size);
 
-                break;
-            }
+        let position = self.counter;
+        self.counter = self.counter.checked_add(1).ok_or(Error::Symmetric)?;
+
+        Ok(Some((position, seg)))
+    }
+
+    // Authenticates a decrypted segment and returns the message it contains.
+    //
+    // Segments must be opened in order, since the verifier hashes all messages.
+    fn open_segment<'a>(
+        &mut self,
+        seg: &'a mut Vec<u8>,
+        position: u32,
+        is_last: bool,
+    ) -> Result<&'a [u8], Error> {
+        if position == 0 {
+            self.extract_header_sig(seg)?;
+            self.pol_id = Some(extract_policy(seg, self.version, self.production)?);
         }
 
-        w.close().await?;
+        let seg: &'a [u8] = seg;
 
-        let private_id = pol_id.unwrap().0;
-        let private = if self.pub_id == private_id {
-            None
-        } else {
-            Some(private_id)
-        };
+        if !is_last && self.version >= VERSION_V6 {
+            self.verifier.update(seg);
+            self.released += seg.len() as u64;
+            return Ok(seg);
+        }
 
-        Ok(VerificationResult {
-            public: self.pub_id,
-            private,
-        })
+        let id = &self.pol_id.as_ref().unwrap().1;
+        let m_len = seg
+            .len()
+            .checked_sub(SIG_BYTES)
+            .ok_or_else(|| Error::FormatViolation("segment signature".to_string()))?;
+        let (m, sig_bytes) = seg.split_at(m_len);
+        let sig: Signature = deserialize_bounded(sig_bytes, SIG_BYTES)?;
+        self.verifier.update(m);
+
+        if !self
+            .verifier
+            .clone()
+            .chain(position.to_be_bytes())
+            .chain([is_last as u8])
+            .verify(&self.vk.0, &sig, id)
+        {
+            return Err(Error::IncorrectSignature);
+        }
+
+        self.released += m.len() as u64;
+        self.verified = self.released;
+
+        Ok(m)
     }
+
+    // Reads the header signature of a private sender from the first segment.
+    fn extract_header_sig(&mut self, buf: &mut Vec<u8>) -> Result<(), Error> {
+        if self.pub_id.is_some() {
+            return Ok(());
+        }
+
+        let (pub_id, len) =
+            payload_header_sig(self.version, buf, &self.verifier, &self.vk, self.production)?;
+        buf.drain(..len);
+        self.pub_id = Some(pub_id);
+
+        Ok(())
+    }
+}
+
+// Reads the length-prefixed policy of the sender from the first segment.
+fn extract_policy(
+    buf: &mut Vec<u8>,
+    version: u16,
+    production: bool,
+) -> Result<(Policy, Identity), Error> {
+    let missing = || Error::FormatViolation("policy".to_string());
+
+    let len_bytes = buf.get(..POL_SIZE_SIZE).ok_or_else(missing)?;
+    let pol_len = u32::from_be_bytes(len_bytes.try_into()?) as usize;
+    if pol_len > MAX_POLICY_SIZE {
+        return Err(Error::ConstraintViolation);
+    }
+
+    let end = POL_SIZE_SIZE + pol_len;
+    let pol_bytes = buf.get(POL_SIZE_SIZE..end).ok_or_else(missing)?;
+    let pol: Policy = deserialize_versioned(pol_bytes, version, MAX_POLICY_SIZE)?;
+    let id = pol.derive_ibs()?;
+
+    if production {
+        production_checked(&pol.con)?;
+    }
+
+    buf.drain(..end);
+
+    Ok((pol, id))
 }
 
 #[cfg(test)]
@@ -380,11 +897,14 @@ mod tests {
     use crate::client::VerificationResult;
     use crate::error::Error;
     use crate::test::TestSetup;
-    use crate::{PREAMBLE_SIZE, SYMMETRIC_CRYPTO_DEFAULT_CHUNK, TAG_SIZE};
+    use crate::{
+        COMMITMENT_SIZE, POL_SIZE_SIZE, PREAMBLE_SIZE, SIG_SIZE_SIZE,
+        SYMMETRIC_CRYPTO_DEFAULT_CHUNK, TAG_SIZE, VERSION_V3, VERSION_V6, VERSION_V7,
+    };
     use alloc::string::String;
     use alloc::vec::Vec;
     use futures::{executor::block_on, io::AllowStdIo};
-    use rand::{thread_rng, Rng, RngCore};
+    use rand::{rngs::StdRng, thread_rng, Rng, RngCore, SeedableRng};
     use std::io::Cursor;
     use tokio::io::AsyncReadExt;
 
@@ -472,6 +992,502 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_segment_boundaries() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        // The first segment starts with the length-prefixed policy of the sender.
+        let prefix = POL_SIZE_SIZE
+            + bincode::serialize(&setup.signing_keys[0].policy)
+                .unwrap()
+                .len();
+        let chunk = SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize;
+
+        // Final segments without a message, or with an almost full one.
+        for l in [chunk - prefix, 2 * chunk - prefix, 2 * chunk - prefix - 1] {
+            seal_and_unseal(&setup, rand_vec(l));
+        }
+    }
+
+    #[test]
+    fn test_private_sender() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        for l in [1, SYMMETRIC_CRYPTO_DEFAULT_CHUNK + 3] {
+            let plain = rand_vec(l as usize);
+            let mut input = AllowStdIo::new(Cursor::new(&plain));
+            let mut ct = AllowStdIo::new(Vec::new());
+
+            block_on(async {
+                Sealer::<_, SealerStreamConfig>::new(
+                    &setup.ibe_pk,
+                    &setup.policy,
+                    &setup.signing_keys[0],
+                    &mut rng,
+                )
+                .unwrap()
+                .with_private_sender()
+                .seal(&mut input, &mut ct)
+                .await
+                .unwrap();
+            });
+
+            // The public identity of the sender is not readable without a key.
+            let mut ct = ct.into_inner();
+            assert!(!ct.windows(17).any(|w| w == b"alice@example.com"));
+
+            let (plain2, vr) = block_on(async {
+                let mut output = AllowStdIo::new(Vec::new());
+                let unsealer = Unsealer::<_, UnsealerStreamConfig>::new(
+                    AllowStdIo::new(Cursor::new(&ct)),
+                    &setup.ibs_pk,
+                )
+                .await
+                .unwrap();
+                assert_eq!(unsealer.pub_id, None);
+
+                let vr = unsealer
+                    .unseal("Bob", &setup.usks[2], &mut output)
+                    .await
+                    .unwrap();
+                (output.into_inner(), vr)
+            });
+
+            assert_eq!(&plain, &plain2);
+            assert_eq!(&vr.public, &setup.signing_keys[0].policy);
+            assert_eq!(vr.private, None);
+
+            // The header is still authenticated, albeit after decryption.
+            ct[PREAMBLE_SIZE + 2] = !ct[PREAMBLE_SIZE + 2];
+            let res = block_on(async {
+                let unsealer = Unsealer::<_, UnsealerStreamConfig>::new(
+                    AllowStdIo::new(Cursor::new(&ct)),
+                    &setup.ibs_pk,
+                )
+                .await?;
+                let mut output = AllowStdIo::new(Vec::new());
+                unsealer.unseal("Bob", &setup.usks[2], &mut output).await
+            });
+            assert!(res.is_err());
+        }
+    }
+
+    // Returns the offset of the first payload segment.
+    fn payload_offset(ct: &[u8]) -> usize {
+        let header_len =
+            u32::from_be_bytes(ct[PREAMBLE_SIZE - 4..PREAMBLE_SIZE].try_into().unwrap());
+        let sig_len_offset = PREAMBLE_SIZE + header_len as usize;
+        let sig_len = u32::from_be_bytes(
+            ct[sig_len_offset..sig_len_offset + SIG_SIZE_SIZE]
+                .try_into()
+                .unwrap(),
+        );
+
+        sig_len_offset + SIG_SIZE_SIZE + sig_len as usize + COMMITMENT_SIZE
+    }
+
+    fn try_unseal(setup: &TestSetup, ct: &[u8]) -> Result<Vec<u8>, Error> {
+        block_on(async {
+            let mut output = AllowStdIo::new(Vec::new());
+            Unsealer::<_, UnsealerStreamConfig>::new(
+                AllowStdIo::new(Cursor::new(ct)),
+                &setup.ibs_pk,
+            )
+            .await?
+            .unseal("Bob", &setup.usks[2], &mut output)
+            .await?;
+
+            Ok(output.into_inner())
+        })
+    }
+
+    #[test]
+    fn test_legacy_version() {
+        let mut rng = StdRng::seed_from_u64(0x5047);
+        let setup = TestSetup::new(&mut rng);
+
+        // Sealed using version 2 by the code from before policies carried an embargo, in which every
+        // segment is signed, and using version 5, in which the AEAD key is taken from the shared
+        // secret directly.
+        for (ct, version, expected) in [
+            (
+                &include_bytes!("../../../testdata/stream_v3.bin")[..],
+                VERSION_V3,
+                &b"This stream was sealed using the version 2 wire format."[..],
+            ),
+            (
+                &include_bytes!("../../../testdata/stream_v6.bin")[..],
+                VERSION_V6,
+                &b"This stream was sealed using the version 5 wire format."[..],
+            ),
+        ] {
+            let unsealer = block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+                AllowStdIo::new(Cursor::new(ct)),
+                &setup.ibs_pk,
+            ))
+            .unwrap();
+            assert_eq!(unsealer.version, version);
+
+            let plain = try_unseal(&setup, ct).unwrap();
+            assert_eq!(&plain[..], expected);
+        }
+
+        // New streams use the new version.
+        let ct = seal_helper(&setup, b"SECRET DATA");
+        assert_eq!(
+            ct[PREAMBLE_SIZE - 6..PREAMBLE_SIZE - 4],
+            VERSION_V7.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn test_key_commitment() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let mut ct = seal_helper(&setup, b"SECRET DATA");
+        let offset = payload_offset(&ct) - COMMITMENT_SIZE;
+        ct[offset] ^= 0x01;
+
+        assert!(matches!(
+            try_unseal(&setup, &ct),
+            Err(Error::IncorrectCommitment)
+        ));
+
+        let unsealer = block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+            AllowStdIo::new(Cursor::new(&ct[..])),
+            &setup.ibs_pk,
+        ))
+        .unwrap();
+        assert!(matches!(
+            unsealer.start("Bob", &setup.usks[2]),
+            Err(Error::IncorrectCommitment)
+        ));
+    }
+
+    #[test]
+    fn test_malformed_header() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let ct = seal_helper(&setup, b"SECRET DATA");
+        let start = payload_offset(&ct);
+
+        let new = |ct: &[u8]| {
+            block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+                AllowStdIo::new(Cursor::new(ct.to_vec())),
+                &setup.ibs_pk,
+            ))
+        };
+
+        // Truncated headers are rejected instead of read out of bounds.
+        for len in 0..start {
+            assert!(new(&ct[..len]).is_err());
+        }
+
+        // Lengths beyond the maxima are refused.
+        let header_len =
+            u32::from_be_bytes(ct[PREAMBLE_SIZE - 4..PREAMBLE_SIZE].try_into().unwrap());
+        let sig_len_offset = PREAMBLE_SIZE + header_len as usize;
+        let mut oversized = ct.clone();
+        oversized[sig_len_offset..sig_len_offset + SIG_SIZE_SIZE]
+            .copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(new(&oversized), Err(Error::ConstraintViolation)));
+    }
+
+    #[test]
+    fn test_malformed_policy() {
+        for mut buf in [
+            vec![],
+            vec![0, 0, 0],
+            vec![0, 0, 0, 8, 1, 2],
+            u32::MAX.to_be_bytes().to_vec(),
+            vec![0, 0, 0, 4, 0xff, 0xff, 0xff, 0xff],
+        ] {
+            assert!(super::extract_policy(&mut buf, VERSION_V6, false).is_err());
+        }
+    }
+
+    #[test]
+    fn test_truncate_and_reorder() {
+        let mut rng = rand::thread_rng();
+        let setup = TestSetup::new(&mut rng);
+
+        let plain = rand_vec(3 * SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize + 16);
+        let ct = seal_helper(&setup, &plain);
+        assert_eq!(try_unseal(&setup, &ct).unwrap(), plain);
+
+        let start = payload_offset(&ct);
+        let seg = SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize + TAG_SIZE;
+
+        // Only the final segment is signed.
+        assert!(ct.len() - start < 4 * seg);
+
+        // Dropping the final segment, or any segment before it.
+        for end in [start + seg, start + 2 * seg, start + 3 * seg] {
+            assert!(try_unseal(&setup, &ct[..end]).is_err());
+        }
+        let mut dropped = ct[..start + seg].to_vec();
+        dropped.extend_from_slice(&ct[start + 2 * seg..]);
+        assert!(try_unseal(&setup, &dropped).is_err());
+
+        // Swapping two segments.
+        let mut swapped = ct.clone();
+        swapped[start + seg..start + 2 * seg]
+            .copy_from_slice(&ct[start + 2 * seg..start + 3 * seg]);
+        swapped[start + 2 * seg..start + 3 * seg]
+            .copy_from_slice(&ct[start + seg..start + 2 * seg]);
+        assert!(try_unseal(&setup, &swapped).is_err());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel() {
+        let setup = TestSetup::new(&mut rand::thread_rng());
+
+        let seal = |plain: &[u8], workers: usize| {
+            let mut output = AllowStdIo::new(Vec::new());
+            block_on(
+                Sealer::<_, SealerStreamConfig>::new(
+                    &setup.ibe_pk,
+                    &setup.policy,
+                    &setup.signing_keys[0],
+                    &mut StdRng::seed_from_u64(0),
+                )
+                .unwrap()
+                .with_workers(workers)
+                .seal(AllowStdIo::new(Cursor::new(plain)), &mut output),
+            )
+            .unwrap();
+            output.into_inner()
+        };
+
+        let unseal = |ct: &[u8], workers: usize| {
+            let mut output = AllowStdIo::new(Vec::new());
+            block_on(async {
+                Unsealer::<_, UnsealerStreamConfig>::new(
+                    AllowStdIo::new(Cursor::new(ct)),
+                    &setup.ibs_pk,
+                )
+                .await?
+                .with_workers(workers)
+                .unseal("Bob", &setup.usks[2], &mut output)
+                .await
+            })
+            .map(|_| output.into_inner())
+        };
+
+        for l in LENGTHS {
+            let plain = rand_vec(*l as usize);
+
+            // The output does not depend on the number of workers.
+            let ct = seal(&plain, 3);
+            assert_eq!(ct, seal(&plain, 1));
+
+            assert_eq!(unseal(&ct, 1).unwrap(), plain);
+            assert_eq!(unseal(&ct, 3).unwrap(), plain);
+            assert_eq!(unseal(&ct, 8).unwrap(), plain);
+
+            // Truncation is still detected.
+            let start = payload_offset(&ct);
+            let seg = SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize + TAG_SIZE;
+            if ct.len() > start + seg {
+                assert!(unseal(&ct[..ct.len() - seg], 3).is_err());
+            }
+        }
+    }
+
+    fn seal_seeded(setup: &TestSetup, plain: &[u8], seed: u64) -> Vec<u8> {
+        let mut output = AllowStdIo::new(Vec::new());
+        block_on(
+            Sealer::<_, SealerStreamConfig>::new(
+                &setup.ibe_pk,
+                &setup.policy,
+                &setup.signing_keys[0],
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .unwrap()
+            .seal(AllowStdIo::new(Cursor::new(plain)), &mut output),
+        )
+        .unwrap();
+
+        output.into_inner()
+    }
+
+    #[test]
+    fn test_incremental() {
+        let setup = TestSetup::new(&mut rand::thread_rng());
+        let chunk_sizes = [7, 4096, SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize + 5];
+
+        for l in LENGTHS {
+            let plain = rand_vec(*l as usize);
+            let expected = seal_seeded(&setup, &plain, 42);
+
+            for chunk_size in chunk_sizes {
+                let mut rng = StdRng::seed_from_u64(42);
+                let mut sealer = Sealer::<_, SealerStreamConfig>::new(
+                    &setup.ibe_pk,
+                    &setup.policy,
+                    &setup.signing_keys[0],
+                    &mut rng,
+                )
+                .unwrap()
+                .start()
+                .unwrap();
+
+                let mut ct = Vec::new();
+                for chunk in plain.chunks(chunk_size) {
+                    ct.extend(sealer.update(chunk).unwrap());
+                }
+                ct.extend(sealer.finalize().unwrap());
+
+                // The incremental sealer produces the exact same bytes.
+                assert_eq!(ct, expected);
+
+                // The header is read from a slice, which then holds the start of the payload.
+                let mut rest = &ct[..];
+                let mut unsealer = block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+                    &mut rest,
+                    &setup.ibs_pk,
+                ))
+                .unwrap()
+                .start("Bob", &setup.usks[2])
+                .unwrap();
+
+                let mut plain2 = Vec::new();
+                for chunk in rest.chunks(chunk_size) {
+                    plain2.extend(unsealer.update(chunk).unwrap());
+
+                    // Only the final segment is signed.
+                    assert_eq!(unsealer.verified_len(), 0);
+                }
+
+                let (m, vr) = unsealer.finalize().unwrap();
+                plain2.extend(m);
+
+                assert_eq!(plain2, plain);
+                assert_eq!(vr.public, setup.signing_keys[0].policy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_errors() {
+        let setup = TestSetup::new(&mut rand::thread_rng());
+        let seg = SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize + TAG_SIZE;
+
+        let plain = rand_vec(3 * SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize);
+        let ct = seal_seeded(&setup, &plain, 42);
+
+        let start = |ct: &[u8]| {
+            let mut rest = ct;
+            let unsealer = block_on(Unsealer::<_, UnsealerStreamConfig>::new(
+                &mut rest,
+                &setup.ibs_pk,
+            ))
+            .unwrap()
+            .start("Bob", &setup.usks[2])
+            .unwrap();
+            (unsealer, ct.len() - rest.len())
+        };
+
+        // After a corrupt segment, no later segments are released.
+        let mut corrupt = ct.clone();
+        let offset = payload_offset(&ct) + seg + 1;
+        corrupt[offset] = !corrupt[offset];
+
+        let (mut unsealer, n) = start(&corrupt);
+        let rest = &corrupt[n..];
+        assert!(unsealer.update(&rest[..3 * seg]).is_err());
+        assert!(unsealer.update(&rest[3 * seg..]).is_err());
+        assert!(unsealer.finalize().is_err());
+
+        // Everything is released, but a truncated payload never verifies.
+        let (mut unsealer, n) = start(&ct);
+        let rest = &ct[n..];
+        let m = unsealer.update(&rest[..3 * seg]).unwrap();
+        assert_eq!(m, plain[..m.len()]);
+        assert_eq!(unsealer.verified_len(), 0);
+        assert!(unsealer.finalize().is_err());
+    }
+
+    #[test]
+    fn test_release() {
+        let setup = TestSetup::new(&mut rand::thread_rng());
+
+        let unseal = |ct: &[u8], verified: bool| {
+            let mut output = AllowStdIo::new(Vec::new());
+            let res = block_on(async {
+                let unsealer = Unsealer::<_, UnsealerStreamConfig>::new(
+                    AllowStdIo::new(Cursor::new(ct)),
+                    &setup.ibs_pk,
+                )
+                .await?;
+
+                match verified {
+                    true => Ok(unsealer
+                        .unseal_verified("Bob", &setup.usks[2], &mut output)
+                        .await?
+                        .0),
+                    false => unsealer.unseal("Bob", &setup.usks[2], &mut output).await,
+                }
+            });
+            (res, output.into_inner())
+        };
+
+        let plain = rand_vec(3 * SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize + 16);
+        let ct = seal_seeded(&setup, &plain, 42);
+
+        for verified in [false, true] {
+            let (res, output) = unseal(&ct, verified);
+            assert_eq!(res.unwrap().public, setup.signing_keys[0].policy);
+            assert_eq!(output, plain);
+        }
+
+        // Truncate the final segment.
+        let truncated = &ct[..ct.len() - SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize];
+
+        let (res, output) = unseal(truncated, false);
+        assert!(res.is_err());
+        assert!(!output.is_empty());
+
+        let (res, output) = unseal(truncated, true);
+        assert!(res.is_err());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_sink() {
+        use futures::{io::AsyncWriteExt, SinkExt};
+
+        let setup = TestSetup::new(&mut rand::thread_rng());
+        let plain = rand_vec(3 * SYMMETRIC_CRYPTO_DEFAULT_CHUNK as usize + 16);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut output = AllowStdIo::new(Vec::new());
+        let mut sink = Sealer::<_, SealerStreamConfig>::new(
+            &setup.ibe_pk,
+            &setup.policy,
+            &setup.signing_keys[0],
+            &mut rng,
+        )
+        .unwrap()
+        .start()
+        .unwrap()
+        .into_sink((&mut output).into_sink());
+
+        block_on(async {
+            for chunk in plain.chunks(1000) {
+                sink.send(chunk).await.unwrap();
+            }
+            SinkExt::<&[u8]>::close(&mut sink).await.unwrap();
+        });
+
+        assert_eq!(output.into_inner(), seal_seeded(&setup, &plain, 42));
+    }
+
     #[test]
     #[should_panic]
     fn test_corrupt_header() {
@@ -0,0 +1,165 @@
+//! Parallel processing of payload segments.
+//!
+//! The STREAM nonce of a segment only depends on its position, so segments can be encrypted and
+//! decrypted independently on separate threads. Reading, hashing and writing still happen in
+//! order on the calling task.
+
+use super::*;
+
+use alloc::collections::VecDeque;
+use futures::channel::oneshot;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex};
+
+// The result of encrypting or decrypting a segment.
+type Done = oneshot::Receiver<Result<Vec<u8>, aead::Error>>;
+
+// A non-final segment, its position and the channel to send the result to.
+type Job = (u32, Vec<u8>, oneshot::Sender<Result<Vec<u8>, aead::Error>>);
+
+// A fixed number of threads that encrypt or decrypt non-final segments.
+//
+// The threads are started once, take segments from a bounded queue and exit when the pool is
+// dropped. Results are returned in the order in which the segments were pushed.
+struct Pool {
+    jobs: SyncSender<Job>,
+    pending: VecDeque<Done>,
+}
+
+impl Pool {
+    fn new(aead: Aes128Gcm, nonce: [u8; STREAM_NONCE_SIZE], workers: usize, encrypt: bool) -> Self {
+        let (jobs, rx) = sync_channel::<Job>(workers);
+        let rx = Arc::new(Mutex::new(rx));
+
+        for _ in 0..workers {
+            let aead = aead.clone();
+            let rx = Arc::clone(&rx);
+
+            std::thread::spawn(move || {
+                let stream = stream(&aead, &nonce);
+
+                // The lock is released before the segment is processed.
+                while let Ok(Ok((position, mut seg, tx))) = rx.lock().map(|rx| rx.recv()) {
+                    let res = match encrypt {
+                        true => stream.encrypt_in_place(position, false, b"", &mut seg),
+                        false => stream.decrypt_in_place(position, false, b"", &mut seg),
+                    };
+
+                    // The receiver is only gone if sealing or unsealing has already failed.
+                    let _ = tx.send(res.map(|_| seg));
+                }
+            });
+        }
+
+        Self {
+            jobs,
+            pending: VecDeque::with_capacity(workers),
+        }
+    }
+
+    // The number of segments in flight.
+    fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Queues a segment. Since at most `workers` segments are in flight, this never blocks.
+    fn push(&mut self, position: u32, seg: Vec<u8>) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.jobs
+            .send((position, seg, tx))
+            .map_err(|_disconnected| Error::Symmetric)?;
+        self.pending.push_back(rx);
+
+        Ok(())
+    }
+
+    // Waits for the oldest segment in flight.
+    async fn next(&mut self) -> Result<Vec<u8>, Error> {
+        match self.pending.pop_front() {
+            Some(rx) => Ok(rx.await.map_err(|_canceled| Error::Symmetric)??),
+            None => Err(Error::Symmetric),
+        }
+    }
+}
+
+pub(super) async fn seal_segments<Rng, R, W>(
+    mut sealer: IncrementalSealer<'_, Rng>,
+    workers: usize,
+    mut r: R,
+    w: &mut W,
+) -> Result<(), Error>
+where
+    Rng: RngCore + CryptoRng,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut pool = Pool::new(sealer.aead.clone(), sealer.nonce, workers, true);
+    let mut chunk = vec![0u8; sealer.segment_size];
+
+    w.write_all(&core::mem::take(&mut sealer.header)).await?;
+
+    loop {
+        let read = r.read(&mut chunk).await?;
+        let mut data = &chunk[..read];
+
+        while let Some((position, seg)) = sealer.next_segment(&mut data)? {
+            pool.push(position, seg)?;
+
+            if pool.len() == workers {
+                w.write_all(&pool.next().await?).await?;
+            }
+        }
+
+        if read == 0 {
+            while pool.len() > 0 {
+                w.write_all(&pool.next().await?).await?;
+            }
+
+            return Ok(w.write_all(&sealer.finalize()?).await?);
+        }
+    }
+}
+
+pub(super) async fn unseal_segments<R, W>(
+    unsealer: &mut IncrementalUnsealer,
+    r: &mut R,
+    w: &mut W,
+) -> Result<(), Error>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let workers = unsealer.workers;
+    let mut pool = Pool::new(unsealer.aead.clone(), unsealer.nonce, workers, false);
+    let mut chunk = vec![0u8; unsealer.bufsize];
+
+    // The position of the next segment to open.
+    let mut opened: u32 = 0;
+
+    loop {
+        let read = r.read(&mut chunk).await?;
+        let mut data = &chunk[..read];
+
+        while let Some((position, seg)) = unsealer.next_segment(&mut data)? {
+            pool.push(position, seg)?;
+
+            if pool.len() == workers {
+                let mut seg = Zeroizing::new(pool.next().await?);
+                w.write_all(unsealer.open_segment(&mut seg, opened, false)?)
+                    .await?;
+                opened += 1;
+            }
+        }
+
+        if read == 0 {
+            while pool.len() > 0 {
+                let mut seg = Zeroizing::new(pool.next().await?);
+                w.write_all(unsealer.open_segment(&mut seg, opened, false)?)
+                    .await?;
+                opened += 1;
+            }
+
+            return Ok(());
+        }
+    }
+}
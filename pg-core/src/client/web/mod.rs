@@ -45,7 +45,7 @@ use alloc::vec::Vec;
 /// In-memory configuration for a [`Sealer`].
 #[derive(Debug)]
 pub struct SealerMemoryConfig {
-    key: [u8; KEY_SIZE],
+    ss: SharedSecretBytes,
     nonce: [u8; IV_SIZE],
 }
 
@@ -61,12 +61,6 @@ impl super::sealed::SealerConfig for SealerMemoryConfig {}
 impl UnsealerConfig for UnsealerMemoryConfig {}
 impl super::sealed::UnsealerConfig for UnsealerMemoryConfig {}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MessageAndSignature {
-    message: Vec<u8>,
-    sig: SignatureExt,
-}
-
 impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
     /// Create a new [`Sealer`].
     pub fn new(
@@ -78,26 +72,31 @@ impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
         let (header, ss) = Header::new(mpk, policies, rng)?;
         let Algorithm::Aes128Gcm(iv) = header.algo;
 
-        let mut key = [0u8; KEY_SIZE];
         let mut nonce = [0u8; IV_SIZE];
-        key.copy_from_slice(&ss.0[..KEY_SIZE]);
         nonce.copy_from_slice(&iv.0[..IV_SIZE]);
 
         Ok(Self {
             rng,
             header,
+            policies: policies.clone(),
             pub_sign_key: pub_sign_key.clone(),
             priv_sign_key: None,
-            config: SealerMemoryConfig { key, nonce },
+            production: false,
+            config: SealerMemoryConfig {
+                ss: SharedSecretBytes::new(ss.0),
+                nonce,
+            },
         })
     }
 
     /// Seals the entire payload.
     pub async fn seal(mut self, message: &Uint8Array) -> Result<Uint8Array, Error> {
+        self.production_checked()?;
+
         let mut out = Vec::with_capacity(message.byte_length() as usize + 1024);
 
         out.extend_from_slice(&PRELUDE);
-        out.extend_from_slice(&VERSION_V3.to_be_bytes());
+        out.extend_from_slice(&VERSION_V7.to_be_bytes());
         self.header = self.header.with_mode(Mode::InMemory {
             size: message.byte_length(),
         });
@@ -106,7 +105,7 @@ impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
         out.extend_from_slice(&(header_buf.len() as u32).to_be_bytes());
         out.extend_from_slice(&header_buf);
 
-        let signer = Signer::new().chain(header_buf);
+        let signer = Signer::new().chain(&header_buf);
         let h_sig = signer.clone().sign(&self.pub_sign_key.key.0, self.rng);
 
         let h_sig_ext = SignatureExt {
@@ -114,23 +113,35 @@ impl<'r, R: RngCore + CryptoRng> Sealer<'r, R, SealerMemoryConfig> {
             pol: self.pub_sign_key.policy.clone(),
         };
 
-        let h_sig_ext_bytes = bincode::serialize(&h_sig_ext)?;
-        out.extend_from_slice(&(h_sig_ext_bytes.len() as u32).to_be_bytes());
-        out.extend_from_slice(&h_sig_ext_bytes);
+        // A private sender only includes the header signature in the payload.
+        let h_sig_ext_bytes = length_prefixed(&bincode::serialize(&h_sig_ext)?)?;
+        let mut input = match self.header.private_sender {
+            true => h_sig_ext_bytes,
+            false => {
+                out.extend_from_slice(&h_sig_ext_bytes);
+                Vec::new()
+            }
+        };
+
+        let (key, commitment) = key_schedule(&self.config.ss, &header_hash(&header_buf))?;
+        out.extend_from_slice(&commitment);
 
         let m = message.to_vec();
         let m_sig_key = self.priv_sign_key.unwrap_or(self.pub_sign_key);
         let m_sig = signer.chain(&m).sign(&m_sig_key.key.0, self.rng);
 
-        let input = bincode::serialize(&MessageAndSignature {
-            message: m,
-            sig: SignatureExt {
-                sig: m_sig,
-                pol: m_sig_key.policy.clone(),
+        bincode::serialize_into(
+            &mut input,
+            &MessageAndSignature {
+                message: m,
+                sig: SignatureExt {
+                    sig: m_sig,
+                    pol: m_sig_key.policy.clone(),
+                },
             },
-        })?;
+        )?;
 
-        let key = get_key(&self.config.key).await?;
+        let key = get_key(&key[..]).await?;
         let ciphertext = encrypt(
             &key,
             &self.config.nonce,
@@ -149,24 +160,16 @@ impl Unsealer<Uint8Array, UnsealerMemoryConfig> {
     /// Create a new [`Unsealer`].
     pub fn new(input: &Uint8Array, vk: &VerifyingKey) -> Result<Self, Error> {
         let b = input.to_vec();
-        let (preamble_bytes, b) = b.split_at(PREAMBLE_SIZE);
-        let (version, header_len) = preamble_checked(preamble_bytes)?;
-
-        let (header_bytes, b) = b.split_at(header_len);
-        let (h_sig_len_bytes, b) = b.split_at(SIG_SIZE_SIZE);
-        let h_sig_len = u32::from_be_bytes(h_sig_len_bytes.try_into()?);
-        let (h_sig_bytes, ct) = b.split_at(h_sig_len as usize);
+        let (version, header, header_bytes, h_sig_bytes, commitment, ct) =
+            split_sealed(&b, &[VERSION_V3, VERSION_V4, VERSION_V5, VERSION_V7])?;
 
-        let h_sig_ext: SignatureExt = bincode::deserialize(h_sig_bytes)?;
-        let id = h_sig_ext.pol.derive_ibs()?;
+        let verifier = Verifier::default().chain(header_bytes);
 
-        let verifier = Verifier::default().chain(&header_bytes);
-
-        if !verifier.clone().verify(&vk.0, &h_sig_ext.sig, &id) {
-            return Err(Error::IncorrectSignature.into());
-        }
+        // A private sender only includes the header signature in the payload.
+        let pub_id = h_sig_bytes
+            .map(|h_sig_bytes| verified_header_sig(version, &verifier, vk, h_sig_bytes))
+            .transpose()?;
 
-        let header: Header = bincode::deserialize(header_bytes)?;
         let message_len = match header.mode {
             Mode::InMemory { size } => size as usize,
             _ => return Err(Error::ModeNotSupported(header.mode).into()),
@@ -175,10 +178,12 @@ impl Unsealer<Uint8Array, UnsealerMemoryConfig> {
         Ok(Self {
             version,
             header,
-            pub_id: h_sig_ext.pol,
+            pub_id,
             r: Uint8Array::from(ct),
             verifier,
             vk: vk.clone(),
+            production: false,
+            commitment,
             config: UnsealerMemoryConfig { message_len },
         })
     }
@@ -189,14 +194,16 @@ impl Unsealer<Uint8Array, UnsealerMemoryConfig> {
         ident: &str,
         usk: &UserSecretKey<CGWKV>,
     ) -> Result<(Uint8Array, VerificationResult), Error> {
+        self.production_checked(ident)?;
+
         let rec_info = self
             .header
             .recipients
             .get(ident)
             .ok_or_else(|| Error::UnknownIdentifier(ident.to_string()))?;
 
-        let ss = rec_info.decaps(usk)?;
-        let key = get_key(&ss.0[..KEY_SIZE]).await?;
+        let ss = SharedSecretBytes::new(rec_info.decaps(usk)?.0);
+        let key = get_key(&self.payload_key(&ss)?[..]).await?;
 
         let Algorithm::Aes128Gcm(iv) = self.header.algo;
 
@@ -204,7 +211,19 @@ impl Unsealer<Uint8Array, UnsealerMemoryConfig> {
             .await?
             .to_vec();
 
-        let msg: MessageAndSignature = bincode::deserialize(&plain).map_err(Into::<Error>::into)?;
+        let (pub_id, start) = match self.pub_id {
+            Some(pub_id) => (pub_id, 0),
+            None => payload_header_sig(
+                self.version,
+                &plain,
+                &self.verifier,
+                &self.vk,
+                self.production,
+            )?,
+        };
+
+        let msg: MessageAndSignature =
+            deserialize_versioned(&plain[start..], self.version, plain.len())?;
         let id = msg.sig.pol.derive_ibs()?;
         let verified = self
             .verifier
@@ -217,21 +236,13 @@ impl Unsealer<Uint8Array, UnsealerMemoryConfig> {
 
         debug_assert_eq!(self.config.message_len, msg.message.len());
 
-        let res = Uint8Array::from(msg.message.as_slice());
+        if self.production {
+            production_checked(&msg.sig.pol.con)?;
+        }
 
-        let private = if self.pub_id == msg.sig.pol {
-            None
-        } else {
-            Some(msg.sig.pol)
-        };
+        let res = Uint8Array::from(msg.message.as_slice());
 
-        Ok((
-            res,
-            VerificationResult {
-                public: self.pub_id,
-                private,
-            },
-        ))
+        Ok((res, VerificationResult::new(pub_id, msg.sig.pol)))
     }
 }
 
@@ -6,7 +6,7 @@ use crate::artifacts::{PublicKey, SigningKeyExt, UserSecretKey, VerifyingKey};
 use crate::client::*;
 use crate::error::Error;
 use crate::identity::{EncryptionPolicy, Policy};
-use crate::util::preamble_checked;
+use crate::util::{deserialize_bounded, preamble_checked};
 use ibs::gg::{Identity, Signature, Signer, Verifier, SIG_BYTES};
 
 use futures::{Sink, SinkExt, Stream, StreamExt};
@@ -22,7 +22,7 @@ use alloc::vec::Vec;
 #[derive(Debug)]
 pub struct StreamSealerConfig {
     segment_size: u32,
-    key: [u8; KEY_SIZE],
+    ss: SharedSecretBytes,
     nonce: [u8; STREAM_NONCE_SIZE],
 }
 
@@ -51,20 +51,19 @@ impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, StreamSealerConfig> {
         let (segment_size, _) = stream_mode_checked(&header)?;
         let Algorithm::Aes128Gcm(iv) = header.algo;
 
-        let mut key = [0u8; KEY_SIZE];
         let mut nonce = [0u8; STREAM_NONCE_SIZE];
-
-        key.copy_from_slice(&ss.0[..KEY_SIZE]);
         nonce.copy_from_slice(&iv.0[..STREAM_NONCE_SIZE]);
 
         Ok(Sealer {
             rng,
             header,
+            policies: policies.clone(),
             pub_sign_key: pub_sign_key.clone(),
             priv_sign_key: None,
+            production: false,
             config: StreamSealerConfig {
                 segment_size,
-                key,
+                ss: SharedSecretBytes::new(ss.0),
                 nonce,
             },
         })
@@ -81,6 +80,8 @@ impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, StreamSealerConfig> {
         R: Stream<Item = Result<JsValue, JsValue>> + Unpin,
         W: Sink<JsValue, Error = JsValue> + Unpin,
     {
+        self.production_checked()?;
+
         let size_hint = r.size_hint();
         let new_hint = (size_hint.0 as u64, size_hint.1.map(|x| x as u64));
 
@@ -90,7 +91,7 @@ impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, StreamSealerConfig> {
         });
 
         w.feed(Uint8Array::from(&PRELUDE[..]).into()).await?;
-        w.feed(Uint8Array::from(&VERSION_V3.to_be_bytes()[..]).into())
+        w.feed(Uint8Array::from(&VERSION_V7.to_be_bytes()[..]).into())
             .await?;
 
         let header_vec = bincode::serialize(&self.header)?;
@@ -106,38 +107,39 @@ impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, StreamSealerConfig> {
             sig: header_sig,
             pol: self.pub_sign_key.policy.clone(),
         };
-        let header_sig_bytes = bincode::serialize(&header_sig_ext)?;
+        let header_sig_bytes = length_prefixed(&bincode::serialize(&header_sig_ext)?)?;
 
-        w.feed(Uint8Array::from(&(header_sig_bytes.len() as u32).to_be_bytes()[..]).into())
-            .await?;
-        w.feed(Uint8Array::from(&header_sig_bytes[..]).into())
-            .await?;
+        // A private sender only includes the header signature in the first segment.
+        let header_sig_private = match self.header.private_sender {
+            true => header_sig_bytes,
+            false => {
+                w.feed(Uint8Array::from(&header_sig_bytes[..]).into())
+                    .await?;
+                Vec::new()
+            }
+        };
+
+        let (key, commitment) = key_schedule(&self.config.ss, &header_hash(&header_vec))?;
+        w.feed(Uint8Array::from(&commitment[..]).into()).await?;
 
-        let key = get_key(&self.config.key).await?;
+        let key = get_key(&key[..]).await?;
 
         // Check for a private signing key, otherwise fall back to the public one.
         let signing_key = self.priv_sign_key.unwrap_or(self.pub_sign_key);
 
-        let pol_bytes = bincode::serialize(&signing_key.policy)?;
-        let pol_len: u32 = pol_bytes.len() as u32;
+        let mut prefix = header_sig_private;
+        prefix.extend_from_slice(&length_prefixed(&bincode::serialize(&signing_key.policy)?)?);
+        let prefix_len = u32::try_from(prefix.len())?;
 
-        if pol_len + POL_SIZE_SIZE as u32 > self.config.segment_size {
+        if prefix_len > self.config.segment_size {
             return Err(Error::ConstraintViolation.into());
         }
 
         let buf = Uint8Array::new_with_length(self.config.segment_size + SIG_BYTES as u32);
-
-        buf.set(
-            &Uint8Array::from(&(pol_len as u32).to_be_bytes()[..]).into(),
-            0,
-        );
-        buf.set(
-            &Uint8Array::from(&pol_bytes[..]).into(),
-            POL_SIZE_SIZE as u32,
-        );
+        buf.set(&Uint8Array::from(&prefix[..]).into(), 0);
 
         let mut counter = 0u32;
-        let mut buf_tail: u32 = POL_SIZE_SIZE as u32 + pol_len;
+        let mut buf_tail: u32 = prefix_len;
         let mut start: u32 = buf_tail;
 
         while let Some(Ok(data)) = r.next().await {
@@ -156,21 +158,14 @@ impl<'r, Rng: RngCore + CryptoRng> Sealer<'r, Rng, StreamSealerConfig> {
                     array = array.slice(rem, len);
                     buf_tail += rem;
 
+                    // Only the final segment is signed, see [`VERSION_V6`].
                     signer.update(&buf.slice(start, buf_tail).to_vec());
-                    let sig = signer
-                        .clone()
-                        .chain(&counter.to_be_bytes())
-                        .chain(&[0x00])
-                        .sign(&signing_key.key.0, self.rng);
-                    let sig_bytes = bincode::serialize(&sig)?;
-
-                    buf.set(&Uint8Array::from(&sig_bytes[..]).into(), buf_tail);
 
                     let ct = encrypt(
                         &key,
                         &aead_nonce(&self.config.nonce, counter, false),
                         &Uint8Array::new_with_length(0),
-                        &buf,
+                        &buf.slice(0, buf_tail),
                     )
                     .await?;
 
@@ -221,6 +216,17 @@ fn aead_nonce(nonce: &[u8], counter: u32, last_block: bool) -> [u8; IV_SIZE] {
     iv
 }
 
+// Splits the signature off the end of a decrypted segment.
+fn split_sig(plain: &Uint8Array) -> Result<(Uint8Array, Signature), Error> {
+    let len = plain.byte_length();
+    let m_len = len
+        .checked_sub(SIG_BYTES as u32)
+        .ok_or_else(|| Error::FormatViolation("segment signature".to_string()))?;
+    let sig = deserialize_bounded(&plain.slice(m_len, len).to_vec(), SIG_BYTES)?;
+
+    Ok((plain.slice(0, m_len), sig))
+}
+
 async fn read_atleast<R>(mut r: R, buf: &mut [u8], spill: &mut Vec<u8>) -> Result<(), Error>
 where
     R: Stream<Item = Result<JsValue, JsValue>> + Unpin,
@@ -278,35 +284,59 @@ where
 
         let mut preamble = [0u8; PREAMBLE_SIZE];
         read_atleast(&mut r, &mut preamble, &mut spill).await?;
-        let (version, header_len) = preamble_checked(&preamble)?;
+        let (version, header_len) = preamble_checked(
+            &preamble,
+            &[VERSION_V3, VERSION_V4, VERSION_V5, VERSION_V6, VERSION_V7],
+        )?;
 
         let mut header_raw = vec![0u8; header_len];
         read_atleast(&mut r, &mut header_raw, &mut spill).await?;
 
-        let mut h_sig_len_bytes = [0u8; SIG_SIZE_SIZE];
-        read_atleast(&mut r, &mut h_sig_len_bytes, &mut spill).await?;
-        let header_sig_len = u32::from_be_bytes(h_sig_len_bytes);
+        let header: Header = deserialize_versioned(&header_raw, version, MAX_HEADER_SIZE)?;
+        let (segment_size, _) = stream_mode_checked(&header)?;
+        let verifier = Verifier::default().chain(&header_raw);
 
-        let mut header_sig_raw = vec![0u8; header_sig_len as usize];
-        read_atleast(&mut r, &mut header_sig_raw, &mut spill).await?;
-        let h_sig_ext: SignatureExt = bincode::deserialize(&header_sig_raw)?;
+        // A private sender only includes the header signature in the payload.
+        let pub_id = match header.private_sender {
+            true => None,
+            false => {
+                let mut h_sig_len_bytes = [0u8; SIG_SIZE_SIZE];
+                read_atleast(&mut r, &mut h_sig_len_bytes, &mut spill).await?;
+                let header_sig_len = u32::from_be_bytes(h_sig_len_bytes);
 
-        let verifier = Verifier::default().chain(&header_raw);
-        let pub_id = h_sig_ext.pol.derive_ibs()?;
+                if header_sig_len as usize > MAX_SIG_SIZE {
+                    return Err(Error::ConstraintViolation);
+                }
 
-        if !verifier.clone().verify(&vk.0, &h_sig_ext.sig, &pub_id) {
-            return Err(Error::IncorrectSignature.into());
-        }
+                let mut header_sig_raw = vec![0u8; header_sig_len as usize];
+                read_atleast(&mut r, &mut header_sig_raw, &mut spill).await?;
 
-        let header: Header = bincode::deserialize(&header_raw)?;
-        let (segment_size, _) = stream_mode_checked(&header)?;
+                Some(verified_header_sig(
+                    version,
+                    &verifier,
+                    vk,
+                    &header_sig_raw,
+                )?)
+            }
+        };
+
+        let commitment = match version {
+            VERSION_V7 => {
+                let mut tag = [0u8; COMMITMENT_SIZE];
+                read_atleast(&mut r, &mut tag, &mut spill).await?;
+                Some(KeyCommitment::new(&header_raw, &tag)?)
+            }
+            _ => None,
+        };
 
         Ok(Unsealer {
             version,
             header,
-            pub_id: h_sig_ext.pol,
+            pub_id,
             verifier,
             vk: vk.clone(),
+            production: false,
+            commitment,
             r,
             config: StreamUnsealerConfig {
                 spill,
@@ -325,14 +355,16 @@ where
     where
         W: Sink<JsValue, Error = JsValue> + Unpin,
     {
+        self.production_checked(ident)?;
+
         let rec_info = self
             .header
             .recipients
             .get(ident)
             .ok_or_else(|| Error::UnknownIdentifier(ident.to_string()))?;
 
-        let ss = rec_info.decaps(usk)?;
-        let key = get_key(&ss.0[..KEY_SIZE]).await?;
+        let ss = SharedSecretBytes::new(rec_info.decaps(usk)?.0);
+        let key = get_key(&self.payload_key(&ss)?[..]).await?;
 
         let Algorithm::Aes128Gcm(iv) = self.header.algo;
         let nonce = &iv.0[..STREAM_NONCE_SIZE];
@@ -340,18 +372,37 @@ where
         let segment_size: u32 = self.config.segment_size + (SIG_BYTES + TAG_SIZE) as u32;
 
         let buf = Uint8Array::new_with_length(segment_size);
+
+        // Since version 5 only the final segment is signed. A full buffer then holds one segment
+        // and the start of the next, because the final segment is always shorter than the buffer.
+        let signed_segments = self.version < VERSION_V6;
+        let ct_size = match signed_segments {
+            true => segment_size,
+            false => segment_size - SIG_BYTES as u32,
+        };
+
         let mut counter = 0u32;
         let mut buf_tail = 0;
         let mut pol_id: Option<(Policy, Identity)> = None;
 
         fn extract_policy(
             plain: Uint8Array,
+            version: u16,
+            production: bool,
         ) -> Result<(Option<(Policy, Identity)>, Uint8Array), Error> {
             let pol_len =
                 u32::from_be_bytes(plain.slice(0, POL_SIZE_SIZE as u32).to_vec()[..].try_into()?);
+            if pol_len as usize > MAX_POLICY_SIZE {
+                return Err(Error::ConstraintViolation);
+            }
+
             let pol_bytes = plain.slice(POL_SIZE_SIZE as u32, POL_SIZE_SIZE as u32 + pol_len);
-            let pol: Policy = bincode::deserialize(&pol_bytes.to_vec())?;
+            let pol: Policy = deserialize_versioned(&pol_bytes.to_vec(), version, MAX_POLICY_SIZE)?;
             let id = pol.derive_ibs()?;
+
+            if production {
+                production_checked(&pol.con)?;
+            }
             let new_plain = plain.slice(POL_SIZE_SIZE as u32 + pol_len, plain.byte_length());
 
             Ok((Some((pol, id)), new_plain))
@@ -385,37 +436,40 @@ where
                         &key,
                         &aead_nonce(nonce, counter, false),
                         &Uint8Array::new_with_length(0),
-                        &buf,
+                        &buf.slice(0, ct_size),
                     )
                     .await?;
 
                     if counter == 0 {
-                        (pol_id, plain) = extract_policy(plain)?;
+                        plain = self.extract_header_sig(plain)?;
+                        (pol_id, plain) = extract_policy(plain, self.version, self.production)?;
                     }
 
-                    debug_assert!(plain.byte_length() > SIG_BYTES as u32);
-
-                    let m = plain.slice(0, plain.byte_length() - SIG_BYTES as u32);
-                    let sig =
-                        plain.slice(plain.byte_length() - SIG_BYTES as u32, plain.byte_length());
-                    let sig: Signature = bincode::deserialize(&sig.to_vec())?;
-
-                    self.verifier.update(&m.to_vec());
-
-                    if !self
-                        .verifier
-                        .clone()
-                        .chain(&counter.to_be_bytes())
-                        .chain(&[0x00])
-                        .verify(&self.vk.0, &sig, &pol_id.as_ref().unwrap().1)
-                    {
-                        return Err(Error::IncorrectSignature.into());
-                    }
+                    let m = if signed_segments {
+                        let (m, sig) = split_sig(&plain)?;
+                        self.verifier.update(m.to_vec());
+
+                        if !self
+                            .verifier
+                            .clone()
+                            .chain(counter.to_be_bytes())
+                            .chain([0x00])
+                            .verify(&self.vk.0, &sig, &pol_id.as_ref().unwrap().1)
+                        {
+                            return Err(Error::IncorrectSignature);
+                        }
+
+                        m
+                    } else {
+                        self.verifier.update(plain.to_vec());
+                        plain
+                    };
 
                     w.feed(m.into()).await?;
 
                     counter = counter.checked_add(1).ok_or(Error::Symmetric)?;
-                    buf_tail = 0;
+                    buf.set(&buf.slice(ct_size, segment_size), 0);
+                    buf_tail = segment_size - ct_size;
                 }
             }
         }
@@ -429,26 +483,20 @@ where
         .await?;
 
         if counter == 0 {
-            (pol_id, final_plain) = extract_policy(final_plain)?;
+            final_plain = self.extract_header_sig(final_plain)?;
+            (pol_id, final_plain) = extract_policy(final_plain, self.version, self.production)?;
         }
 
-        debug_assert!(final_plain.byte_length() > SIG_BYTES as u32);
-        let m = final_plain.slice(0, final_plain.byte_length() - SIG_BYTES as u32);
-        let sig = final_plain.slice(
-            final_plain.byte_length() - SIG_BYTES as u32,
-            final_plain.byte_length(),
-        );
-
-        let sig: Signature = bincode::deserialize(&sig.to_vec())?;
-        self.verifier.update(&m.to_vec());
+        let (m, sig) = split_sig(&final_plain)?;
+        self.verifier.update(m.to_vec());
         if !self
             .verifier
             .clone()
-            .chain(&counter.to_be_bytes())
-            .chain(&[0x01])
+            .chain(counter.to_be_bytes())
+            .chain([0x01])
             .verify(&self.vk.0, &sig, &pol_id.as_ref().unwrap().1)
         {
-            return Err(Error::IncorrectSignature.into());
+            return Err(Error::IncorrectSignature);
         }
 
         w.feed(m.into()).await?;
@@ -456,16 +504,26 @@ where
         w.flush().await?;
         w.close().await?;
 
-        let private_id = pol_id.unwrap().0;
-        let private = if self.pub_id == private_id {
-            None
-        } else {
-            Some(private_id)
-        };
+        let pub_id = self.pub_id.clone().ok_or(Error::IncorrectSignature)?;
 
-        Ok(VerificationResult {
-            public: self.pub_id.clone(),
-            private,
-        })
+        Ok(VerificationResult::new(pub_id, pol_id.unwrap().0))
+    }
+
+    // Reads the header signature of a private sender from the first segment.
+    fn extract_header_sig(&mut self, plain: Uint8Array) -> Result<Uint8Array, Error> {
+        if self.pub_id.is_some() {
+            return Ok(plain);
+        }
+
+        let (pub_id, len) = payload_header_sig(
+            self.version,
+            &plain.to_vec(),
+            &self.verifier,
+            &self.vk,
+            self.production,
+        )?;
+        self.pub_id = Some(pub_id);
+
+        Ok(plain.slice(u32::try_from(len)?, plain.byte_length()))
     }
 }
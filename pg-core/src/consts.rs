@@ -21,6 +21,38 @@ pub const VERSION_V2: u16 = 1;
 /// The binary header format is defined by Bincode.
 pub const VERSION_V3: u16 = 2;
 
+/// Version 3.
+///
+/// Like version 2, but policies carry an optional embargo, which changes their bincode layout in
+/// the header, the header signature and the payload. Data sealed using version 2 is decoded using
+/// the layout from before the embargo was introduced.
+pub const VERSION_V4: u16 = 3;
+
+/// Version 4.
+///
+/// Like version 3, but the header states whether the sender is private. A private sender omits
+/// the header signature and its length after the header, and instead prefixes the header signature,
+/// which includes the public identity of the sender, to the plaintext of the payload.
+pub const VERSION_V5: u16 = 4;
+
+/// Version 5.
+///
+/// Like version 4, but in streaming mode only the final segment carries an IBS signature. This
+/// signature covers a running hash of the header and all preceding segments. Segments are chained
+/// by the AEAD STREAM construction, which prevents reordering and truncation.
+/// The in-memory mode is unchanged and still uses version 4.
+pub const VERSION_V6: u16 = 5;
+
+/// Version 6.
+///
+/// Like version 5, but the AEAD key is no longer the shared secret itself. Instead, HKDF-SHA256
+/// over the shared secret, salted with the SHA-256 hash of the raw header, derives separate
+/// encryption and commitment keys. The commitment key is stored after the header signature, or
+/// after the header of a private sender, so that a sender cannot encrypt a payload that decrypts
+/// differently for different recipients. Both the in-memory and the streaming mode use this
+/// version.
+pub const VERSION_V7: u16 = 6;
+
 /// The size of the tag with which all PostGuard bytestreams begin.
 pub const PRELUDE_SIZE: usize = 4;
 
@@ -45,6 +77,12 @@ pub const MAX_HEADER_SIZE: usize = 1024 * 1024;
 /// The maximum size of symmetric segments (4 MiB).
 pub const MAX_SYMMETRIC_CHUNK_SIZE: u32 = 1024 * 1024 * 4;
 
+/// The maximum size of a serialized signature, including the claims of the signer (64 KiB).
+pub const MAX_SIG_SIZE: usize = 64 * 1024;
+
+/// The maximum size of the serialized policy of the sender (64 KiB).
+pub const MAX_POLICY_SIZE: usize = 64 * 1024;
+
 /// The preamble contains the following bytes:
 /// * Prelude: 4 bytes,
 /// * Version identifier: 2 bytes,
@@ -73,6 +111,9 @@ pub const IV_SIZE: usize = 12;
 /// Size of the nonce in the "STREAM" encryption construction.
 pub const STREAM_NONCE_SIZE: usize = 7;
 
+/// Size of the key commitment, stored after the header signature since version 6.
+pub const COMMITMENT_SIZE: usize = 32;
+
 /// Size of the authentication tag.
 /// The authentication tag is appended to each segment.
 pub const TAG_SIZE: usize = 16;
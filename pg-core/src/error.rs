@@ -31,6 +31,8 @@ pub enum Error {
     Bincode(bincode::Error),
     /// The recipient identifier was not found in the policies.
     UnknownIdentifier(String),
+    /// The user secret key does not match any of the recipients.
+    NoMatchingRecipient,
     /// Incorrect scheme version.
     IncorrectSchemeVersion,
     /// Constraint violation.
@@ -47,6 +49,14 @@ pub enum Error {
     KEM,
     /// The identity-based signature did not verify.
     IncorrectSignature,
+    /// The key commitment does not match the shared secret of the recipient.
+    IncorrectCommitment,
+    /// An attribute from the demo scheme was found while in production mode.
+    DemoAttributeNotAllowed(String),
+    /// The identity key of the PKG does not have the pinned fingerprint.
+    UntrustedIdentity(String),
+    /// The log of public parameters is inconsistent.
+    InconsistentLog,
     /// Opaque asynchronous IO error from the futures crate.
     #[cfg(feature = "stream")]
     FuturesIO(FuturesIOError),
@@ -65,6 +75,9 @@ impl core::fmt::Display for Error {
                 write!(f, "wrong version, expected: {expected}, found: {found}")
             }
             Self::UnknownIdentifier(ident) => write!(f, "recipient unknown: {ident}"),
+            Self::NoMatchingRecipient => {
+                write!(f, "the user secret key does not match any recipient")
+            }
             Self::FormatViolation(s) => write!(f, "{s} not (correctly) found in format"),
             Self::Bincode(e) => {
                 write!(f, "Bincode error: {e}")
@@ -77,6 +90,15 @@ impl core::fmt::Display for Error {
             Self::ModeNotSupported(m) => write!(f, "mode is not supported: {m:?}"),
             Self::KEM => write!(f, "KEM error"),
             Self::IncorrectSignature => write!(f, "incorrect signature"),
+            Self::IncorrectCommitment => write!(f, "incorrect key commitment"),
+            Self::DemoAttributeNotAllowed(atype) => {
+                write!(f, "demo attribute not allowed in production mode: {atype}")
+            }
+            Self::UntrustedIdentity(found) => write!(
+                f,
+                "the identity key of the PKG has an unexpected fingerprint: {found}"
+            ),
+            Self::InconsistentLog => write!(f, "the log of public parameters is inconsistent"),
             #[cfg(feature = "stream")]
             Self::FuturesIO(e) => write!(f, "futures IO error: {e}"),
             #[cfg(feature = "web")]
@@ -23,9 +23,26 @@ const HINT_TYPES: &[&str] = &[
     "irma-demo.nuts.agb.agbcode",
 ];
 
+/// Attribute types starting with this prefix belong to the IRMA demo scheme.
+///
+/// Credentials from this scheme can be self-issued by anyone.
+const DEMO_SCHEME_PREFIX: &str = "irma-demo.";
+
 /// The complete encryption policy for all recipients.
 pub type EncryptionPolicy = BTreeMap<String, Policy>;
 
+/// Rounds a timestamp down to the start of its epoch of `epoch` seconds.
+///
+/// Policies with the same conjunction and a timestamp in the same epoch map to the same identity,
+/// so that a single user secret key opens all messages sealed in that epoch, e.g., a day. An
+/// epoch of zero leaves the timestamp as-is.
+pub fn round_timestamp(timestamp: u64, epoch: u64) -> u64 {
+    match epoch {
+        0 => timestamp,
+        _ => timestamp - timestamp % epoch,
+    }
+}
+
 /// A PostGuard IRMA attribute, which is a simple case of an IRMA ConDisCon.
 #[derive(Serialize, Deserialize, Debug, Ord, PartialOrd, PartialEq, Eq, Clone, Default)]
 pub struct Attribute {
@@ -47,6 +64,10 @@ pub struct Policy {
 
     /// A conjunction of attributes.
     pub con: Vec<Attribute>,
+
+    /// Embargo timestamp (UNIX time), before which the PKG refuses to issue a key.
+    #[serde(rename = "nb", default)]
+    pub not_before: Option<u64>,
 }
 
 /// An PostGuard hidden policy.
@@ -61,22 +82,102 @@ pub struct HiddenPolicy {
 
     /// A conjunction of attributes, with redacted values.
     pub con: Vec<Attribute>,
+
+    /// Embargo timestamp (UNIX time), before which the PKG refuses to issue a key.
+    #[serde(rename = "nb", default)]
+    pub not_before: Option<u64>,
 }
 
-impl Attribute {
-    fn hintify_value(&self) -> Attribute {
-        let hidden_value = self.value.as_ref().map(|v| {
-            if HINT_TYPES.contains(&&self.atype[..]) {
-                let (begin, end) = v.split_at(v.len().saturating_sub(AMOUNT_CHARS_TO_HIDE));
-                format!("{begin}{}", "*".repeat(end.len()))
-            } else {
-                "".to_string()
+/// A rule describing which part of an attribute value is revealed in a [`HiddenPolicy`].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum HintRule {
+    /// Reveal nothing, the value is replaced by `""`.
+    Hide,
+
+    /// Mask the last `n` characters, revealing the rest, e.g., `"12345****"`.
+    MaskLast(usize),
+
+    /// Mask all but the last `n` characters, e.g., `"*******89"`.
+    RevealLast(usize),
+
+    /// Mask the local part of an email address, revealing its domain, e.g.,
+    /// `"*****@example.com"`. Values without `@` are hidden completely.
+    RevealDomain,
+}
+
+/// The hint rules a sender uses to hide the attribute values of the recipient policies.
+///
+/// The default reveals all but the last four characters of some attribute types, such as mobile
+/// numbers, and hides the value of all other attribute types.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct HintPolicy {
+    /// The rules per attribute type.
+    pub rules: BTreeMap<String, HintRule>,
+
+    /// The rule for attribute types without a rule.
+    pub fallback: HintRule,
+}
+
+impl HintRule {
+    /// Applies the rule to an attribute value.
+    pub fn apply(&self, value: &str) -> String {
+        let len = value.chars().count();
+
+        match *self {
+            HintRule::Hide => String::new(),
+            HintRule::MaskLast(n) => {
+                let shown = len.saturating_sub(n);
+                let prefix: String = value.chars().take(shown).collect();
+                format!("{prefix}{}", "*".repeat(len - shown))
+            }
+            HintRule::RevealLast(n) => {
+                let hidden = len.saturating_sub(n);
+                let suffix: String = value.chars().skip(hidden).collect();
+                format!("{}{suffix}", "*".repeat(hidden))
             }
-        });
+            HintRule::RevealDomain => match value.rsplit_once('@') {
+                Some((local, domain)) => format!("{}@{domain}", "*".repeat(local.chars().count())),
+                None => String::new(),
+            },
+        }
+    }
+}
+
+impl HintPolicy {
+    /// Sets the rule for an attribute type.
+    pub fn with_rule(mut self, atype: &str, rule: HintRule) -> Self {
+        self.rules.insert(atype.to_string(), rule);
+        self
+    }
+
+    /// Returns the rule for an attribute type.
+    pub fn rule(&self, atype: &str) -> &HintRule {
+        self.rules.get(atype).unwrap_or(&self.fallback)
+    }
+}
+
+impl Default for HintPolicy {
+    fn default() -> Self {
+        HintPolicy {
+            rules: HINT_TYPES
+                .iter()
+                .map(|t| (t.to_string(), HintRule::MaskLast(AMOUNT_CHARS_TO_HIDE)))
+                .collect(),
+            fallback: HintRule::Hide,
+        }
+    }
+}
 
+impl Attribute {
+    fn hintify_value(&self, hints: &HintPolicy) -> Attribute {
         Attribute {
             atype: self.atype.clone(),
-            value: hidden_value,
+            value: self
+                .value
+                .as_ref()
+                .map(|v| hints.rule(&self.atype).apply(v)),
         }
     }
 }
@@ -84,9 +185,15 @@ impl Attribute {
 impl Policy {
     /// Completely hides the attribute value, or provides a hint for certain attribute types
     pub fn to_hidden(&self) -> HiddenPolicy {
+        self.to_hidden_with(&HintPolicy::default())
+    }
+
+    /// Hides the attribute values, revealing a hint as configured by `hints`.
+    pub fn to_hidden_with(&self, hints: &HintPolicy) -> HiddenPolicy {
         HiddenPolicy {
             timestamp: self.timestamp,
-            con: self.con.iter().map(Attribute::hintify_value).collect(),
+            con: self.con.iter().map(|a| a.hintify_value(hints)).collect(),
+            not_before: self.not_before,
         }
     }
 
@@ -96,10 +203,13 @@ impl Policy {
         // Suppose we have the following policy:
         //  - con[0..n - 1] consisting of n conjunctions.
         //  - timestamp
-        // = H(0 || f_0 || f'_0 ||  .. || f_{n-1} || f'_{n-1} || timestamp),
+        //  - optionally, an embargo not_before
+        // = H(0 || f_0 || f'_0 ||  .. || f_{n-1} || f'_{n-1} || timestamp [|| 1 || not_before]),
         // where f_i  = H(2i + 1 || a.typ.len() || a.typ),
         // and   f'_i = H(2i + 2 || a.val.len() || a.val).
         //
+        // Policies without an embargo derive the same identity as before it was introduced.
+        //
         // Conjunction is sorted. This requires that Attribute implements a stable Ord.
         // Since lengths encoded as usize are not platform-agnostic, we convert all
         // usize to u64.
@@ -146,6 +256,12 @@ impl Policy {
         }
 
         pre_h.update(&self.timestamp.to_be_bytes());
+
+        if let Some(not_before) = self.not_before {
+            pre_h.update(&[0x01]);
+            pre_h.update(&not_before.to_be_bytes());
+        }
+
         let mut res = [0u8; 64];
         pre_h.finalize(&mut res);
 
@@ -171,13 +287,20 @@ impl Attribute {
 
         Attribute { atype, value }
     }
+
+    /// Returns whether this attribute belongs to the IRMA demo scheme.
+    ///
+    /// Anyone can self-issue demo credentials, so these should never be trusted in production.
+    pub fn is_demo(&self) -> bool {
+        self.atype.starts_with(DEMO_SCHEME_PREFIX)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::identity::{Attribute, Policy};
+    use crate::identity::{round_timestamp, Attribute, HintPolicy, HintRule, Policy};
     use crate::test::TestSetup;
-    use alloc::string::ToString;
+    use alloc::string::{String, ToString};
     use alloc::vec::Vec;
     use ibe::kem::cgw_kv::CGWKV;
 
@@ -199,30 +322,137 @@ mod tests {
         assert_ne!(&p1_derived, &reversed.derive_kem::<CGWKV>().unwrap());
     }
 
+    #[test]
+    fn test_not_before() {
+        let policy = Policy {
+            timestamp: 1_700_000_000,
+            con: vec![Attribute::new(
+                "pbdf.sidn-pbdf.email.email",
+                Some("alice@example.com"),
+            )],
+            not_before: None,
+        };
+        let derived = policy.derive_kem::<CGWKV>().unwrap();
+
+        // An embargo should matter, and therefore map to a different IBE identity.
+        let mut embargoed = policy.clone();
+        embargoed.not_before = Some(1_800_000_000);
+        let embargoed_derived = embargoed.derive_kem::<CGWKV>().unwrap();
+        assert_ne!(&derived, &embargoed_derived);
+
+        embargoed.not_before = Some(1_800_000_001);
+        assert_ne!(
+            &embargoed_derived,
+            &embargoed.derive_kem::<CGWKV>().unwrap()
+        );
+
+        // The embargo is public, so that recipients know when they can decrypt.
+        assert_eq!(embargoed.to_hidden().not_before, Some(1_800_000_001));
+    }
+
+    #[test]
+    fn test_round_timestamp() {
+        const DAY: u64 = 24 * 60 * 60;
+        let timestamp = 1_700_000_000;
+
+        assert_eq!(round_timestamp(timestamp, 0), timestamp);
+        assert_eq!(round_timestamp(timestamp, 1), timestamp);
+        assert_eq!(round_timestamp(timestamp, DAY), 1_699_920_000);
+        assert_eq!(
+            round_timestamp(timestamp + DAY - 1, DAY),
+            1_699_920_000 + DAY
+        );
+        assert_eq!(round_timestamp(1_699_920_000, DAY), 1_699_920_000);
+    }
+
     #[test]
     fn test_hints() {
         let attr = Attribute {
             atype: "pbdf.sidn-pbdf.mobilenumber.mobilenumber".to_string(),
             value: Some("123456789".to_string()),
         };
-        let hinted = attr.hintify_value();
+        let hints = HintPolicy::default();
+        let hinted = attr.hintify_value(&hints);
         assert_eq!(hinted.value, Some("12345****".to_string()));
 
         let attr_short = Attribute {
             atype: "pbdf.sidn-pbdf.mobilenumber.mobilenumber".to_string(),
             value: Some("123".to_string()),
         };
-        let hinted_short = attr_short.hintify_value();
+        let hinted_short = attr_short.hintify_value(&hints);
         assert_eq!(hinted_short.value, Some("***".to_string()));
 
         let attr_not_whitelisted = Attribute {
             atype: "pbdf.sidn-pbdf.mobilenumber.test".to_string(),
             value: Some("123456789".to_string()),
         };
-        let hinted_empty = attr_not_whitelisted.hintify_value();
+        let hinted_empty = attr_not_whitelisted.hintify_value(&hints);
         assert_eq!(hinted_empty.value, Some("".to_string()));
     }
 
+    #[test]
+    fn test_hint_policy() {
+        let hints = HintPolicy::default()
+            .with_rule("pbdf.sidn-pbdf.email.email", HintRule::RevealDomain)
+            .with_rule("pbdf.gemeente.personalData.bsn", HintRule::RevealLast(2))
+            .with_rule("pbdf.sidn-pbdf.mobilenumber.mobilenumber", HintRule::Hide);
+
+        let policy = Policy {
+            timestamp: 0,
+            con: vec![
+                Attribute::new("pbdf.sidn-pbdf.email.email", Some("alice@example.com")),
+                Attribute::new("pbdf.gemeente.personalData.bsn", Some("123456789")),
+                Attribute::new(
+                    "pbdf.sidn-pbdf.mobilenumber.mobilenumber",
+                    Some("0612345678"),
+                ),
+                Attribute::new("pbdf.gemeente.personalData.fullname", Some("Alice")),
+                Attribute::new("pbdf.gemeente.personalData.surname", None),
+            ],
+            not_before: None,
+        };
+
+        let hidden: Vec<Option<String>> = policy
+            .to_hidden_with(&hints)
+            .con
+            .into_iter()
+            .map(|a| a.value)
+            .collect();
+        assert_eq!(
+            hidden,
+            [
+                Some("*****@example.com".to_string()),
+                Some("*******89".to_string()),
+                Some("".to_string()),
+                Some("".to_string()),
+                None,
+            ]
+        );
+
+        // The default rules are unchanged.
+        assert_eq!(
+            policy.to_hidden(),
+            policy.to_hidden_with(&HintPolicy::default())
+        );
+        assert_eq!(HintRule::RevealDomain.apply("no-domain"), "");
+        assert_eq!(HintRule::MaskLast(4).apply("€12"), "***");
+
+        let json = r#"{"rules":{"pbdf.sidn-pbdf.email.email":"revealDomain"},"fallback":{"revealLast":2}}"#;
+        let parsed: HintPolicy = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            parsed.rule("pbdf.sidn-pbdf.email.email"),
+            &HintRule::RevealDomain
+        );
+        assert_eq!(parsed.rule("other"), &HintRule::RevealLast(2));
+    }
+
+    #[test]
+    fn test_is_demo() {
+        assert!(Attribute::new("irma-demo.gemeente.personalData.fullname", None).is_demo());
+        assert!(!Attribute::new("pbdf.gemeente.personalData.fullname", None).is_demo());
+        assert!(!Attribute::new("pbdf.irma-demo.personalData.fullname", None).is_demo());
+    }
+
     #[test]
     fn test_regression() {
         let mut rng = rand::thread_rng();
@@ -35,6 +35,7 @@
 //!         "pbdf.gemeente.personalData.bsn",
 //!         Some("123bob789"),
 //!     )],
+//!     not_before: None,
 //! };
 //!
 //! let p2 = Policy {
@@ -43,6 +44,7 @@
 //!         Attribute::new("pbdf.gemeente.personalData.name", Some("Charlie")),
 //!         Attribute::new("pbdf.sidn-pbdf.email.email", Some("charlie@example.com")),
 //!     ],
+//!     not_before: None,
 //! };
 //!
 //! let policy = EncryptionPolicy::from([(id1, p1), (id2, p2)]);
@@ -141,6 +143,43 @@
  ```
 "##
 )]
+#![cfg_attr(
+    feature = "std",
+    doc = r##"
+ ### Seal a bytestream without an async runtime
+
+ With the `std` feature, the same wire format can be produced and consumed using
+ [`std::io::Read`] and [`std::io::Write`].
+
+ ```rust
+ use pg_core::client::rust::blocking::{SealerBlockingConfig, UnsealerBlockingConfig};
+ use pg_core::client::{Sealer, Unsealer};
+ # use pg_core::error::Error;
+ use pg_core::test::TestSetup;
+
+ # fn main() -> Result<(), Error> {
+ let mut rng = rand::thread_rng();
+ # let setup = TestSetup::new(&mut rng);
+ # let signing_key = &setup.signing_keys[0];
+ # let vk = setup.ibs_pk;
+ # let usk = &setup.usks[2];
+ let input = b"SECRET DATA";
+ let mut sealed = Vec::new();
+
+ Sealer::<_, SealerBlockingConfig>::new(&setup.ibe_pk, &setup.policy, &signing_key, &mut rng)?
+     .seal(&input[..], &mut sealed)?;
+
+ let mut original = Vec::new();
+ let policy = Unsealer::<_, UnsealerBlockingConfig>::new(&sealed[..], &vk)?
+     .unseal("Bob", &usk, &mut original)?;
+
+ assert_eq!(&input[..], original);
+ assert_eq!(&policy.public, &signing_key.policy);
+ # Ok(())
+ # }
+ ```
+"##
+)]
 //!
 //! ### Using the Web Crypto backend
 //!
@@ -158,13 +197,40 @@
 //! = PRELUDE (4) || VERSION (2) || HEADER LEN (4)
 //!
 //!                  HEADER (*)
-//! = HEADER (*) || HEADER SIG LEN (4) || HEADER SIG (*)
+//! = HEADER (*) || HEADER SIG LEN (4) || HEADER SIG (*) || KEY COMMITMENT (32)
 //!
 //!                  PAYLOAD  (*)
 //! = DEM.Enc(M (*) || STREAM SIG (*) || STREAM SIG LEN (4))
 //! ```
+//!
+//! In streaming mode the payload is split into segments that are encrypted using the STREAM
+//! construction, which binds every segment to its position and marks the final one. Since
+//! [`consts::VERSION_V6`], only the final segment carries a signature, which covers the header and
+//! all preceding plaintext. Streams sealed using earlier versions, in which every segment is
+//! signed, can still be unsealed.
+//!
+//! Policies in data sealed using [`consts::VERSION_V3`] are encoded without an embargo, which was
+//! introduced in [`consts::VERSION_V4`].
+//!
+//! Since [`consts::VERSION_V7`], the AEAD key is derived from the shared secret and the hash of
+//! the header using HKDF, along with a key commitment. Unsealers reject data of which the
+//! commitment does not match the shared secret of the recipient, so that all recipients that
+//! unseal a payload decrypt it using the same key. Earlier versions have no key commitment and use
+//! the shared secret as key.
+//!
+//! Since [`consts::VERSION_V5`], the header states whether the sender is private. A private sender
+//! omits the header signature and its length after the header. The header signature, including
+//! the public identity of the sender, is prefixed to the plaintext of the payload instead:
+//!
+//! ```text
+//!                  HEADER (*)
+//! = HEADER (*) || KEY COMMITMENT (32)
+//!
+//!                  PAYLOAD  (*)
+//! = DEM.Enc(HEADER SIG LEN (4) || HEADER SIG (*) || M (*) || STREAM SIG (*) || STREAM SIG LEN (4))
+//! ```
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 extern crate std;
 
 // We depend on alloc for String, Vec and BTreeMap/HashMap.
@@ -176,6 +242,7 @@ pub mod artifacts;
 pub mod consts;
 pub mod error;
 pub mod identity;
+pub mod transparency;
 
 #[cfg(any(feature = "rust", feature = "web"))]
 pub mod client;
@@ -53,6 +53,7 @@ impl TestSetup {
                     "pbdf.sidn-pbdf.email.email",
                     Some("alice@example.com"),
                 )],
+                not_before: None,
             }, // Alice just BSN
             Policy {
                 timestamp: 1566722350,
@@ -60,6 +61,7 @@ impl TestSetup {
                     "pbdf.gemeente.personalData.bsn",
                     Some("<Alice's social security number>"),
                 )],
+                not_before: None,
             }, // Bob name + email
             Policy {
                 timestamp: 1566722350,
@@ -67,6 +69,7 @@ impl TestSetup {
                     Attribute::new("pbdf.gemeente.personalData.name", Some("Bob")),
                     Attribute::new("pbdf.sidn-pbdf.email.email", Some("bob@example.com")),
                 ],
+                not_before: None,
             }, // Charlie name + email
             Policy {
                 timestamp: 1566722350,
@@ -74,6 +77,7 @@ impl TestSetup {
                     Attribute::new("pbdf.gemeente.personalData.name", Some("Charlie")),
                     Attribute::new("pbdf.sidn-pbdf.email.email", Some("charlie@example.com")),
                 ],
+                not_before: None,
             }, // Charlie just name
             Policy {
                 timestamp: 1566722350,
@@ -81,6 +85,7 @@ impl TestSetup {
                     "pbdf.gemeente.personalData.name",
                     Some("Charlie"),
                 )],
+                not_before: None,
             },
         ];
 
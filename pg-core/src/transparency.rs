@@ -0,0 +1,453 @@
+//! Transparency of the public parameters of the Private Key Generator (PKG).
+//!
+//! A PKG signs its public parameters using a long-term Ed25519 identity key. Clients that pin the
+//! fingerprint of this key can verify the parameters, instead of trusting whatever the transport
+//! delivers. A PKG can additionally keep an append-only log of the fingerprints of all public
+//! parameters it has used. The signed statement commits to the head of this log, which lets
+//! clients check that parameters are consistent with those seen in earlier fetches.
+//!
+//! # Notes
+//!
+//! The log is a hash chain: the hash of each entry covers the hash of the previous entry. A log
+//! extends an earlier log if the entry at the size of the earlier log has the same hash.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use ed25519_dalek::{Signer, Verifier};
+use ibe::kem::cgw_kv::CGWKV;
+use ibe::Compress;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tiny_keccak::{Hasher, Sha3};
+
+use crate::artifacts::{deserialize_bin_or_b64, serialize_bin_or_b64, PublicKey, VerifyingKey};
+use crate::error::Error;
+use crate::util::hex;
+
+/// Context prepended to statements before signing, to separate them from other signed data.
+const SIGNATURE_CONTEXT: &[u8] = b"postguard-parameters-v1";
+
+/// Computes the fingerprint of `bytes`: the hex-encoded SHA3-256 hash.
+pub fn fingerprint(bytes: &[u8]) -> String {
+    let mut h = Sha3::v256();
+    h.update(bytes);
+
+    let mut out = [0u8; 32];
+    h.finalize(&mut out);
+
+    hex(&out)
+}
+
+/// The long-term identity key of a PKG, used to sign its public parameters.
+#[derive(Clone)]
+pub struct IdentitySecretKey(ed25519_dalek::SigningKey);
+
+impl fmt::Debug for IdentitySecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IdentitySecretKey").field(&"..").finish()
+    }
+}
+
+impl IdentitySecretKey {
+    /// Generates a new identity key.
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut bytes = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+        rng.fill_bytes(&mut bytes);
+
+        Self(ed25519_dalek::SigningKey::from_bytes(&bytes))
+    }
+
+    /// Reads an identity key from its bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let bytes: &[u8; ed25519_dalek::SECRET_KEY_LENGTH] = bytes.try_into()?;
+
+        Ok(Self(ed25519_dalek::SigningKey::from_bytes(bytes)))
+    }
+
+    /// Returns the bytes of the identity key.
+    pub fn to_bytes(&self) -> [u8; ed25519_dalek::SECRET_KEY_LENGTH] {
+        self.0.to_bytes()
+    }
+
+    /// Returns the public identity key.
+    pub fn public_key(&self) -> IdentityKey {
+        IdentityKey(self.0.verifying_key())
+    }
+
+    /// Signs a statement about the public parameters.
+    pub fn sign(&self, statement: &ParametersStatement) -> Result<SignedParameters, Error> {
+        let statement = serde_json::to_string(statement).map_err(Error::Json)?;
+        let signature = self.0.sign(&signed_message(&statement));
+
+        Ok(SignedParameters {
+            identity_key: self.public_key(),
+            statement,
+            signature: ParametersSignature(signature),
+        })
+    }
+}
+
+fn signed_message(statement: &str) -> Vec<u8> {
+    [SIGNATURE_CONTEXT, statement.as_bytes()].concat()
+}
+
+/// The public identity key of a PKG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdentityKey(pub ed25519_dalek::VerifyingKey);
+
+impl IdentityKey {
+    /// Returns the fingerprint of the identity key, which clients can pin.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(self.0.as_bytes())
+    }
+}
+
+impl Serialize for IdentityKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_bin_or_b64(self.0.as_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for IdentityKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut buf = [0u8; ed25519_dalek::PUBLIC_KEY_LENGTH];
+        deserialize_bin_or_b64(&mut buf, deserializer)?;
+
+        let vk = ed25519_dalek::VerifyingKey::from_bytes(&buf)
+            .map_err(|e| serde::de::Error::custom(format!("not a valid identity key: {e}")))?;
+
+        Ok(IdentityKey(vk))
+    }
+}
+
+/// A signature over a statement about the public parameters.
+#[derive(Debug, Clone)]
+pub struct ParametersSignature(pub ed25519_dalek::Signature);
+
+impl Serialize for ParametersSignature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_bin_or_b64(&self.0.to_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ParametersSignature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut buf = [0u8; ed25519_dalek::SIGNATURE_LENGTH];
+        deserialize_bin_or_b64(&mut buf, deserializer)?;
+
+        Ok(ParametersSignature(ed25519_dalek::Signature::from_bytes(
+            &buf,
+        )))
+    }
+}
+
+/// A statement of a PKG about its public parameters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParametersStatement {
+    /// The formatting version of the public keys.
+    pub format_version: u8,
+
+    /// The Master Public Key for encryption.
+    pub public_key: PublicKey<CGWKV>,
+
+    /// The Master Public Key for signature verification.
+    pub verifying_key: VerifyingKey,
+
+    /// The key validity window (in seconds), see [`crate::api::Parameters::key_validity`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_validity: Option<u64>,
+
+    /// The head of the log of public parameters, if the PKG keeps one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<LogHead>,
+}
+
+impl ParametersStatement {
+    /// Returns the fingerprint of the Master Public Key for encryption.
+    pub fn public_key_fingerprint(&self) -> String {
+        fingerprint(self.public_key.0.to_bytes().as_ref())
+    }
+
+    /// Returns the fingerprint of the Master Public Key for signature verification.
+    pub fn verifying_key_fingerprint(&self) -> Result<String, Error> {
+        Ok(fingerprint(&bincode::serialize(&self.verifying_key.0)?))
+    }
+
+    /// Checks that `log` is consistent with this statement and with a statement seen earlier.
+    ///
+    /// The log must be given if this statement commits to a log. It must end in the parameters of
+    /// this statement and extend the log of the earlier statement, if any. A statement without a
+    /// log is only consistent with an earlier statement that has no log either.
+    pub fn check_log(
+        &self,
+        log: Option<&ParametersLog>,
+        previous: Option<&ParametersStatement>,
+    ) -> Result<(), Error> {
+        let previous = previous.and_then(|p| p.log.as_ref());
+
+        let (head, log) = match (&self.log, log) {
+            (None, _) if previous.is_none() => return Ok(()),
+            (Some(head), Some(log)) => (head, log),
+            _ => return Err(Error::InconsistentLog),
+        };
+
+        log.verify(head)?;
+
+        match log.entries.last() {
+            Some(last)
+                if last.public_key == self.public_key_fingerprint()
+                    && last.verifying_key == self.verifying_key_fingerprint()? => {}
+            _ => return Err(Error::InconsistentLog),
+        }
+
+        match previous {
+            Some(previous) => log.extends(previous),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Public parameters signed by the identity key of a PKG.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedParameters {
+    /// The identity key of the PKG.
+    pub identity_key: IdentityKey,
+
+    /// The serialized [`ParametersStatement`], exactly as signed.
+    pub statement: String,
+
+    /// The signature over the statement.
+    pub signature: ParametersSignature,
+}
+
+impl SignedParameters {
+    /// Verifies the signature and returns the statement.
+    ///
+    /// If `fingerprint` is given, the identity key must have this fingerprint. Without a
+    /// fingerprint, this only checks that the statement is signed by the included key.
+    pub fn verify(&self, fingerprint: Option<&str>) -> Result<ParametersStatement, Error> {
+        let found = self.identity_key.fingerprint();
+        if fingerprint.is_some_and(|expected| !expected.eq_ignore_ascii_case(&found)) {
+            return Err(Error::UntrustedIdentity(found));
+        }
+
+        self.identity_key
+            .0
+            .verify(&signed_message(&self.statement), &self.signature.0)
+            .map_err(|_e| Error::IncorrectSignature)?;
+
+        serde_json::from_str(&self.statement).map_err(Error::Json)
+    }
+}
+
+/// The head of a log of public parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogHead {
+    /// The number of entries in the log.
+    pub size: u64,
+
+    /// The hash of the last entry.
+    pub hash: String,
+}
+
+/// An entry in the log of public parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    /// The fingerprint of the Master Public Key for encryption.
+    pub public_key: String,
+
+    /// The fingerprint of the Master Public Key for signature verification.
+    pub verifying_key: String,
+
+    /// The time (UNIX timestamp) since which the PKG uses these parameters.
+    pub since: u64,
+
+    /// The hash of this entry, which covers the hash of the previous entry.
+    pub hash: String,
+}
+
+impl LogEntry {
+    fn compute_hash(previous: &str, public_key: &str, verifying_key: &str, since: u64) -> String {
+        let mut h = Sha3::v256();
+        for field in [previous, public_key, verifying_key] {
+            h.update(&(field.len() as u64).to_be_bytes());
+            h.update(field.as_bytes());
+        }
+        h.update(&since.to_be_bytes());
+
+        let mut out = [0u8; 32];
+        h.finalize(&mut out);
+
+        hex(&out)
+    }
+}
+
+/// An append-only log of the public parameters of a PKG.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ParametersLog {
+    /// The entries, oldest first.
+    pub entries: Vec<LogEntry>,
+}
+
+impl ParametersLog {
+    /// Returns the head of the log, or `None` if the log is empty.
+    pub fn head(&self) -> Option<LogHead> {
+        self.entries.last().map(|last| LogHead {
+            size: self.entries.len() as u64,
+            hash: last.hash.clone(),
+        })
+    }
+
+    /// Appends the parameters of `statement`, unless they are the last entry already.
+    ///
+    /// Returns whether an entry was appended.
+    pub fn append(&mut self, statement: &ParametersStatement, since: u64) -> Result<bool, Error> {
+        let public_key = statement.public_key_fingerprint();
+        let verifying_key = statement.verifying_key_fingerprint()?;
+
+        if let Some(last) = self.entries.last() {
+            if last.public_key == public_key && last.verifying_key == verifying_key {
+                return Ok(false);
+            }
+        }
+
+        let previous = self.entries.last().map_or("", |last| last.hash.as_str());
+        let hash = LogEntry::compute_hash(previous, &public_key, &verifying_key, since);
+
+        self.entries.push(LogEntry {
+            public_key,
+            verifying_key,
+            since,
+            hash,
+        });
+
+        Ok(true)
+    }
+
+    /// Verifies the hash chain of the log and checks that it ends in `head`.
+    pub fn verify(&self, head: &LogHead) -> Result<(), Error> {
+        let mut previous = "";
+        for entry in &self.entries {
+            let hash = LogEntry::compute_hash(
+                previous,
+                &entry.public_key,
+                &entry.verifying_key,
+                entry.since,
+            );
+            if hash != entry.hash {
+                return Err(Error::InconsistentLog);
+            }
+            previous = &entry.hash;
+        }
+
+        if self.head().as_ref() != Some(head) {
+            return Err(Error::InconsistentLog);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that this log extends the log with head `previous`.
+    ///
+    /// The hash chain must have been verified using [`ParametersLog::verify`].
+    pub fn extends(&self, previous: &LogHead) -> Result<(), Error> {
+        let index = usize::try_from(previous.size)?
+            .checked_sub(1)
+            .ok_or(Error::InconsistentLog)?;
+
+        match self.entries.get(index) {
+            Some(entry) if entry.hash == previous.hash => Ok(()),
+            _ => Err(Error::InconsistentLog),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ibe::kem::IBKEM;
+
+    fn statement(rng: &mut rand::rngs::ThreadRng) -> ParametersStatement {
+        let (pk, _) = CGWKV::setup(rng);
+        let (vk, _) = ibs::gg::setup(rng);
+
+        ParametersStatement {
+            format_version: 0x00,
+            public_key: PublicKey(pk),
+            verifying_key: VerifyingKey(vk),
+            key_validity: None,
+            log: None,
+        }
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let mut rng = rand::thread_rng();
+        let sk = IdentitySecretKey::generate(&mut rng);
+        let fp = sk.public_key().fingerprint();
+
+        let signed = sk.sign(&statement(&mut rng)).unwrap();
+        let json = serde_json::to_string(&signed).unwrap();
+        let signed: SignedParameters = serde_json::from_str(&json).unwrap();
+
+        assert!(signed.verify(Some(&fp)).is_ok());
+        assert!(signed.verify(None).is_ok());
+
+        let other = IdentitySecretKey::generate(&mut rng)
+            .public_key()
+            .fingerprint();
+        assert!(matches!(
+            signed.verify(Some(&other)),
+            Err(Error::UntrustedIdentity(found)) if found == fp
+        ));
+
+        let mut tampered = signed.clone();
+        tampered.statement = serde_json::to_string(&statement(&mut rng)).unwrap();
+        assert!(matches!(
+            tampered.verify(Some(&fp)),
+            Err(Error::IncorrectSignature)
+        ));
+    }
+
+    #[test]
+    fn test_log() {
+        let mut rng = rand::thread_rng();
+        let (mut first, mut second) = (statement(&mut rng), statement(&mut rng));
+
+        let mut log = ParametersLog::default();
+        assert!(log.append(&first, 1).unwrap());
+        assert!(!log.append(&first, 2).unwrap());
+        first.log = log.head();
+
+        assert!(log.append(&second, 3).unwrap());
+        second.log = log.head();
+
+        assert!(second.check_log(Some(&log), Some(&first)).is_ok());
+        assert!(second.check_log(Some(&log), None).is_ok());
+
+        // The log must end in the parameters of the statement.
+        assert!(first.check_log(Some(&log), None).is_err());
+
+        // The log cannot be rewritten, nor dropped.
+        let mut forked = ParametersLog::default();
+        forked.append(&statement(&mut rng), 1).unwrap();
+        forked.append(&second, 3).unwrap();
+        second.log = forked.head();
+        assert!(second.check_log(Some(&forked), None).is_ok());
+        assert!(second.check_log(Some(&forked), Some(&first)).is_err());
+
+        second.log = None;
+        assert!(second.check_log(None, Some(&first)).is_err());
+
+        // Tampering with an entry breaks the hash chain.
+        let head = log.head().unwrap();
+        log.entries[0].since = 0;
+        assert!(log.verify(&head).is_err());
+    }
+}
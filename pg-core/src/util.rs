@@ -1,6 +1,15 @@
 use crate::consts::*;
 use crate::error::Error;
+use crate::identity::Attribute;
 use alloc::string::String;
+use alloc::string::ToString;
+use bincode::Options;
+use serde::Deserialize;
+
+/// Encodes `bytes` as lowercase hexadecimal.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
 
 pub(crate) fn open_ct<T>(x: subtle::CtOption<T>) -> Option<T> {
     if bool::from(x.is_some()) {
@@ -10,7 +19,10 @@ pub(crate) fn open_ct<T>(x: subtle::CtOption<T>) -> Option<T> {
     }
 }
 
-pub(crate) fn preamble_checked(preamble: &[u8]) -> Result<(u16, usize), Error> {
+/// Checks the preamble and returns the version and header length.
+///
+/// The version must be one of `versions`, of which the last one is the most recent.
+pub(crate) fn preamble_checked(preamble: &[u8], versions: &[u16]) -> Result<(u16, usize), Error> {
     if preamble.len() != PREAMBLE_SIZE || preamble[..PRELUDE_SIZE] != PRELUDE {
         return Err(Error::NotPostGuard);
     }
@@ -21,9 +33,9 @@ pub(crate) fn preamble_checked(preamble: &[u8]) -> Result<(u16, usize), Error> {
             .map_err(|_e| Error::FormatViolation(String::from("version")))?,
     );
 
-    if version != VERSION_V3 {
+    if !versions.contains(&version) {
         return Err(Error::IncorrectVersion {
-            expected: VERSION_V3,
+            expected: versions.last().copied().unwrap_or(VERSION_V3),
             found: version,
         });
     }
@@ -40,3 +52,28 @@ pub(crate) fn preamble_checked(preamble: &[u8]) -> Result<(u16, usize), Error> {
 
     Ok((version, header_len))
 }
+
+/// Deserializes untrusted bincode, refusing to read more than `limit` bytes.
+///
+/// Uses the same encoding as [`bincode::deserialize`].
+pub(crate) fn deserialize_bounded<'a, T: Deserialize<'a>>(
+    bytes: &'a [u8],
+    limit: usize,
+) -> Result<T, Error> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit as u64)
+        .deserialize(bytes)
+        .map_err(Error::Bincode)
+}
+
+/// Checks that none of the attributes belong to the demo scheme.
+pub(crate) fn production_checked<'a>(
+    attrs: impl IntoIterator<Item = &'a Attribute>,
+) -> Result<(), Error> {
+    match attrs.into_iter().find(|a| a.is_demo()) {
+        Some(a) => Err(Error::DemoAttributeNotAllowed(a.atype.to_string())),
+        None => Ok(()),
+    }
+}
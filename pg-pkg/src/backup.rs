@@ -0,0 +1,514 @@
+//! Backup of the master secret keys using Shamir secret sharing.
+//!
+//! Both master secret keys are split into shares, such that a threshold of shares is required to
+//! recover them. Each share is written to a printable text file:
+//!
+//! ```text
+//! -----BEGIN PG-PKG KEY SHARE-----
+//! Version: 1
+//! Id: 5f0c3a9e7d21b4c8
+//! Share: 2/5
+//! Threshold: 3
+//! Keys: 8Rk2lX0Zt9w
+//! Checksum: 1c291ca3
+//!
+//! <base64-encoded share, wrapped at 64 characters>
+//! -----END PG-PKG KEY SHARE-----
+//! ```
+//!
+//! The `Id` identifies the set of shares created by a single split, `Keys` is a fingerprint of
+//! the public keys and `Checksum` is a CRC-32 over the metadata and the share, to catch
+//! transcription errors.
+
+use base64::{engine::general_purpose, Engine as _};
+use blahaj::{Share, Sharks};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use pg_core::ibs::gg::{self, Signer, Verifier};
+use pg_core::identity::{Attribute, Policy};
+use pg_core::kem::{cgw_kv::CGWKV, IBKEM};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::keyfile::{self, Passphrase, PASSPHRASE_ENV};
+use crate::opts::*;
+use crate::util::*;
+use crate::PKGError;
+
+const BEGIN: &str = "-----BEGIN PG-PKG KEY SHARE-----";
+const END: &str = "-----END PG-PKG KEY SHARE-----";
+
+/// Current version of the share format.
+const SHARE_VERSION_V1: u8 = 1;
+
+/// Width of the base64-encoded share lines.
+const LINE_WIDTH: usize = 64;
+
+/// The secret that is split, i.e., the plain contents of both secret key files.
+///
+/// Both keys are wiped from memory when dropped.
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupSecret {
+    ibe_sk: Vec<u8>,
+    ibs_sk: Vec<u8>,
+}
+
+impl Drop for BackupSecret {
+    fn drop(&mut self) {
+        self.ibe_sk.zeroize();
+        self.ibs_sk.zeroize();
+    }
+}
+
+/// A single share of the master secret keys, including its metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyShare {
+    /// Identifier of the set of shares.
+    id: String,
+
+    /// Index of this share, starting at 1.
+    index: u8,
+
+    /// Total number of shares.
+    total: u8,
+
+    /// Number of shares required to recover the secret.
+    threshold: u8,
+
+    /// Fingerprint of the public keys.
+    keys: String,
+
+    /// The share, i.e., the x-coordinate followed by the y-coordinates.
+    data: Vec<u8>,
+}
+
+impl KeyShare {
+    fn checksum(&self) -> u32 {
+        let mut h = crc32fast::Hasher::new();
+        h.update(
+            format!(
+                "{SHARE_VERSION_V1}|{}|{}|{}|{}|{}|",
+                self.id, self.index, self.total, self.threshold, self.keys
+            )
+            .as_bytes(),
+        );
+        h.update(&self.data);
+        h.finalize()
+    }
+
+    fn to_text(&self) -> String {
+        let encoded = general_purpose::STANDARD.encode(&self.data);
+
+        let mut out = format!(
+            "{BEGIN}\nVersion: {SHARE_VERSION_V1}\nId: {}\nShare: {}/{}\nThreshold: {}\nKeys: {}\nChecksum: {:08x}\n\n",
+            self.id,
+            self.index,
+            self.total,
+            self.threshold,
+            self.keys,
+            self.checksum()
+        );
+
+        for line in encoded.as_bytes().chunks(LINE_WIDTH) {
+            out.push_str(std::str::from_utf8(line).unwrap_or_default());
+            out.push('\n');
+        }
+
+        out.push_str(END);
+        out.push('\n');
+
+        out
+    }
+
+    fn from_text(text: &str) -> Result<Self, PKGError> {
+        let err = |s: &str| PKGError::Backup(format!("malformed share: {s}"));
+
+        let mut lines = text.lines().map(str::trim).skip_while(|l| l.is_empty());
+        if lines.next() != Some(BEGIN) {
+            return Err(err("missing begin marker"));
+        }
+
+        let mut headers = BTreeMap::new();
+        for line in lines.by_ref().take_while(|l| !l.is_empty()) {
+            let (k, v) = line.split_once(':').ok_or_else(|| err("invalid header"))?;
+            headers.insert(k.trim().to_lowercase(), v.trim().to_string());
+        }
+
+        let mut encoded = String::new();
+        loop {
+            match lines.next() {
+                Some(END) => break,
+                Some(line) => encoded.push_str(line),
+                None => return Err(err("missing end marker")),
+            }
+        }
+
+        let header = |k: &str| headers.get(k).ok_or_else(|| err(&format!("missing {k}")));
+        let number = |s: &str| s.parse::<u8>().map_err(|_e| err("invalid number"));
+
+        if number(header("version")?)? != SHARE_VERSION_V1 {
+            return Err(err("unsupported version"));
+        }
+
+        let (index, total) = header("share")?
+            .split_once('/')
+            .ok_or_else(|| err("invalid share index"))?;
+
+        let share = KeyShare {
+            id: header("id")?.clone(),
+            index: number(index)?,
+            total: number(total)?,
+            threshold: number(header("threshold")?)?,
+            keys: header("keys")?.clone(),
+            data: general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|_e| err("invalid encoding"))?,
+        };
+
+        let checksum =
+            u32::from_str_radix(header("checksum")?, 16).map_err(|_e| err("invalid checksum"))?;
+        if checksum != share.checksum() {
+            return Err(PKGError::Backup(format!(
+                "checksum mismatch in share {}/{}",
+                share.index, share.total
+            )));
+        }
+
+        if share.data.len() < 2 || share.data[0] != share.index {
+            return Err(err("share data does not match its index"));
+        }
+
+        Ok(share)
+    }
+}
+
+/// Fingerprint of the public keys, to match shares against them.
+fn fingerprint(ibe_pk: &[u8], ibs_pk: &[u8]) -> String {
+    xxhash64(&[ibe_pk, ibs_pk].concat())
+}
+
+fn split(secret: &[u8], threshold: u8, total: u8, keys: &str) -> Result<Vec<KeyShare>, PKGError> {
+    if threshold < 2 || threshold > total {
+        return Err(PKGError::Backup(
+            "the threshold must be at least 2 and at most the number of shares".to_string(),
+        ));
+    }
+
+    let mut rng = rand::thread_rng();
+
+    let mut id = [0u8; 8];
+    rng.fill_bytes(&mut id);
+    let id: String = id.iter().map(|b| format!("{b:02x}")).collect();
+
+    let shares = Sharks(threshold)
+        .dealer_rng(secret, &mut rng)
+        .take(total.into())
+        .map(|share| KeyShare {
+            id: id.clone(),
+            index: share.x.0,
+            total,
+            threshold,
+            keys: keys.to_string(),
+            data: Vec::from(&share),
+        })
+        .collect();
+
+    Ok(shares)
+}
+
+fn combine(shares: &[KeyShare]) -> Result<Vec<u8>, PKGError> {
+    let first = shares
+        .first()
+        .ok_or_else(|| PKGError::Backup("no shares given".to_string()))?;
+
+    if shares.iter().any(|s| {
+        s.id != first.id
+            || s.total != first.total
+            || s.threshold != first.threshold
+            || s.keys != first.keys
+    }) {
+        return Err(PKGError::Backup(
+            "the shares do not belong to the same backup".to_string(),
+        ));
+    }
+
+    // Duplicate shares do not count towards the threshold.
+    let distinct: BTreeMap<u8, Share> = shares
+        .iter()
+        .map(|s| {
+            Share::try_from(s.data.as_slice())
+                .map(|share| (s.index, share))
+                .map_err(|e| PKGError::Backup(e.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    if distinct.len() < usize::from(first.threshold) {
+        return Err(PKGError::Backup(format!(
+            "{} distinct share(s) given, but {} are required",
+            distinct.len(),
+            first.threshold
+        )));
+    }
+
+    Sharks(first.threshold)
+        .recover(distinct.values())
+        .map_err(|e| PKGError::Backup(e.to_string()))
+}
+
+/// Checks that the secret keys belong to the public keys by a round of each scheme.
+fn verify_keys(
+    ibe_pk: &<CGWKV as IBKEM>::Pk,
+    ibe_sk: &<CGWKV as IBKEM>::Sk,
+    ibs_pk: &gg::PublicKey,
+    ibs_sk: &gg::SecretKey,
+) -> Result<(), PKGError> {
+    let mut rng = rand::thread_rng();
+    let mismatch = || PKGError::Backup("the recovered keys do not match the public keys".into());
+
+    let pol = Policy {
+        timestamp: 0,
+        con: vec![Attribute::new("pg-pkg.backup.check", None)],
+        not_before: None,
+    };
+
+    let id = pol.derive_kem::<CGWKV>().map_err(|_e| mismatch())?;
+    let (ct, ss1) = CGWKV::encaps(ibe_pk, &id, &mut rng);
+    let usk = CGWKV::extract_usk(None, ibe_sk, &id, &mut rng);
+    let ss2 = CGWKV::decaps(None, &usk, &ct).map_err(|_e| mismatch())?;
+
+    let id = pol.derive_ibs().map_err(|_e| mismatch())?;
+    let usk = gg::keygen(ibs_sk, &id, &mut rng);
+    let sig = Signer::new().chain(b"backup").sign(&usk, &mut rng);
+    let valid = Verifier::new().chain(b"backup").verify(ibs_pk, &sig, &id);
+
+    if ss1 != ss2 || !valid {
+        return Err(mismatch());
+    }
+
+    Ok(())
+}
+
+fn exec_split(opts: &SplitOpts) -> Result<(), PKGError> {
+    let SplitOpts {
+        threshold,
+        shares,
+        out_dir,
+        ibe_secret_path,
+        ibe_public_path,
+        ibs_secret_path,
+        ibs_public_path,
+        passphrase,
+    } = opts;
+
+    let mut passphrase = Passphrase::unlock(passphrase);
+    let secret = BackupSecret {
+        ibe_sk: keyfile::read_secret(ibe_secret_path, &mut passphrase)?.to_vec(),
+        ibs_sk: keyfile::read_secret(ibs_secret_path, &mut passphrase)?.to_vec(),
+    };
+
+    let (ibe_pk, ibs_pk) = (
+        cgwkv_read_pk(ibe_public_path)?,
+        gg_read_pk(ibs_public_path)?,
+    );
+    verify_keys(
+        &ibe_pk,
+        &cgwkv_sk_from_bytes(&secret.ibe_sk)?,
+        &ibs_pk,
+        &gg_sk_from_bytes(&secret.ibs_sk)?,
+    )?;
+
+    let keys = fingerprint(
+        &std::fs::read(ibe_public_path)?,
+        &std::fs::read(ibs_public_path)?,
+    );
+
+    let secret_bytes = Zeroizing::new(
+        bincode::serialize(&secret)
+            .map_err(|e| PKGError::Backup(format!("could not serialize keys: {e}")))?,
+    );
+
+    let mut written = vec![];
+    for share in split(&secret_bytes, *threshold, *shares, &keys)? {
+        let path = Path::new(out_dir).join(format!(
+            "pkg-share-{}-{}-of-{}.txt",
+            share.id, share.index, share.total
+        ));
+
+        write_owned(&path, share.to_text())?;
+        written.push(path.display().to_string());
+    }
+
+    println!(
+        "Any {threshold} of the following {shares} shares recover the secret keys:\n{}",
+        written.join("\n")
+    );
+
+    Ok(())
+}
+
+fn exec_combine(opts: &CombineOpts) -> Result<(), PKGError> {
+    let CombineOpts {
+        share_paths,
+        ibe_secret_path,
+        ibe_public_path,
+        ibs_secret_path,
+        ibs_public_path,
+        encrypt,
+        passphrase,
+    } = opts;
+
+    let shares = share_paths
+        .iter()
+        .map(|p| KeyShare::from_text(&std::fs::read_to_string(p)?))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let keys = fingerprint(
+        &std::fs::read(ibe_public_path)?,
+        &std::fs::read(ibs_public_path)?,
+    );
+
+    if shares.iter().any(|s| s.keys != keys) {
+        return Err(PKGError::Backup(
+            "the shares do not belong to these public keys".to_string(),
+        ));
+    }
+
+    let secret: BackupSecret = bincode::deserialize(&Zeroizing::new(combine(&shares)?))
+        .map_err(|_e| PKGError::Backup("could not recover the secret keys".to_string()))?;
+
+    // Make sure the keys are correct before anything is written.
+    verify_keys(
+        &cgwkv_read_pk(ibe_public_path)?,
+        &cgwkv_sk_from_bytes(&secret.ibe_sk)?,
+        &gg_read_pk(ibs_public_path)?,
+        &gg_sk_from_bytes(&secret.ibs_sk)?,
+    )?;
+
+    let (ibe_sk, ibs_sk) = if *encrypt {
+        let mut passphrase =
+            Passphrase::new_from(passphrase.passphrase_file.clone(), PASSPHRASE_ENV);
+
+        (
+            Zeroizing::new(keyfile::seal(&secret.ibe_sk, passphrase.get()?)?),
+            Zeroizing::new(keyfile::seal(&secret.ibs_sk, passphrase.get()?)?),
+        )
+    } else {
+        (
+            Zeroizing::new(secret.ibe_sk.clone()),
+            Zeroizing::new(secret.ibs_sk.clone()),
+        )
+    };
+
+    write_pair((ibe_secret_path, &ibe_sk), (ibs_secret_path, &ibs_sk))?;
+
+    println!("The following keys were recovered:\n{ibe_secret_path}\n{ibs_secret_path}");
+
+    Ok(())
+}
+
+/// Writes two new files, such that either both are written or neither is left behind.
+fn write_pair(first: (&str, &[u8]), second: (&str, &[u8])) -> Result<(), PKGError> {
+    write_owned(first.0, first.1)?;
+
+    if let Err(e) = write_owned(second.0, second.1) {
+        let _ = std::fs::remove_file(first.0);
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+pub fn exec(backup_opts: &BackupOpts) -> Result<(), PKGError> {
+    match &backup_opts.cmd {
+        BackupCommand::Split(o) => exec_split(o),
+        BackupCommand::Combine(o) => exec_combine(o),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_combine() {
+        let secret = b"some secret key bytes".to_vec();
+        let shares = split(&secret, 3, 5, "fingerprint").unwrap();
+        assert_eq!(shares.len(), 5);
+
+        // Shares survive printing.
+        let parsed: Vec<KeyShare> = shares
+            .iter()
+            .map(|s| KeyShare::from_text(&s.to_text()).unwrap())
+            .collect();
+        assert_eq!(parsed, shares);
+
+        assert_eq!(combine(&parsed[1..4]).unwrap(), secret);
+        assert_eq!(
+            combine(&[&parsed[4..], &parsed[..2]].concat()).unwrap(),
+            secret
+        );
+
+        // Too few or duplicate shares.
+        assert!(combine(&parsed[..2]).is_err());
+        assert!(combine(&[&parsed[..2], &parsed[..1]].concat()).is_err());
+
+        // Shares from different backups do not mix.
+        let other = split(&secret, 3, 5, "fingerprint").unwrap();
+        assert!(combine(&[&parsed[..2], &other[2..3]].concat()).is_err());
+
+        // All shares must agree on the number of shares.
+        let mut inconsistent = parsed[..3].to_vec();
+        inconsistent[2].total = 6;
+        assert!(combine(&inconsistent).is_err());
+
+        assert!(split(&secret, 1, 5, "fingerprint").is_err());
+        assert!(split(&secret, 4, 3, "fingerprint").is_err());
+    }
+
+    #[test]
+    fn test_share_checksum() {
+        let share = split(b"some secret key bytes", 2, 2, "fingerprint").unwrap()[0].to_text();
+
+        // Flip a character in the encoded share.
+        let pos = share.find("\n\n").unwrap() + 3;
+        let mut tampered = share.into_bytes();
+        tampered[pos] = if tampered[pos] == b'A' { b'B' } else { b'A' };
+
+        assert!(KeyShare::from_text(std::str::from_utf8(&tampered).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_write_pair() {
+        let dir = std::env::temp_dir().join(format!("pkg-test-{}", rand::random::<u64>()));
+        std::fs::create_dir(&dir).unwrap();
+        let first = dir.join("first");
+        let second = dir.join("missing").join("second");
+        let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+        // The first file is removed again if the second cannot be written.
+        assert!(write_pair((first, b"1"), (second, b"2")).is_err());
+        assert!(!Path::new(first).exists());
+
+        let second = dir.join("second");
+        assert!(write_pair((first, b"1"), (second.to_str().unwrap(), b"2")).is_ok());
+        assert_eq!(std::fs::read(&second).unwrap(), b"2");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_keys() {
+        let mut rng = rand::thread_rng();
+        let (ibe_pk, ibe_sk) = CGWKV::setup(&mut rng);
+        let (ibs_pk, ibs_sk) = gg::setup(&mut rng);
+        let (ibe_pk2, _) = CGWKV::setup(&mut rng);
+        let (ibs_pk2, _) = gg::setup(&mut rng);
+
+        assert!(verify_keys(&ibe_pk, &ibe_sk, &ibs_pk, &ibs_sk).is_ok());
+        assert!(verify_keys(&ibe_pk2, &ibe_sk, &ibs_pk, &ibs_sk).is_err());
+        assert!(verify_keys(&ibe_pk, &ibe_sk, &ibs_pk2, &ibs_sk).is_err());
+    }
+}
@@ -12,11 +12,16 @@ pub enum Error {
     ChronologyError,
     SessionNotFound,
     UpstreamError,
+    IssuerError,
     VersionError,
     DecodingError,
     NoAttributesError,
+    DemoAttributeError,
     NoTimestampError,
+    ExpiredTimestampError,
+    EmbargoError,
     ValidityError,
+    TooManyWaiters,
     Unexpected,
 }
 
@@ -30,6 +35,12 @@ pub enum PKGError {
 
     /// Invalid version specifier.
     InvalidVersion(String),
+
+    /// Error reading or writing an encrypted key file.
+    KeyFile(String),
+
+    /// Error splitting or combining key backup shares.
+    Backup(String),
 }
 
 impl From<std::io::Error> for PKGError {
@@ -44,6 +55,8 @@ impl std::fmt::Debug for PKGError {
             PKGError::Setup(s) => write!(f, "error during PKG setup: {s}"),
             PKGError::StdIO(e) => write!(f, "IO error: {e}"),
             PKGError::InvalidVersion(v) => write!(f, "wrong version specifier: {v}"),
+            PKGError::KeyFile(s) => write!(f, "key file error: {s}"),
+            PKGError::Backup(s) => write!(f, "backup error: {s}"),
         }
     }
 }
@@ -65,11 +78,16 @@ impl ResponseError for Error {
             Error::ChronologyError | Error::VersionError => StatusCode::BAD_REQUEST,
             Error::SessionNotFound => StatusCode::NOT_FOUND,
             Error::UpstreamError => StatusCode::SERVICE_UNAVAILABLE,
+            Error::IssuerError => StatusCode::SERVICE_UNAVAILABLE,
             Error::DecodingError => StatusCode::UNAUTHORIZED,
             Error::NoAttributesError => StatusCode::FORBIDDEN,
+            Error::DemoAttributeError => StatusCode::FORBIDDEN,
             Error::ValidityError => StatusCode::BAD_REQUEST,
+            Error::TooManyWaiters => StatusCode::SERVICE_UNAVAILABLE,
             Error::Unexpected => StatusCode::INTERNAL_SERVER_ERROR,
             Error::NoTimestampError => StatusCode::BAD_REQUEST,
+            Error::ExpiredTimestampError => StatusCode::FORBIDDEN,
+            Error::EmbargoError => StatusCode::FORBIDDEN,
         }
     }
 }
@@ -81,11 +99,18 @@ impl Display for Error {
             Error::ChronologyError => write!(f, "chronology error"),
             Error::SessionNotFound => write!(f, "session not found"),
             Error::UpstreamError => write!(f, "upstream error"),
+            Error::IssuerError => write!(f, "key issuer error"),
             Error::VersionError => write!(f, "no such protocol version"),
             Error::DecodingError => write!(f, "JWT decoding error"),
             Error::ValidityError => write!(f, "validity exceeds maximum validity"),
+            Error::TooManyWaiters => write!(f, "too many sessions are being waited on"),
             Error::NoTimestampError => write!(f, "no (valid) timestamp given"),
+            Error::ExpiredTimestampError => {
+                write!(f, "timestamp is outside the key validity window")
+            }
+            Error::EmbargoError => write!(f, "the policy is embargoed until a later time"),
             Error::NoAttributesError => write!(f, "no valid attributes were disclosed"),
+            Error::DemoAttributeError => write!(f, "demo attributes are not allowed"),
             Error::Prometheus(e) => write!(f, "prometheus error: {e}"),
             Error::Unexpected => write!(f, "unexpected"),
         }
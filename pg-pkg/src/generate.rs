@@ -1,23 +1,12 @@
 use pg_core::ibs::gg;
 use pg_core::kem::cgw_kv::CGWKV;
+use pg_core::transparency::IdentitySecretKey;
 use pg_core::{kem::IBKEM, Compress};
 
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::os::unix::fs::OpenOptionsExt;
-use std::path::Path;
-
+use crate::keyfile::{self, Passphrase, PASSPHRASE_ENV};
+use crate::util::write_owned;
 use crate::{opts::*, PKGError};
 
-fn write_owned<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> std::io::Result<()> {
-    OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .mode(0o600)
-        .open(path)?
-        .write_all(contents.as_ref())
-}
-
 pub fn exec(gen_opts: &GenOpts) -> Result<(), PKGError> {
     let mut rng = rand::thread_rng();
 
@@ -27,23 +16,44 @@ pub fn exec(gen_opts: &GenOpts) -> Result<(), PKGError> {
         ibe_public_path,
         ibs_secret_path,
         ibs_public_path,
+        identity_secret_path,
+        encrypt,
+        passphrase,
     } = gen_opts;
 
+    let mut passphrase = Passphrase::new_from(passphrase.passphrase_file.clone(), PASSPHRASE_ENV);
+    let mut protect = |sk_bytes: Vec<u8>| -> Result<Vec<u8>, PKGError> {
+        if *encrypt {
+            keyfile::seal(&sk_bytes, passphrase.get()?)
+        } else {
+            Ok(sk_bytes)
+        }
+    };
+
     match scheme.as_ref() {
         "3" => {
             let (ibe_pk, ibe_sk) = CGWKV::setup(&mut rng);
             let (ibs_pk, ibs_sk) = gg::setup(&mut rng);
+            let identity_sk = IdentitySecretKey::generate(&mut rng);
 
-            println!("Keys IBE and IBS key pairs generated.");
+            println!("Keys IBE and IBS key pairs and identity key generated.");
             let ibs_pk_bytes = bincode::serialize(&ibs_pk).unwrap();
             let ibs_sk_bytes = bincode::serialize(&ibs_sk).unwrap();
 
             write_owned(ibe_public_path, ibe_pk.to_bytes().as_ref())?;
-            write_owned(ibe_secret_path, ibe_sk.to_bytes().as_ref())?;
+            write_owned(ibe_secret_path, protect(ibe_sk.to_bytes().to_vec())?)?;
             write_owned(ibs_public_path, ibs_pk_bytes)?;
-            write_owned(ibs_secret_path, ibs_sk_bytes)?;
+            write_owned(ibs_secret_path, protect(ibs_sk_bytes)?)?;
+            write_owned(
+                identity_secret_path,
+                protect(identity_sk.to_bytes().to_vec())?,
+            )?;
 
-            println!("The following keys were written:\n{ibe_public_path}\n{ibe_secret_path}\n{ibs_public_path}\n{ibs_secret_path}");
+            println!("The following keys were written:\n{ibe_public_path}\n{ibe_secret_path}\n{ibs_public_path}\n{ibs_secret_path}\n{identity_secret_path}");
+            println!(
+                "Fingerprint of the identity key: {}",
+                identity_sk.public_key().fingerprint()
+            );
         }
         x => {
             return Err(PKGError::InvalidVersion(x.into()));
@@ -0,0 +1,32 @@
+use actix_web::{web::Data, HttpResponse};
+use serde_json::json;
+
+use std::sync::atomic::Ordering;
+
+use crate::issuer::KeyIssuer;
+use crate::server::Readiness;
+
+/// Liveness: the process is up and serving requests.
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+/// Readiness: the service is able to issue keys.
+pub async fn readyz(readiness: Data<Readiness>, issuer: Data<dyn KeyIssuer>) -> HttpResponse {
+    let jwt_pk = !readiness.jwt_required || readiness.jwt_pk.load(Ordering::Acquire);
+    let keys = issuer.is_ready().await;
+
+    let body = json!({
+        "ready": jwt_pk && keys,
+        "checks": {
+            "jwtPublicKey": jwt_pk,
+            "keys": keys,
+        }
+    });
+
+    if jwt_pk && keys {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
@@ -0,0 +1,42 @@
+use actix_web::{web::Data, HttpResponse};
+
+use pg_core::api::{Algorithms, Fingerprints, ServerInfo};
+use pg_core::consts::{VERSION_V3, VERSION_V4, VERSION_V5, VERSION_V6, VERSION_V7};
+
+use crate::middleware::irma::IrmaAuthType;
+use crate::server::{IssuanceSettings, ParametersData};
+
+/// Describes the capabilities of this PKG.
+pub(crate) fn server_info(
+    settings: &IssuanceSettings,
+    auth: IrmaAuthType,
+    ibe_pd: &ParametersData,
+    ibs_pd: &ParametersData,
+    identity: Option<&str>,
+) -> ServerInfo {
+    ServerInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        wire_versions: vec![VERSION_V3, VERSION_V4, VERSION_V5, VERSION_V6, VERSION_V7],
+        modes: vec!["memory".to_string(), "stream".to_string()],
+        algorithms: Algorithms {
+            kem: "CGWKV".to_string(),
+            ibs: "GG".to_string(),
+            symmetric: "AES-128-GCM".to_string(),
+        },
+        auth_methods: vec![match auth {
+            IrmaAuthType::Jwt => "jwt".to_string(),
+            IrmaAuthType::Token => "token".to_string(),
+        }],
+        fingerprints: Fingerprints {
+            encryption: ibe_pd.etag.tag().to_string(),
+            signing: ibs_pd.etag.tag().to_string(),
+            identity: identity.map(String::from),
+        },
+        production: settings.production,
+        session_wait: true,
+    }
+}
+
+pub async fn info(info: Data<ServerInfo>) -> HttpResponse {
+    HttpResponse::Ok().json(info.get_ref())
+}
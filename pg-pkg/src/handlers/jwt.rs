@@ -2,18 +2,21 @@ use actix_web::http::header::ContentType;
 use actix_web::HttpRequest;
 use actix_web::{web::Data, HttpResponse};
 
-pub async fn jwt(irma: Data<String>, req: HttpRequest) -> Result<HttpResponse, crate::Error> {
-    let token = req.match_info().query("token");
-    let irma_url = irma.get_ref().clone();
-
-    let jwt = reqwest::get(&format!("{irma_url}/session/{token}/result-jwt"))
+/// Retrieves the signed result of a session from the IRMA server.
+pub(crate) async fn fetch_jwt(irma_url: &str, token: &str) -> Result<String, crate::Error> {
+    reqwest::get(&format!("{irma_url}/session/{token}/result-jwt"))
         .await
         .or(Err(crate::Error::Unexpected))?
         .error_for_status()
         .or(Err(crate::Error::UpstreamError))?
         .text()
         .await
-        .or(Err(crate::Error::Unexpected))?;
+        .or(Err(crate::Error::Unexpected))
+}
+
+pub async fn jwt(irma: Data<String>, req: HttpRequest) -> Result<HttpResponse, crate::Error> {
+    let token = req.match_info().query("token");
+    let jwt = fetch_jwt(irma.get_ref(), token).await?;
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::plaintext())
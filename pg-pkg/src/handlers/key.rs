@@ -1,24 +1,32 @@
-use actix_web::{web::Data, HttpResponse};
+use actix_web::web::{Data, Query};
+use actix_web::HttpResponse;
 use actix_web::{HttpMessage, HttpRequest};
 
+use irma::{ProofStatus, SessionStatus};
 use pg_core::api::KeyResponse;
-use pg_core::artifacts::UserSecretKey;
-use pg_core::identity::Policy;
-use pg_core::kem::IBKEM;
+use pg_core::identity::{Attribute, Policy};
+use serde::Deserialize;
+use zeroize::Zeroizing;
 
+use crate::issuer::KeyIssuer;
 use crate::middleware::irma::IrmaAuthResult;
+use crate::server::IssuanceSettings;
 use crate::util::current_time_u64;
 
-use serde::Serialize;
-
-pub async fn key<K>(req: HttpRequest, msk: Data<K::Sk>) -> Result<HttpResponse, crate::Error>
-where
-    K: IBKEM + 'static,
-    UserSecretKey<K>: Serialize,
-{
-    let sk = msk.get_ref();
-    let mut rng = rand::thread_rng();
+/// Optional query parameters of a key request.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyQuery {
+    /// Embargo timestamp of the policy (UNIX time).
+    not_before: Option<u64>,
+}
 
+pub async fn key(
+    req: HttpRequest,
+    issuer: Data<dyn KeyIssuer>,
+    settings: Data<IssuanceSettings>,
+    query: Query<KeyQuery>,
+) -> Result<HttpResponse, crate::Error> {
     let timestamp = req
         .match_info()
         .query("timestamp")
@@ -49,19 +57,47 @@ where
         }
     }
 
+    // It is not allowed to ask for USKs with a timestamp older than the key validity window.
+    if let Some(key_validity) = settings.key_validity {
+        if timestamp < now.saturating_sub(key_validity) {
+            return Err(crate::Error::ExpiredTimestampError);
+        }
+    }
+
+    // It is not allowed to ask for USKs before the embargo of the policy has passed.
+    if let Some(not_before) = query.not_before {
+        if not_before > now {
+            return Err(crate::Error::EmbargoError);
+        }
+    }
+
+    // In production, demo attributes can be self-issued by anyone and are never trusted.
+    if settings.production && con.iter().any(Attribute::is_demo) {
+        return Err(crate::Error::DemoAttributeError);
+    }
+
     req.extensions_mut().clear();
 
-    let policy = Policy { timestamp, con };
+    // The key remains `None` until the session is done and the proof is valid.
+    if status != SessionStatus::Done || proof_status != Some(ProofStatus::Valid) {
+        return Ok(HttpResponse::Ok().json(KeyResponse::<()> {
+            status,
+            proof_status,
+            key: None,
+        }));
+    }
 
-    let id = policy
-        .derive_kem::<K>()
-        .map_err(|_e| crate::Error::Unexpected)?;
+    let policy = Policy {
+        timestamp,
+        con,
+        not_before: query.not_before,
+    };
 
-    let usk = K::extract_usk(None, sk, &id, &mut rng);
+    let usk = Zeroizing::new(issuer.extract_usk(&policy).await?);
 
     Ok(HttpResponse::Ok().json(KeyResponse {
         status,
         proof_status,
-        key: Some(UserSecretKey::<K>(usk)),
+        key: Some(&*usk),
     }))
 }
@@ -1,13 +1,19 @@
+mod health;
+mod info;
 mod jwt;
 mod key;
 mod metrics;
 mod parameters;
 mod signing_key;
 mod start;
+mod wait;
 
+pub use health::*;
+pub use info::*;
 pub use jwt::*;
 pub use key::*;
 pub use metrics::*;
 pub use parameters::*;
 pub use signing_key::*;
 pub use start::*;
+pub use wait::*;
@@ -1,23 +1,22 @@
 use actix_web::{web::Data, web::Json, HttpResponse};
 use actix_web::{HttpMessage, HttpRequest};
 
-use irma::SessionStatus;
+use irma::{ProofStatus, SessionStatus};
 use pg_core::api::{SigningKeyRequest, SigningKeyResponse};
-use pg_core::artifacts::{SigningKey, SigningKeyExt};
-use pg_core::ibs::gg::{keygen, SecretKey};
+use pg_core::artifacts::SigningKeyExt;
 use pg_core::identity::{Attribute, Policy};
 
+use crate::issuer::KeyIssuer;
 use crate::middleware::irma::IrmaAuthResult;
+use crate::server::IssuanceSettings;
 use crate::util::current_time_u64;
 
 pub async fn signing_key(
     req: HttpRequest,
-    msk: Data<SecretKey>,
+    issuer: Data<dyn KeyIssuer>,
+    settings: Data<IssuanceSettings>,
     body: Json<SigningKeyRequest>,
 ) -> Result<HttpResponse, crate::Error> {
-    let sk = msk.get_ref();
-    let mut rng = rand::thread_rng();
-
     let IrmaAuthResult {
         con,
         status,
@@ -29,22 +28,25 @@ pub async fn signing_key(
         .cloned()
         .ok_or(crate::Error::Unexpected)?;
 
+    // In production, demo attributes can be self-issued by anyone and are never trusted.
+    if settings.production && con.iter().any(Attribute::is_demo) {
+        return Err(crate::Error::DemoAttributeError);
+    }
+
     req.extensions_mut().clear();
 
     // The PKG gets to decide the timestamp in the policy.
     let iat = current_time_u64()?;
     let body = body.into_inner();
 
-    match status {
-        SessionStatus::Done => (),
-        _ => {
-            return Ok(HttpResponse::Ok().json(SigningKeyResponse {
-                status,
-                proof_status,
-                pub_sign_key: None,
-                priv_sign_key: None,
-            }))
-        }
+    // The keys remain `None` until the session is done and the proof is valid.
+    if status != SessionStatus::Done || proof_status != Some(ProofStatus::Valid) {
+        return Ok(HttpResponse::Ok().json(SigningKeyResponse {
+            status,
+            proof_status,
+            pub_sign_key: None,
+            priv_sign_key: None,
+        }));
     }
 
     let pub_con = con
@@ -62,36 +64,31 @@ pub async fn signing_key(
     let policy = Policy {
         timestamp: iat,
         con: pub_con,
+        not_before: None,
     };
-    let id = policy.derive_ibs().map_err(|_e| crate::Error::Unexpected)?;
-    let key = keygen(sk, &id, &mut rng);
+    let key = issuer.signing_key(&policy).await?;
 
-    let pub_sign_key = SigningKeyExt {
-        key: SigningKey(key),
-        policy,
-    };
+    let pub_sign_key = SigningKeyExt { key, policy };
 
-    let priv_sign_key = body.priv_sign_id.as_ref().map(|priv_sign_id| {
-        let priv_con = con
-            .clone()
-            .into_iter()
-            .filter(|a| priv_sign_id.contains(&Attribute::new(&a.atype, None)))
-            .collect();
-        let policy = Policy {
-            timestamp: iat,
-            con: priv_con,
-        };
+    let priv_sign_key = match body.priv_sign_id.as_ref() {
+        Some(priv_sign_id) => {
+            let priv_con = con
+                .clone()
+                .into_iter()
+                .filter(|a| priv_sign_id.contains(&Attribute::new(&a.atype, None)))
+                .collect();
+            let policy = Policy {
+                timestamp: iat,
+                con: priv_con,
+                not_before: None,
+            };
 
-        let id = policy.derive_ibs().map_err(|_e| crate::Error::Unexpected)?;
-        let key = keygen(sk, &id, &mut rng);
+            let key = issuer.signing_key(&policy).await?;
 
-        Ok(SigningKeyExt {
-            key: SigningKey(key),
-            policy,
-        })
-    });
-
-    let priv_sign_key = priv_sign_key.map_or(Ok(None), |r| r.map(Some))?;
+            Some(SigningKeyExt { key, policy })
+        }
+        None => None,
+    };
 
     Ok(HttpResponse::Ok().json(SigningKeyResponse {
         status,
@@ -1,17 +1,20 @@
+use crate::middleware::irma::TokenSessions;
+use crate::util::current_time_u64;
 use crate::Error;
 use actix_web::{web::Data, web::Json, HttpResponse};
 use irma::*;
 use pg_core::api::IrmaAuthRequest;
 
 /// Maximum allowed valitidy (in seconds) of a JWT (1 day).
-const MAX_VALIDITY: u64 = 60 * 60 * 24;
+pub(crate) const MAX_VALIDITY: u64 = 60 * 60 * 24;
 
 /// Default validity if no validity is specified (5 min).
-const DEFAULT_VALIDITY: u64 = 60 * 5;
+pub(crate) const DEFAULT_VALIDITY: u64 = 60 * 5;
 
 pub async fn start(
     url: Data<String>,
     value: Json<IrmaAuthRequest>,
+    token_sessions: Option<Data<TokenSessions>>,
 ) -> Result<HttpResponse, crate::Error> {
     let irma_url = url.get_ref().clone();
     let kr = value.into_inner();
@@ -53,5 +56,10 @@ pub async fn start(
         .await
         .or(Err(crate::Error::Unexpected))?;
 
+    // Without JWTs, the PKG has to remember the requested validity itself.
+    if let Some(token_sessions) = token_sessions {
+        token_sessions.start(&session.token.0, validity, current_time_u64()?);
+    }
+
     Ok(HttpResponse::Ok().json(session))
 }
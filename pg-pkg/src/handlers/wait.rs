@@ -0,0 +1,182 @@
+use actix_web::{web::Data, HttpRequest, HttpResponse};
+use irma::{IrmaClientBuilder, SessionStatus, SessionToken};
+use pg_core::api::SessionWaitResponse;
+use tokio::sync::watch;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::handlers::fetch_jwt;
+
+/// Interval at which the status of a session is retrieved from the IRMA server.
+///
+/// All requests waiting on the same session share a single poller.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The outcome of retrieving the status of a session.
+#[derive(Debug, Clone)]
+enum Polled {
+    Status(SessionStatus),
+    NotFound,
+    Failed,
+}
+
+/// The latest outcome of the poller of each session that is being waited on.
+type Pollers = Mutex<HashMap<String, Arc<watch::Sender<Option<Polled>>>>>;
+
+/// Long-polling of sessions, shared between workers.
+#[derive(Debug)]
+pub struct SessionWaiter {
+    irma_url: String,
+    timeout: Duration,
+    max_waiters: usize,
+    waiters: AtomicUsize,
+    jwt: bool,
+    pollers: Pollers,
+}
+
+/// A slot for a single waiter, released when dropped, e.g., when the client disconnects.
+struct Slot<'a>(&'a AtomicUsize);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl SessionWaiter {
+    /// Waits at most `timeout` per request, for at most `max_waiters` requests at once.
+    ///
+    /// If `jwt` is set, the signed session result is included once the session is done.
+    pub fn new(irma_url: &str, timeout: Duration, max_waiters: usize, jwt: bool) -> Self {
+        Self {
+            irma_url: irma_url.to_string(),
+            timeout,
+            max_waiters,
+            waiters: AtomicUsize::new(0),
+            jwt,
+            pollers: Mutex::default(),
+        }
+    }
+
+    fn acquire(&self) -> Option<Slot<'_>> {
+        let slot = Slot(&self.waiters);
+
+        // If the limit is exceeded, the slot is released again right away.
+        (self.waiters.fetch_add(1, Ordering::AcqRel) < self.max_waiters).then_some(slot)
+    }
+}
+
+fn is_finished(status: &SessionStatus) -> bool {
+    matches!(
+        status,
+        SessionStatus::Done | SessionStatus::Cancelled | SessionStatus::Timeout
+    )
+}
+
+/// Subscribes to the status of a session, starting a poller if there is none yet.
+fn subscribe(
+    waiter: &Data<SessionWaiter>,
+    token: &SessionToken,
+) -> watch::Receiver<Option<Polled>> {
+    let mut pollers = waiter.pollers.lock().unwrap();
+
+    if let Some(tx) = pollers.get(&token.0) {
+        return tx.subscribe();
+    }
+
+    let (tx, rx) = watch::channel(None);
+    let tx = Arc::new(tx);
+    pollers.insert(token.0.clone(), tx.clone());
+    actix_rt::spawn(poll(waiter.clone(), token.clone(), tx));
+
+    rx
+}
+
+/// Retrieves the status of a session until it is final, fails, or nobody is waiting anymore.
+async fn poll(
+    waiter: Data<SessionWaiter>,
+    token: SessionToken,
+    tx: Arc<watch::Sender<Option<Polled>>>,
+) {
+    let client = IrmaClientBuilder::new(&waiter.irma_url).map(|builder| builder.build());
+
+    loop {
+        let polled = match &client {
+            Ok(client) => match client.status(&token).await {
+                Ok(status) => Polled::Status(status),
+                Err(irma::Error::NetworkError(e))
+                    if e.status() == Some(reqwest::StatusCode::BAD_REQUEST)
+                        || e.status() == Some(reqwest::StatusCode::NOT_FOUND) =>
+                {
+                    Polled::NotFound
+                }
+                Err(e) => {
+                    log::error!("could not retrieve session status: {e}");
+                    Polled::Failed
+                }
+            },
+            Err(e) => {
+                log::error!("could not create IRMA client: {e}");
+                Polled::Failed
+            }
+        };
+
+        let last = !matches!(&polled, Polled::Status(status) if !is_finished(status));
+        tx.send_replace(Some(polled));
+
+        if !last {
+            actix_rt::time::sleep(POLL_INTERVAL).await;
+        }
+
+        // Removing the poller under the lock ensures no waiter subscribes to a stopped poller.
+        let mut pollers = waiter.pollers.lock().unwrap();
+        if last || tx.receiver_count() == 0 {
+            pollers.remove(&token.0);
+            return;
+        }
+    }
+}
+
+/// Waits until the status of a session is final, or the timeout passes.
+pub async fn wait(
+    waiter: Data<SessionWaiter>,
+    req: HttpRequest,
+) -> Result<HttpResponse, crate::Error> {
+    let token = SessionToken(req.match_info().query("token").to_string());
+    let _slot = waiter.acquire().ok_or(crate::Error::TooManyWaiters)?;
+    let mut rx = subscribe(&waiter, &token);
+
+    let deadline = Instant::now() + waiter.timeout;
+    loop {
+        let finished =
+            matches!(&*rx.borrow_and_update(), Some(Polled::Status(status)) if is_finished(status));
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        // The channel closes once the poller has stopped, after which the status stays the same.
+        if finished
+            || !matches!(
+                actix_rt::time::timeout(remaining, rx.changed()).await,
+                Ok(Ok(()))
+            )
+        {
+            break;
+        }
+    }
+
+    let polled = rx.borrow().clone();
+    let status = match polled {
+        Some(Polled::Status(status)) => status,
+        Some(Polled::NotFound) => return Err(crate::Error::SessionNotFound),
+        Some(Polled::Failed) | None => return Err(crate::Error::UpstreamError),
+    };
+
+    let jwt = match status {
+        SessionStatus::Done if waiter.jwt => Some(fetch_jwt(&waiter.irma_url, &token.0).await?),
+        _ => None,
+    };
+
+    Ok(HttpResponse::Ok().json(SessionWaitResponse { status, jwt }))
+}
@@ -0,0 +1,156 @@
+//! Issuing of user secret keys and signing keys.
+//!
+//! Handlers never touch the master secret keys directly, they only depend on a [`KeyIssuer`].
+//! By default, the master secret keys are read from files and kept in process memory (see
+//! [`LocalIssuer`]). Alternatively, key extraction can be delegated to an external process that
+//! holds the master secret keys, e.g., a daemon backed by a hardware security module, using
+//! [`socket::SocketIssuer`].
+
+pub mod socket;
+
+use futures::future::{ready, LocalBoxFuture};
+use futures::FutureExt;
+use zeroize::Zeroize;
+
+use pg_core::artifacts::{SecretKey, SigningKey, UserSecretKey};
+use pg_core::ibs::gg;
+use pg_core::identity::Policy;
+use pg_core::kem::{cgw_kv::CGWKV, IBKEM};
+
+use crate::Error;
+
+/// An oracle that issues keys using the master secret keys of the PKG.
+///
+/// Implementors are responsible for deriving the identity from the policy. The policy is
+/// assumed to have been authenticated and checked by the caller.
+pub trait KeyIssuer: Send + Sync {
+    /// Extracts a user secret key (decryption) for a policy.
+    fn extract_usk<'a>(
+        &'a self,
+        policy: &'a Policy,
+    ) -> LocalBoxFuture<'a, Result<UserSecretKey<CGWKV>, Error>>;
+
+    /// Generates a signing key for a policy.
+    fn signing_key<'a>(
+        &'a self,
+        policy: &'a Policy,
+    ) -> LocalBoxFuture<'a, Result<SigningKey, Error>>;
+
+    /// Whether the issuer is able to issue keys.
+    fn is_ready(&self) -> LocalBoxFuture<'_, bool> {
+        ready(true).boxed_local()
+    }
+}
+
+/// Issues keys using master secret keys held in process memory.
+///
+/// The master secret keys are wiped from memory when the issuer is dropped.
+#[derive(Debug, Clone)]
+pub struct LocalIssuer {
+    ibe_sk: SecretKey<CGWKV>,
+    ibs_sk: gg::SecretKey,
+}
+
+impl LocalIssuer {
+    /// Create a new issuer from the master secret keys.
+    pub fn new(ibe_sk: <CGWKV as IBKEM>::Sk, ibs_sk: gg::SecretKey) -> Self {
+        Self {
+            ibe_sk: SecretKey(ibe_sk),
+            ibs_sk,
+        }
+    }
+}
+
+impl Zeroize for LocalIssuer {
+    fn zeroize(&mut self) {
+        self.ibe_sk.zeroize();
+        self.ibs_sk.zeroize();
+    }
+}
+
+impl Drop for LocalIssuer {
+    fn drop(&mut self) {
+        self.zeroize();
+
+        #[cfg(test)]
+        tests::record_drop(self);
+    }
+}
+
+impl KeyIssuer for LocalIssuer {
+    fn extract_usk<'a>(
+        &'a self,
+        policy: &'a Policy,
+    ) -> LocalBoxFuture<'a, Result<UserSecretKey<CGWKV>, Error>> {
+        let res = policy
+            .derive_kem::<CGWKV>()
+            .map_err(|_e| Error::Unexpected)
+            .map(|id| {
+                let usk = CGWKV::extract_usk(None, &self.ibe_sk.0, &id, &mut rand::thread_rng());
+                UserSecretKey(usk)
+            });
+
+        ready(res).boxed_local()
+    }
+
+    fn signing_key<'a>(
+        &'a self,
+        policy: &'a Policy,
+    ) -> LocalBoxFuture<'a, Result<SigningKey, Error>> {
+        let res = policy
+            .derive_ibs()
+            .map_err(|_e| Error::Unexpected)
+            .map(|id| SigningKey(gg::keygen(&self.ibs_sk, &id, &mut rand::thread_rng())));
+
+        ready(res).boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pg_core::Compress;
+    use std::cell::RefCell;
+
+    thread_local! {
+        // The key bytes left behind by the last issuer dropped on this thread.
+        static DROPPED: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+    }
+
+    /// Records the keys of an issuer at the end of [`LocalIssuer::drop`], while they are still
+    /// alive.
+    pub(super) fn record_drop(issuer: &LocalIssuer) {
+        let mut bytes = issuer.ibe_sk.0.to_bytes().as_ref().to_vec();
+        bytes.extend(bincode::serialize(&issuer.ibs_sk).unwrap());
+        DROPPED.with(|dropped| *dropped.borrow_mut() = Some(bytes));
+    }
+
+    #[test]
+    fn test_zeroize_wipes_keys() {
+        let mut rng = rand::thread_rng();
+        let (_, ibe_sk) = CGWKV::setup(&mut rng);
+        let (_, ibs_sk) = gg::setup(&mut rng);
+
+        let mut issuer = LocalIssuer::new(ibe_sk, ibs_sk);
+        assert!(issuer.ibe_sk.0.to_bytes().iter().any(|&b| b != 0));
+
+        issuer.zeroize();
+
+        let ibs_bytes = bincode::serialize(&issuer.ibs_sk).unwrap();
+        assert!(issuer.ibe_sk.0.to_bytes().iter().all(|&b| b == 0));
+        assert!(ibs_bytes.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_drop_wipes_keys() {
+        let mut rng = rand::thread_rng();
+        let (_, ibe_sk) = CGWKV::setup(&mut rng);
+        let (_, ibs_sk) = gg::setup(&mut rng);
+
+        drop(LocalIssuer::new(ibe_sk, ibs_sk));
+
+        let bytes = DROPPED.with(|dropped| dropped.take()).unwrap();
+        assert!(bytes.iter().all(|&b| b == 0));
+    }
+}
@@ -0,0 +1,241 @@
+//! Delegation of key issuing to an external process over a local Unix socket.
+//!
+//! The protocol is a simple request-response protocol. Each message is a Bincode-serialized
+//! [`Request`] or [`Response`], prefixed by its length as a big-endian `u32`. The process on the
+//! other end of the socket can be anything that speaks this protocol. For testing, and as a
+//! reference implementation, [`serve`] exposes any [`KeyIssuer`] over a socket.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_rt::net::{UnixListener, UnixStream};
+use actix_rt::time::timeout;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use zeroize::{Zeroize, Zeroizing};
+
+use pg_core::artifacts::{SigningKey, UserSecretKey};
+use pg_core::identity::Policy;
+use pg_core::kem::cgw_kv::CGWKV;
+
+use super::KeyIssuer;
+use crate::Error;
+
+/// The maximum size of a single message (64 KiB).
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// The default time a request may take, including connecting (10 seconds).
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A request to the key issuing process.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Request {
+    /// Extract a user secret key for a policy.
+    Usk(Policy),
+
+    /// Generate a signing key for a policy.
+    SigningKey(Policy),
+}
+
+/// A response from the key issuing process.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Response {
+    /// A user secret key.
+    Usk(Box<UserSecretKey<CGWKV>>),
+
+    /// A signing key.
+    SigningKey(Box<SigningKey>),
+
+    /// The request could not be handled.
+    Error,
+}
+
+impl Zeroize for Response {
+    fn zeroize(&mut self) {
+        match self {
+            Response::Usk(usk) => usk.as_mut().zeroize(),
+            Response::SigningKey(key) => key.as_mut().zeroize(),
+            Response::Error => {}
+        }
+    }
+}
+
+async fn write_message<T: Serialize>(stream: &mut UnixStream, msg: &T) -> std::io::Result<()> {
+    let bytes = Zeroizing::new(
+        bincode::serialize(msg)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+    );
+    let len = u32::try_from(bytes.len())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    stream.write_all(&len.to_be_bytes()).await?;
+    stream.write_all(&bytes).await?;
+    stream.flush().await
+}
+
+async fn read_message<T: DeserializeOwned>(stream: &mut UnixStream) -> std::io::Result<T> {
+    let mut len_bytes = [0u8; 4];
+    stream.read_exact(&mut len_bytes).await?;
+
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "message too large",
+        ));
+    }
+
+    let mut buf = Zeroizing::new(vec![0u8; len]);
+    stream.read_exact(&mut buf).await?;
+
+    bincode::deserialize(&buf).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Issues keys by delegating to an external process listening on a Unix socket.
+#[derive(Debug, Clone)]
+pub struct SocketIssuer {
+    path: PathBuf,
+    timeout: Duration,
+}
+
+impl SocketIssuer {
+    /// Create a new issuer that connects to the socket at `path`.
+    ///
+    /// A new connection is made for every request.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Set the time a request may take, including connecting.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn request(&self, req: &Request) -> Result<Response, Error> {
+        timeout(self.timeout, self.exchange(req))
+            .await
+            .map_err(|_elapsed| {
+                log::error!("key issuer did not respond within {:?}", self.timeout);
+                Error::IssuerError
+            })?
+    }
+
+    async fn exchange(&self, req: &Request) -> Result<Response, Error> {
+        let mut stream = UnixStream::connect(&self.path).await.map_err(|e| {
+            log::error!("could not connect to key issuer: {e}");
+            Error::IssuerError
+        })?;
+
+        write_message(&mut stream, req).await.map_err(|e| {
+            log::error!("could not send request to key issuer: {e}");
+            Error::IssuerError
+        })?;
+
+        read_message(&mut stream).await.map_err(|e| {
+            log::error!("could not read response from key issuer: {e}");
+            Error::IssuerError
+        })
+    }
+}
+
+impl KeyIssuer for SocketIssuer {
+    fn extract_usk<'a>(
+        &'a self,
+        policy: &'a Policy,
+    ) -> LocalBoxFuture<'a, Result<UserSecretKey<CGWKV>, Error>> {
+        async move {
+            let resp = Zeroizing::new(self.request(&Request::Usk(policy.clone())).await?);
+            match &*resp {
+                Response::Usk(usk) => Ok(usk.as_ref().clone()),
+                _ => Err(Error::IssuerError),
+            }
+        }
+        .boxed_local()
+    }
+
+    fn signing_key<'a>(
+        &'a self,
+        policy: &'a Policy,
+    ) -> LocalBoxFuture<'a, Result<SigningKey, Error>> {
+        async move {
+            match self.request(&Request::SigningKey(policy.clone())).await? {
+                Response::SigningKey(key) => Ok(*key),
+                _ => Err(Error::IssuerError),
+            }
+        }
+        .boxed_local()
+    }
+
+    fn is_ready(&self) -> LocalBoxFuture<'_, bool> {
+        async move {
+            timeout(self.timeout, UnixStream::connect(&self.path))
+                .await
+                .is_ok_and(|res| res.is_ok())
+        }
+        .boxed_local()
+    }
+}
+
+async fn handle(
+    mut stream: UnixStream,
+    issuer: Arc<dyn KeyIssuer>,
+    read_timeout: Duration,
+) -> std::io::Result<()> {
+    loop {
+        let req: Request = match timeout(read_timeout, read_message(&mut stream)).await {
+            Ok(Ok(req)) => req,
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Ok(Err(e)) => return Err(e),
+            Err(_elapsed) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "no request received in time",
+                ))
+            }
+        };
+
+        let resp = Zeroizing::new(match req {
+            Request::Usk(pol) => issuer
+                .extract_usk(&pol)
+                .await
+                .map_or(Response::Error, |usk| Response::Usk(Box::new(usk))),
+            Request::SigningKey(pol) => issuer
+                .signing_key(&pol)
+                .await
+                .map_or(Response::Error, |key| Response::SigningKey(Box::new(key))),
+        });
+
+        write_message(&mut stream, &*resp).await?;
+    }
+}
+
+/// Serves requests for keys from a [`KeyIssuer`] on a Unix socket.
+///
+/// Runs until accepting a connection fails. Each connection is handled on a separate task, and
+/// closed if no complete request arrives within `read_timeout`.
+pub async fn serve(
+    listener: UnixListener,
+    issuer: Arc<dyn KeyIssuer>,
+    read_timeout: Duration,
+) -> std::io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let issuer = issuer.clone();
+
+        actix_rt::spawn(async move {
+            if let Err(e) = handle(stream, issuer, read_timeout).await {
+                log::warn!("key issuer connection closed: {e}");
+            }
+        });
+    }
+}
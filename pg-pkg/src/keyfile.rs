@@ -0,0 +1,321 @@
+//! Encrypted-at-rest master secret key files.
+//!
+//! A secret key file is either plain, i.e., the raw serialized secret key, or encrypted using a
+//! passphrase. Encrypted key files use the following versioned container format:
+//!
+//! ```text
+//! MAGIC (8) || VERSION (1) || M_COST (4) || T_COST (4) || P_COST (4) || SALT (16) || NONCE (12) || CIPHERTEXT
+//! ```
+//!
+//! The passphrase is stretched into a key using Argon2id with the stored cost parameters and
+//! salt. The secret key is encrypted using AES-256-GCM, where everything preceding the ciphertext
+//! is authenticated as associated data. All integers are big-endian. Since the cost parameters are
+//! only authenticated after deriving the key, key files with excessive costs are rejected.
+//!
+//! Passphrases, derived keys and decrypted secret keys are wiped from memory when dropped.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use std::path::Path;
+
+use crate::opts::PassphraseOpts;
+use crate::PKGError;
+
+/// Magic bytes identifying an encrypted key file.
+const MAGIC: &[u8; 8] = b"PGPKGSEC";
+
+/// Current version of the container format.
+const VERSION_V1: u8 = 1;
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const KEY_SIZE: usize = 32;
+
+/// Size of everything preceding the ciphertext.
+const HEADER_SIZE: usize = MAGIC.len() + 1 + 3 * 4 + SALT_SIZE + NONCE_SIZE;
+
+/// Environment variable containing the passphrase of the secret key files.
+pub(crate) const PASSPHRASE_ENV: &str = "PKG_PASSPHRASE";
+
+/// Environment variable containing the new passphrase when rewrapping secret key files.
+pub(crate) const NEW_PASSPHRASE_ENV: &str = "PKG_NEW_PASSPHRASE";
+
+/// Argon2id cost parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KdfParams {
+    /// Memory size in KiB.
+    pub m_cost: u32,
+
+    /// Number of iterations.
+    pub t_cost: u32,
+
+    /// Degree of parallelism.
+    pub p_cost: u32,
+}
+
+/// The most expensive cost parameters accepted when opening a key file: 1 GiB, 16 iterations and
+/// 8 lanes. A key file is untrusted input, so this bounds the work done before it is
+/// authenticated.
+const MAX_KDF_PARAMS: KdfParams = KdfParams {
+    m_cost: 1024 * 1024,
+    t_cost: 16,
+    p_cost: 8,
+};
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        // 64 MiB, 3 iterations, single lane.
+        Self {
+            m_cost: 64 * 1024,
+            t_cost: 3,
+            p_cost: 1,
+        }
+    }
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    params: &KdfParams,
+) -> Result<Zeroizing<[u8; KEY_SIZE]>, PKGError> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KEY_SIZE))
+        .map_err(|e| PKGError::KeyFile(format!("invalid KDF parameters: {e}")))?;
+
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+        .map_err(|e| PKGError::KeyFile(format!("could not derive key: {e}")))?;
+
+    Ok(key)
+}
+
+/// Returns whether the contents of a key file are encrypted.
+pub(crate) fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Encrypts a serialized secret key using a passphrase.
+pub(crate) fn seal(plain: &[u8], passphrase: &str) -> Result<Vec<u8>, PKGError> {
+    seal_with(plain, passphrase, &KdfParams::default())
+}
+
+pub(crate) fn seal_with(
+    plain: &[u8],
+    passphrase: &str,
+    params: &KdfParams,
+) -> Result<Vec<u8>, PKGError> {
+    let mut rng = rand::thread_rng();
+
+    let mut salt = [0u8; SALT_SIZE];
+    let mut nonce = [0u8; NONCE_SIZE];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let mut out = Vec::with_capacity(HEADER_SIZE + plain.len() + 16);
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION_V1);
+    out.extend_from_slice(&params.m_cost.to_be_bytes());
+    out.extend_from_slice(&params.t_cost.to_be_bytes());
+    out.extend_from_slice(&params.p_cost.to_be_bytes());
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let key = derive_key(passphrase, &salt, params)?;
+    let ct = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key[..]))
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plain,
+                aad: &out,
+            },
+        )
+        .map_err(|_e| PKGError::KeyFile("could not encrypt key file".to_string()))?;
+
+    out.extend_from_slice(&ct);
+
+    Ok(out)
+}
+
+/// Decrypts an encrypted key file using a passphrase.
+pub(crate) fn open(bytes: &[u8], passphrase: &str) -> Result<Zeroizing<Vec<u8>>, PKGError> {
+    if !is_encrypted(bytes) || bytes.len() < HEADER_SIZE {
+        return Err(PKGError::KeyFile("not an encrypted key file".to_string()));
+    }
+
+    let (header, ct) = bytes.split_at(HEADER_SIZE);
+    let rest = &header[MAGIC.len()..];
+
+    if rest[0] != VERSION_V1 {
+        return Err(PKGError::KeyFile(format!(
+            "unsupported key file version: {}",
+            rest[0]
+        )));
+    }
+
+    let u32_at = |i: usize| u32::from_be_bytes([rest[i], rest[i + 1], rest[i + 2], rest[i + 3]]);
+    let params = KdfParams {
+        m_cost: u32_at(1),
+        t_cost: u32_at(5),
+        p_cost: u32_at(9),
+    };
+
+    if params.m_cost > MAX_KDF_PARAMS.m_cost
+        || params.t_cost > MAX_KDF_PARAMS.t_cost
+        || params.p_cost > MAX_KDF_PARAMS.p_cost
+    {
+        return Err(PKGError::KeyFile(format!(
+            "KDF parameters exceed the maximum of {MAX_KDF_PARAMS:?}"
+        )));
+    }
+
+    let salt = &rest[13..13 + SALT_SIZE];
+    let nonce = &rest[13 + SALT_SIZE..];
+
+    let key = derive_key(passphrase, salt, &params)?;
+
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key[..]))
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ct,
+                aad: header,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_e| PKGError::KeyFile("wrong passphrase or corrupted key file".to_string()))
+}
+
+/// Obtains a passphrase from a file, an environment variable or an interactive prompt, in that
+/// order. The passphrase is only obtained when it is first needed, and then reused.
+#[derive(Debug)]
+pub(crate) struct Passphrase {
+    file: Option<String>,
+    env: &'static str,
+    prompt: &'static str,
+    confirm: bool,
+    cached: Option<Zeroizing<String>>,
+}
+
+impl Passphrase {
+    /// The passphrase to unlock existing key files.
+    pub(crate) fn unlock(opts: &PassphraseOpts) -> Self {
+        Self {
+            file: opts.passphrase_file.clone(),
+            env: PASSPHRASE_ENV,
+            prompt: "Passphrase: ",
+            confirm: false,
+            cached: None,
+        }
+    }
+
+    /// A passphrase to encrypt new key files, which is asked twice when prompting.
+    pub(crate) fn new_from(file: Option<String>, env: &'static str) -> Self {
+        Self {
+            file,
+            env,
+            prompt: "New passphrase: ",
+            confirm: true,
+            cached: None,
+        }
+    }
+
+    /// Returns the passphrase.
+    pub(crate) fn get(&mut self) -> Result<&str, PKGError> {
+        if self.cached.is_none() {
+            self.cached = Some(self.obtain()?);
+        }
+
+        Ok(self.cached.as_ref().map_or("", |p| p.as_str()))
+    }
+
+    fn obtain(&self) -> Result<Zeroizing<String>, PKGError> {
+        let passphrase = if let Some(file) = &self.file {
+            let contents = Zeroizing::new(std::fs::read_to_string(file)?);
+            Zeroizing::new(contents.trim_end_matches(['\r', '\n']).to_string())
+        } else if let Ok(passphrase) = std::env::var(self.env) {
+            Zeroizing::new(passphrase)
+        } else {
+            let passphrase = Zeroizing::new(rpassword::prompt_password(self.prompt)?);
+            let confirm = || rpassword::prompt_password("Confirm passphrase: ").map(Zeroizing::new);
+            if self.confirm && passphrase != confirm()? {
+                return Err(PKGError::KeyFile("passphrases do not match".to_string()));
+            }
+
+            passphrase
+        };
+
+        if passphrase.is_empty() {
+            return Err(PKGError::KeyFile("empty passphrase".to_string()));
+        }
+
+        Ok(passphrase)
+    }
+}
+
+/// Reads a secret key file, decrypting it if it is encrypted.
+pub(crate) fn read_secret(
+    path: impl AsRef<Path>,
+    passphrase: &mut Passphrase,
+) -> Result<Zeroizing<Vec<u8>>, PKGError> {
+    let bytes = Zeroizing::new(std::fs::read(path)?);
+
+    if is_encrypted(&bytes) {
+        open(&bytes, passphrase.get()?)
+    } else {
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PARAMS: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn test_round() {
+        let plain = b"some secret key bytes";
+        let sealed = seal_with(plain, "correct horse", &TEST_PARAMS).unwrap();
+
+        assert!(is_encrypted(&sealed));
+        assert!(!is_encrypted(plain));
+        assert_eq!(&open(&sealed, "correct horse").unwrap()[..], plain);
+        assert!(open(&sealed, "battery staple").is_err());
+    }
+
+    #[test]
+    fn test_tampered() {
+        let mut sealed = seal_with(b"some secret key bytes", "pass", &TEST_PARAMS).unwrap();
+
+        // The header is authenticated as well.
+        sealed[MAGIC.len() + 1 + 3 * 4] ^= 1;
+        assert!(open(&sealed, "pass").is_err());
+
+        assert!(open(&sealed[..HEADER_SIZE - 1], "pass").is_err());
+    }
+
+    #[test]
+    fn test_kdf_params_bounded() {
+        let sealed = seal_with(b"some secret key bytes", "pass", &TEST_PARAMS).unwrap();
+
+        // Each cost parameter is checked before deriving a key.
+        for offset in [1, 5, 9] {
+            let mut tampered = sealed.clone();
+            let at = MAGIC.len() + offset;
+            tampered[at..at + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+            assert!(matches!(
+                open(&tampered, "pass"),
+                Err(PKGError::KeyFile(e)) if e.contains("exceed the maximum")
+            ));
+        }
+    }
+}
@@ -1,9 +1,15 @@
+mod backup;
 mod error;
 mod generate;
 mod handlers;
+mod issuer;
+mod keyfile;
 mod middleware;
 mod opts;
+mod oracle;
+mod rewrap;
 mod server;
+mod transparency;
 mod util;
 
 pub use crate::error::*;
@@ -17,6 +23,9 @@ fn main() -> Result<(), PKGError> {
     match opts.subcmd {
         Subcommand::Gen(o) => crate::generate::exec(&o)?,
         Subcommand::Server(o) => crate::server::exec(o)?,
+        Subcommand::Oracle(o) => crate::oracle::exec(o)?,
+        Subcommand::Rewrap(o) => crate::rewrap::exec(&o)?,
+        Subcommand::Backup(o) => crate::backup::exec(&o)?,
     }
 
     Ok(())
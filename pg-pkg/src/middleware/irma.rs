@@ -8,15 +8,21 @@ use actix_web_httpauth::extractors::bearer::BearerAuth;
 
 use futures::FutureExt;
 use futures_util::future::LocalBoxFuture;
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 use irma::*;
 use pg_core::identity::Attribute;
 
-use jsonwebtoken::{decode, errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{Algorithm, Validation};
 
 use serde::{Deserialize, Serialize};
 
+use crate::handlers::{DEFAULT_VALIDITY, MAX_VALIDITY};
+use crate::middleware::jwt_keys::JwtKeys;
+use crate::util::current_time_u64;
+
 #[derive(Debug, Clone)]
 pub(crate) struct IrmaAuthResult {
     pub con: Vec<Attribute>,
@@ -48,13 +54,66 @@ struct Claims {
     disclosed: Vec<Vec<DisclosedAttribute>>,
 }
 
+#[derive(Debug, Clone, Copy)]
+struct TokenSession {
+    started: u64,
+    validity: u64,
+    completed: Option<u64>,
+}
+
+/// Validities and completion times of sessions authenticated using tokens.
+///
+/// Unlike JWTs, session results retrieved using a token carry no expiry. Instead, the PKG keeps
+/// track of when it first saw a session completed. From then on, the session is valid for the
+/// validity requested at its start, or the default validity if the session was not started by
+/// this PKG.
+#[derive(Debug, Default)]
+pub struct TokenSessions {
+    sessions: Mutex<HashMap<String, TokenSession>>,
+}
+
+impl TokenSessions {
+    /// Registers the validity of a newly started session.
+    pub(crate) fn start(&self, token: &str, validity: u64, now: u64) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            // Forget sessions that can no longer be valid.
+            sessions.retain(|_, s| now <= s.started + s.validity + MAX_VALIDITY);
+            sessions.insert(
+                token.to_string(),
+                TokenSession {
+                    started: now,
+                    validity,
+                    completed: None,
+                },
+            );
+        }
+    }
+
+    /// Marks a session as completed, if not done before, and returns its expiry.
+    pub(crate) fn complete(&self, token: &str, now: u64) -> Result<u64, crate::Error> {
+        let mut sessions = self
+            .sessions
+            .lock()
+            .map_err(|_e| crate::Error::Unexpected)?;
+        let session = sessions
+            .entry(token.to_string())
+            .or_insert_with(|| TokenSession {
+                started: now,
+                validity: DEFAULT_VALIDITY,
+                completed: None,
+            });
+
+        Ok(*session.completed.get_or_insert(now) + session.validity)
+    }
+}
+
 #[derive(Clone)]
 enum Auth {
     // Check the ongoing session using a token from the request.
-    Token(String),
+    Token(String, Arc<TokenSessions>),
 
     // Check the session by decoding a JWT from the request.
-    Jwt(DecodingKey),
+    Jwt(Arc<JwtKeys>),
 }
 
 #[doc(hidden)]
@@ -81,7 +140,7 @@ where
             let mut exp = None;
 
             let session_result = match &*auth {
-                Auth::Token(url) => {
+                Auth::Token(url, sessions) => {
                     let token_str = req.match_info().query("token");
 
                     if token_str.is_empty() {
@@ -94,25 +153,53 @@ where
                         .map_err(|_e| crate::Error::Unexpected)?
                         .build()
                         .result(&token)
-                        .await
-                        .map_err(|_e| crate::Error::Unexpected)?;
+                        .await;
+
+                    // Unfinished sessions are reported as such, without attributes.
+                    let unfinished = |status| SessionResult {
+                        token: token.clone(),
+                        sessiontype: SessionType::Disclosing,
+                        status,
+                        proof_status: None,
+                        disclosed: vec![],
+                        signature: None,
+                    };
 
-                    res
+                    match res {
+                        Ok(res) => {
+                            let now = current_time_u64()?;
+                            let session_exp = sessions.complete(token_str, now)?;
+
+                            if now > session_exp {
+                                return Err(crate::Error::ChronologyError.into());
+                            }
+
+                            exp = Some(session_exp);
+                            res
+                        }
+                        Err(irma::Error::SessionNotFinished(status)) => unfinished(status),
+                        Err(irma::Error::SessionCancelled) => unfinished(SessionStatus::Cancelled),
+                        Err(irma::Error::SessionTimedOut) => unfinished(SessionStatus::Timeout),
+                        Err(irma::Error::NetworkError(e))
+                            if e.status() == Some(reqwest::StatusCode::BAD_REQUEST)
+                                || e.status() == Some(reqwest::StatusCode::NOT_FOUND) =>
+                        {
+                            return Err(crate::Error::SessionNotFound.into())
+                        }
+                        Err(e) => {
+                            log::error!("could not retrieve session result: {e}");
+                            return Err(crate::Error::UpstreamError.into());
+                        }
+                    }
                 }
-                Auth::Jwt(decoding_key) => {
+                Auth::Jwt(keys) => {
                     let auth = req.extract::<BearerAuth>().await?;
                     let jwt = auth.token();
 
                     let mut validation = Validation::new(Algorithm::RS256);
                     validation.leeway = 0;
 
-                    let decoded =
-                        decode::<Claims>(jwt, decoding_key, &validation).map_err(|e| {
-                            match e.into_kind() {
-                                ErrorKind::ExpiredSignature => crate::Error::ChronologyError,
-                                _ => crate::Error::DecodingError,
-                            }
-                        })?;
+                    let decoded = keys.decode::<Claims>(jwt, &validation).await?;
 
                     exp = Some(decoded.claims.exp);
 
@@ -169,19 +256,18 @@ where
     }
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// IRMA authentication type.
 pub enum IrmaAuthType {
     /// Authenticate using IRMA session tokens.
     ///
     /// This method will retrieve the session results from the IRMA server using the supplied
-    /// token.
+    /// token. This does not require the IRMA server to sign session results.
     Token,
     /// Authenticate using IRMA signed session results (JWTs).
     ///
-    /// This method will try to retrieve the public key to verify JWTs from the URL.
-    /// If no public key can be retrieved, setup of the middleware will panic.
+    /// The public keys to verify JWTs are retrieved from the URL when first needed, unless they
+    /// are given using [`IrmaAuth::with_jwt_keys`].
     Jwt,
 }
 
@@ -192,6 +278,10 @@ pub struct IrmaAuth {
     irma_url: String,
     /// The authentication method.
     method: IrmaAuthType,
+    /// The public keys to verify JWTs, shared between workers.
+    jwt_keys: Option<Arc<JwtKeys>>,
+    /// The sessions authenticated using tokens, shared between workers.
+    token_sessions: Option<Arc<TokenSessions>>,
 }
 
 impl IrmaAuth {
@@ -199,7 +289,24 @@ impl IrmaAuth {
     ///
     /// See [`IrmaAuthType`] for the available methods.
     pub fn new(irma_url: String, method: IrmaAuthType) -> Self {
-        Self { irma_url, method }
+        Self {
+            irma_url,
+            method,
+            jwt_keys: None,
+            token_sessions: None,
+        }
+    }
+
+    /// Use shared bookkeeping of sessions authenticated using tokens.
+    pub fn with_token_sessions(mut self, token_sessions: Arc<TokenSessions>) -> Self {
+        self.token_sessions = Some(token_sessions);
+        self
+    }
+
+    /// Use shared public keys to verify JWTs, instead of retrieving them per worker.
+    pub fn with_jwt_keys(mut self, jwt_keys: Arc<JwtKeys>) -> Self {
+        self.jwt_keys = Some(jwt_keys);
+        self
     }
 }
 
@@ -215,23 +322,16 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         let url = self.irma_url.clone();
-        let auth_type = self.method.clone();
+        let auth_type = self.method;
+        let jwt_keys = self.jwt_keys.clone();
+        let token_sessions = self.token_sessions.clone();
 
         async move {
             let auth_data = match auth_type {
                 IrmaAuthType::Jwt => {
-                    let jwt_pk_bytes = reqwest::get(&format!("{url}/publickey"))
-                        .await
-                        .expect("could not retrieve JWT public key")
-                        .bytes()
-                        .await
-                        .expect("could not retrieve JWT public key bytes");
-                    let decoding_key = DecodingKey::from_rsa_pem(&jwt_pk_bytes)
-                        .expect("could not parse JWT public key");
-
-                    Auth::Jwt(decoding_key)
+                    Auth::Jwt(jwt_keys.unwrap_or_else(|| Arc::new(JwtKeys::new(&url))))
                 }
-                IrmaAuthType::Token => Auth::Token(url),
+                IrmaAuthType::Token => Auth::Token(url, token_sessions.unwrap_or_default()),
             };
 
             Ok(IrmaAuthService {
@@ -242,3 +342,31 @@ where
         .boxed_local()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_sessions() {
+        let sessions = TokenSessions::default();
+        sessions.start("a", 60, 1000);
+
+        // The validity starts when the session is first seen completed.
+        assert_eq!(sessions.complete("a", 1010).unwrap(), 1070);
+        assert_eq!(sessions.complete("a", 1100).unwrap(), 1070);
+
+        // Sessions started elsewhere get the default validity.
+        assert_eq!(
+            sessions.complete("b", 1000).unwrap(),
+            1000 + DEFAULT_VALIDITY
+        );
+
+        // Stale sessions are forgotten when new sessions start.
+        sessions.start("c", 60, 1000 + 60 + MAX_VALIDITY + 1);
+        assert_eq!(
+            sessions.complete("a", 2000).unwrap(),
+            2000 + DEFAULT_VALIDITY
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Caution!
+//! This is a mock IRMA server for testing purposes only. Sessions are started as usual, but their
+//! outcome is scripted by the test. When a session is done, it discloses exactly the requested
+//! attributes.
+//!
+//! The mock implements the subset of the IRMA server API used by the PKG: starting sessions,
+//! retrieving their status and results, either plain or as RS256-signed JWTs, and retrieving the
+//! public key to verify those JWTs.
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::util::current_time_u64;
+
+/// The key used to sign session results.
+const SK: &[u8] = include_bytes!("testdata/jwt_1.pem");
+
+/// The public key to verify session results.
+pub const PK: &[u8] = include_bytes!("testdata/jwt_1.pub.pem");
+
+/// The validity of session results if none was requested, as in the IRMA server.
+const DEFAULT_VALIDITY: u64 = 120;
+
+/// The scripted state of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockState {
+    /// The session is waiting for the user.
+    Pending,
+    /// The session is done and the proof is valid.
+    Valid,
+    /// The session is done and the proof is valid, but its signed result has expired.
+    Expired,
+    /// The session is done, but the proof is invalid.
+    InvalidProof,
+    /// The user cancelled the session.
+    Cancelled,
+    /// The session timed out.
+    Timeout,
+}
+
+#[derive(Debug)]
+struct MockSession {
+    state: MockState,
+    validity: u64,
+    status_requests: usize,
+    disclose: Vec<(String, Option<String>)>,
+}
+
+#[derive(Debug, Default)]
+struct Sessions {
+    next: u64,
+    sessions: HashMap<String, MockSession>,
+}
+
+/// A mock IRMA server listening on a local port.
+#[derive(Debug, Clone)]
+pub struct MockIrma {
+    /// The URL of the server.
+    pub url: String,
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+/// Collects the requested attribute types and values from a con-dis-con.
+fn requested(disclose: &Value) -> Vec<(String, Option<String>)> {
+    match disclose {
+        Value::Array(items) => items.iter().flat_map(requested).collect(),
+        Value::Object(attr) => attr
+            .get("type")
+            .and_then(Value::as_str)
+            .map(|atype| {
+                let value = attr.get("value").and_then(Value::as_str).map(String::from);
+                vec![(atype.to_string(), value)]
+            })
+            .unwrap_or_default(),
+        Value::String(atype) => vec![(atype.clone(), None)],
+        _ => vec![],
+    }
+}
+
+async fn session(sessions: web::Data<Mutex<Sessions>>, req: web::Json<Value>) -> HttpResponse {
+    let mut sessions = sessions.lock().unwrap();
+
+    sessions.next += 1;
+    let token = format!("mock{}", sessions.next);
+    let disclose = requested(&req["request"]["disclose"]);
+    let validity = req["validity"].as_u64().unwrap_or(DEFAULT_VALIDITY);
+
+    sessions.sessions.insert(
+        token.clone(),
+        MockSession {
+            state: MockState::Pending,
+            validity,
+            status_requests: 0,
+            disclose,
+        },
+    );
+
+    HttpResponse::Ok().json(json!({
+        "sessionPtr": { "u": format!("https://irma.invalid/irma/session/{token}"), "irmaqr": "disclosing" },
+        "token": token,
+    }))
+}
+
+/// The session status and proof status as returned by the IRMA server.
+fn statuses(session: &MockSession) -> (&'static str, Option<&'static str>) {
+    match session.state {
+        MockState::Pending => ("CONNECTED", None),
+        MockState::Valid | MockState::Expired => ("DONE", Some("VALID")),
+        MockState::InvalidProof => ("DONE", Some("INVALID")),
+        MockState::Cancelled => ("CANCELLED", None),
+        MockState::Timeout => ("TIMEOUT", None),
+    }
+}
+
+/// The session result as returned by the IRMA server.
+fn session_result(token: &str, session: &MockSession) -> Value {
+    let (status, proof_status) = statuses(session);
+
+    let mut result = json!({
+        "token": token,
+        "type": "disclosing",
+        "status": status,
+    });
+
+    if let Some(proof_status) = proof_status {
+        result["proofStatus"] = json!(proof_status);
+        result["disclosed"] = json!([session
+            .disclose
+            .iter()
+            .map(|(atype, value)| json!({
+                "id": atype,
+                "rawvalue": value,
+                "status": "PRESENT",
+            }))
+            .collect::<Vec<_>>()]);
+    }
+
+    result
+}
+
+async fn status(sessions: web::Data<Mutex<Sessions>>, token: web::Path<String>) -> HttpResponse {
+    let mut sessions = sessions.lock().unwrap();
+
+    match sessions.sessions.get_mut(token.as_str()) {
+        Some(session) => {
+            session.status_requests += 1;
+            HttpResponse::Ok().json(statuses(session).0)
+        }
+        None => HttpResponse::BadRequest().finish(),
+    }
+}
+
+async fn result(sessions: web::Data<Mutex<Sessions>>, token: web::Path<String>) -> HttpResponse {
+    let sessions = sessions.lock().unwrap();
+
+    match sessions.sessions.get(token.as_str()) {
+        Some(session) => HttpResponse::Ok().json(session_result(&token, session)),
+        None => HttpResponse::BadRequest().finish(),
+    }
+}
+
+async fn result_jwt(
+    sessions: web::Data<Mutex<Sessions>>,
+    token: web::Path<String>,
+) -> HttpResponse {
+    let sessions = sessions.lock().unwrap();
+    let session = match sessions.sessions.get(token.as_str()) {
+        Some(session) => session,
+        None => return HttpResponse::BadRequest().finish(),
+    };
+
+    let now = current_time_u64().unwrap();
+    let iat = match session.state {
+        MockState::Expired => now - session.validity - 1,
+        _ => now,
+    };
+
+    let mut claims = session_result(&token, session);
+    claims["iat"] = json!(iat);
+    claims["exp"] = json!(iat + session.validity);
+    claims["iss"] = json!("irmaserver");
+    claims["sub"] = json!("disclosing_result");
+
+    let jwt = encode(
+        &Header::new(Algorithm::RS256),
+        &claims,
+        &EncodingKey::from_rsa_pem(SK).unwrap(),
+    )
+    .unwrap();
+
+    HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(jwt)
+}
+
+impl MockIrma {
+    /// Starts the server on the current runtime.
+    pub fn start() -> Self {
+        let sessions = Arc::new(Mutex::new(Sessions::default()));
+        let data = web::Data::from(sessions.clone());
+
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .route("/session", web::post().to(session))
+                .route("/session/{token}/status", web::get().to(status))
+                .route("/session/{token}/result", web::get().to(result))
+                .route("/session/{token}/result-jwt", web::get().to(result_jwt))
+                .route("/publickey", web::get().to(|| async { PK }))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_rt::spawn(server.run());
+
+        Self { url, sessions }
+    }
+
+    /// Scripts the state of a session.
+    pub fn set_state(&self, token: &str, state: MockState) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.sessions.get_mut(token) {
+            session.state = state;
+        }
+    }
+
+    /// The number of times the status of a session has been retrieved.
+    pub fn status_requests(&self, token: &str) -> usize {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .sessions
+            .get(token)
+            .map_or(0, |session| session.status_requests)
+    }
+}
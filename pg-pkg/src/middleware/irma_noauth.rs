@@ -63,7 +63,11 @@ where
                         con.extend(priv_id);
                     }
 
-                    Policy { timestamp: 0, con }
+                    Policy {
+                        timestamp: 0,
+                        con,
+                        not_before: None,
+                    }
                 }
             };
 
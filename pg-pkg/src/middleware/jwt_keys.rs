@@ -0,0 +1,374 @@
+//! Public keys to verify JWTs signed by the IRMA server.
+//!
+//! The keys are retrieved lazily from the IRMA server, with retries and exponential backoff. The
+//! keys are refreshed periodically and whenever a JWT fails to verify, such that a key rotation
+//! on the IRMA server does not require a restart. During a rotation the previous key remains
+//! valid until the next refresh. Alternatively, keys can be pinned from local PEM files, in which
+//! case they are never retrieved.
+
+use futures::lock::Mutex;
+use jsonwebtoken::{decode, errors::ErrorKind, DecodingKey, TokenData, Validation};
+use serde::de::DeserializeOwned;
+
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::server::{Readiness, JWT_PK_READY};
+
+/// The maximum number of retrieved keys held at once.
+const MAX_KEYS: usize = 2;
+
+/// The number of attempts to retrieve the public key.
+const RETRIES: u32 = 4;
+
+/// The delay before the first retry, doubled after every attempt.
+const BACKOFF: Duration = Duration::from_millis(250);
+
+/// The minimum time between refreshes, either periodic or triggered by JWTs that fail to verify.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The maximum time to connect to the IRMA server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The maximum time to retrieve the public key, including connecting.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Key {
+    pem: Vec<u8>,
+    key: DecodingKey,
+}
+
+/// Public keys of the IRMA server, shared between workers.
+pub struct JwtKeys {
+    url: String,
+    client: reqwest::Client,
+    pinned: bool,
+    keys: RwLock<Vec<Key>>,
+    last_refresh: Mutex<Option<Instant>>,
+    min_refresh_interval: Duration,
+    readiness: Option<Arc<Readiness>>,
+}
+
+impl std::fmt::Debug for JwtKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtKeys")
+            .field("url", &self.url)
+            .field("pinned", &self.pinned)
+            .finish()
+    }
+}
+
+impl JwtKeys {
+    /// Retrieve the keys from the IRMA server at `irma_url`.
+    pub fn new(irma_url: &str) -> Self {
+        // Like `reqwest::Client::new`, this only fails if the TLS backend cannot be initialized.
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("could not initialize HTTP client");
+
+        Self {
+            url: format!("{irma_url}/publickey"),
+            client,
+            pinned: false,
+            keys: RwLock::new(vec![]),
+            last_refresh: Mutex::new(None),
+            min_refresh_interval: MIN_REFRESH_INTERVAL,
+            readiness: None,
+        }
+    }
+
+    /// Only use the given PEM-encoded keys, instead of retrieving them.
+    pub fn with_pinned(mut self, pems: Vec<Vec<u8>>) -> Result<Self, crate::PKGError> {
+        let keys = pems
+            .into_iter()
+            .map(|pem| {
+                DecodingKey::from_rsa_pem(&pem)
+                    .map(|key| Key { pem, key })
+                    .map_err(|e| crate::PKGError::Setup(format!("invalid JWT public key: {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.pinned = !keys.is_empty();
+        self.keys = RwLock::new(keys);
+        self.set_ready(self.pinned);
+
+        Ok(self)
+    }
+
+    /// Report whether a key is available to the readiness state of the service.
+    pub fn with_readiness(mut self, readiness: Arc<Readiness>) -> Self {
+        self.readiness = Some(readiness);
+        self.set_ready(self.pinned);
+        self
+    }
+
+    fn set_ready(&self, ready: bool) {
+        JWT_PK_READY.set(ready.into());
+        if let Some(readiness) = &self.readiness {
+            readiness.jwt_pk.store(ready, Ordering::Release);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.keys.read().map(|keys| keys.is_empty()).unwrap_or(true)
+    }
+
+    async fn fetch(&self) -> Result<Vec<u8>, reqwest::Error> {
+        let bytes = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        Ok(bytes.to_vec())
+    }
+
+    /// Retrieves the current key from the IRMA server.
+    ///
+    /// If `force` is false, the key is only refreshed if the last refresh is long enough ago.
+    async fn refresh(&self, force: bool) -> Result<(), crate::Error> {
+        if self.pinned {
+            return Ok(());
+        }
+
+        // Only one refresh at a time, others wait for its result.
+        let mut last_refresh = self.last_refresh.lock().await;
+        if !force && last_refresh.is_some_and(|t| t.elapsed() < self.min_refresh_interval) {
+            return Ok(());
+        }
+
+        let mut delay = BACKOFF;
+        let mut attempt = 1;
+        let pem = loop {
+            match self.fetch().await {
+                Ok(pem) => break pem,
+                Err(e) if attempt < RETRIES => {
+                    log::warn!("could not retrieve JWT public key (attempt {attempt}): {e}");
+                    actix_rt::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    log::error!("could not retrieve JWT public key: {e}");
+                    *last_refresh = Some(Instant::now());
+                    self.set_ready(!self.is_empty());
+                    return Err(crate::Error::UpstreamError);
+                }
+            }
+        };
+
+        *last_refresh = Some(Instant::now());
+
+        let key = DecodingKey::from_rsa_pem(&pem).map_err(|e| {
+            log::error!("could not parse JWT public key: {e}");
+            crate::Error::UpstreamError
+        })?;
+
+        let mut keys = self.keys.write().map_err(|_e| crate::Error::Unexpected)?;
+        if keys.first().is_none_or(|k| k.pem != pem) {
+            log::info!("retrieved new JWT public key");
+            keys.insert(0, Key { pem, key });
+            keys.truncate(MAX_KEYS);
+        }
+
+        drop(keys);
+        self.set_ready(true);
+
+        Ok(())
+    }
+
+    /// Refreshes the keys every `interval`, but at most once per 30 seconds, forever.
+    pub async fn refresh_periodically(self: Arc<Self>, interval: Duration) {
+        if self.pinned {
+            return;
+        }
+
+        let interval = interval.max(MIN_REFRESH_INTERVAL);
+
+        loop {
+            // Errors are logged, and the next attempt is made on demand or at the next interval.
+            let _ = self.refresh(true).await;
+            actix_rt::time::sleep(interval).await;
+        }
+    }
+
+    fn try_decode<T: DeserializeOwned>(
+        &self,
+        jwt: &str,
+        validation: &Validation,
+    ) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        let keys = self
+            .keys
+            .read()
+            .map_err(|_e| jsonwebtoken::errors::Error::from(ErrorKind::InvalidSignature))?;
+
+        for Key { key, .. } in keys.iter() {
+            match decode::<T>(jwt, key, validation) {
+                Err(e) if matches!(e.kind(), ErrorKind::InvalidSignature) => continue,
+                res => return res,
+            }
+        }
+
+        Err(ErrorKind::InvalidSignature.into())
+    }
+
+    /// Decodes and verifies a JWT using any of the keys.
+    ///
+    /// Retrieves the keys if there are none yet. If the signature is not valid under any of the
+    /// keys, the keys are refreshed and the JWT is tried again.
+    pub async fn decode<T: DeserializeOwned>(
+        &self,
+        jwt: &str,
+        validation: &Validation,
+    ) -> Result<TokenData<T>, crate::Error> {
+        if self.is_empty() {
+            self.refresh(false).await?;
+
+            // A recent attempt to retrieve the keys failed.
+            if self.is_empty() {
+                return Err(crate::Error::UpstreamError);
+            }
+        }
+
+        let map_err = |e: jsonwebtoken::errors::Error| match e.into_kind() {
+            ErrorKind::ExpiredSignature => crate::Error::ChronologyError,
+            _ => crate::Error::DecodingError,
+        };
+
+        match self.try_decode(jwt, validation) {
+            Err(e) if matches!(e.kind(), ErrorKind::InvalidSignature) && !self.pinned => {
+                if self.refresh(false).await.is_err() {
+                    return Err(map_err(e));
+                }
+
+                self.try_decode(jwt, validation).map_err(map_err)
+            }
+            res => res.map_err(map_err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::{web, App, HttpServer};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    const SK_1: &[u8] = include_bytes!("testdata/jwt_1.pem");
+    const PK_1: &[u8] = include_bytes!("testdata/jwt_1.pub.pem");
+    const SK_2: &[u8] = include_bytes!("testdata/jwt_2.pem");
+    const PK_2: &[u8] = include_bytes!("testdata/jwt_2.pub.pem");
+
+    fn jwt(sk: &[u8]) -> String {
+        let exp = crate::util::current_time_u64().unwrap() + 60;
+
+        encode(
+            &Header::new(Algorithm::RS256),
+            &json!({ "exp": exp, "sub": "test" }),
+            &EncodingKey::from_rsa_pem(sk).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn validation() -> Validation {
+        Validation::new(Algorithm::RS256)
+    }
+
+    /// Serves the public key that is currently in `pk`.
+    fn serve_pk(pk: Arc<std::sync::Mutex<Vec<u8>>>) -> String {
+        let server = HttpServer::new(move || {
+            let pk = pk.clone();
+            App::new().route(
+                "/publickey",
+                web::get().to(move || {
+                    let pk = pk.clone();
+                    async move { pk.lock().unwrap().clone() }
+                }),
+            )
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_rt::spawn(server.run());
+
+        url
+    }
+
+    #[actix_web::test]
+    async fn test_pinned() {
+        let keys = JwtKeys::new("http://unused.invalid")
+            .with_pinned(vec![PK_1.to_vec(), PK_2.to_vec()])
+            .unwrap();
+
+        assert!(keys
+            .decode::<Value>(&jwt(SK_1), &validation())
+            .await
+            .is_ok());
+        assert!(keys
+            .decode::<Value>(&jwt(SK_2), &validation())
+            .await
+            .is_ok());
+
+        let keys = JwtKeys::new("http://unused.invalid")
+            .with_pinned(vec![PK_1.to_vec()])
+            .unwrap();
+
+        assert!(matches!(
+            keys.decode::<Value>(&jwt(SK_2), &validation()).await,
+            Err(crate::Error::DecodingError)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_rotation() {
+        let pk = Arc::new(std::sync::Mutex::new(PK_1.to_vec()));
+        let url = serve_pk(pk.clone());
+
+        let readiness = Arc::new(Readiness::default());
+        let mut keys = JwtKeys::new(&url).with_readiness(readiness.clone());
+        keys.min_refresh_interval = Duration::ZERO;
+
+        assert!(!readiness.jwt_pk.load(Ordering::Acquire));
+
+        // The key is retrieved lazily.
+        assert!(keys
+            .decode::<Value>(&jwt(SK_1), &validation())
+            .await
+            .is_ok());
+        assert!(readiness.jwt_pk.load(Ordering::Acquire));
+
+        // The IRMA server rotates its key.
+        *pk.lock().unwrap() = PK_2.to_vec();
+
+        assert!(keys
+            .decode::<Value>(&jwt(SK_2), &validation())
+            .await
+            .is_ok());
+        assert!(keys
+            .decode::<Value>(&jwt(SK_1), &validation())
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_unavailable() {
+        let readiness = Arc::new(Readiness::default());
+        let keys = JwtKeys::new("http://127.0.0.1:1").with_readiness(readiness.clone());
+
+        assert!(matches!(
+            keys.decode::<Value>(&jwt(SK_1), &validation()).await,
+            Err(crate::Error::UpstreamError)
+        ));
+        assert!(!readiness.jwt_pk.load(Ordering::Acquire));
+    }
+}
@@ -114,6 +114,7 @@ mod tests {
         let pol = Policy {
             timestamp: ts,
             con: vec![Attribute::new("testattribute", Some("testvalue"))],
+            not_before: None,
         };
         let req_usk = test::TestRequest::get()
             .uri(&format!("/v2/key/{ts}"))
@@ -130,6 +131,14 @@ mod tests {
         assert_eq!(res.status(), StatusCode::OK);
         let body = test::read_body(res).await;
 
+        // Other metrics are registered in the same registry.
+        let body: String = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .filter(|l| l.contains("postguard_clients"))
+            .map(|l| format!("{l}\n"))
+            .collect();
+
         let expected = "\
         # HELP postguard_clients Contains information about PostGuard clients connecting with the PKG.\n\
         # TYPE postguard_clients counter\n\
@@ -137,6 +146,6 @@ mod tests {
         postguard_clients{client=\"pg4ol\",client_version=\"0.0.1\",host=\"Outlook\",host_version=\"1234.5678.90\",path=\"/v2/parameters\",status=\"200\"} 2\n\
         postguard_clients{client=\"pg4tb\",client_version=\"0.0.2\",host=\"Thunderbird\",host_version=\"1234.5678.90\",path=\"/v2/parameters\",status=\"200\"} 1\n";
 
-        assert_eq!(actix_web::web::Bytes::from(expected), body);
+        assert_eq!(expected, body);
     }
 }
@@ -12,7 +12,11 @@
 //! The metrics middleware collects Prometheus metrics.
 
 pub mod irma;
+pub mod jwt_keys;
 pub mod metrics;
 
+#[cfg(test)]
+pub mod irma_mock;
+
 #[cfg(test)]
 pub mod irma_noauth;
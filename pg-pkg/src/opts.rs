@@ -1,4 +1,4 @@
-use clap::{Parser, ValueHint};
+use clap::{ArgEnum, Args, Parser, ValueHint};
 
 /// Private Key Generator (PKG) for PostGuard, an Identity Based Encryption standard.
 #[derive(Parser, Debug)]
@@ -12,10 +12,33 @@ pub struct Opts {
     pub subcmd: Subcommand,
 }
 
+/// How the PKG authenticates key requests.
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    /// Using JWTs signed by the IRMA server.
+    Jwt,
+    /// Using session tokens.
+    Token,
+}
+
 #[derive(Parser, Debug)]
 pub enum Subcommand {
     Gen(GenOpts),
     Server(ServerOpts),
+    Oracle(OracleOpts),
+    Rewrap(RewrapOpts),
+    Backup(BackupOpts),
+}
+
+/// Where to obtain the passphrase of encrypted secret key files.
+///
+/// If no passphrase file is given, the passphrase is read from the `PKG_PASSPHRASE` environment
+/// variable, or prompted for.
+#[derive(Args, Debug, Clone)]
+pub struct PassphraseOpts {
+    /// Path to a file containing the passphrase of the secret key files.
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    pub passphrase_file: Option<String>,
 }
 
 /// Generate a master key pair.
@@ -41,6 +64,17 @@ pub struct GenOpts {
     /// Path to store the IBS public key.
     #[clap(long, default_value = "./pkg_ibs.pub")]
     pub ibs_public_path: String,
+
+    /// Path to store the identity key, which signs the public parameters.
+    #[clap(long, default_value = "./pkg_identity.sec")]
+    pub identity_secret_path: String,
+
+    /// Encrypt the secret keys using a passphrase.
+    #[clap(long)]
+    pub encrypt: bool,
+
+    #[clap(flatten)]
+    pub passphrase: PassphraseOpts,
 }
 
 /// Run the IRMASeal PKG HTTP service.
@@ -74,4 +108,196 @@ pub struct ServerOpts {
     /// Path to store the IBS public key.
     #[clap(long, default_value = "./pkg_ibs.pub", value_hint = ValueHint::FilePath)]
     pub ibs_public_path: String,
+
+    /// Production mode: refuse to issue keys for attributes from the demo scheme.
+    #[clap(long)]
+    pub production: bool,
+
+    /// Delegate key issuing to a key oracle listening on this Unix socket.
+    ///
+    /// If set, the secret keys are not read by the server.
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    pub key_issuer_socket: Option<String>,
+
+    /// Maximum time (in seconds) a request to the key oracle may take.
+    #[clap(long, default_value = "10")]
+    pub key_issuer_timeout: u64,
+
+    #[clap(flatten)]
+    pub passphrase: PassphraseOpts,
+
+    /// Path to a PEM-encoded public key of the IRMA server to verify JWTs.
+    ///
+    /// Can be given multiple times, e.g., during a key rotation. If given, the public key is not
+    /// retrieved from the IRMA server.
+    #[clap(long, multiple_occurrences = true, value_hint = ValueHint::FilePath)]
+    pub irma_jwt_pk: Vec<String>,
+
+    /// Interval (in seconds) at which to retrieve the public key of the IRMA server, at least 30.
+    #[clap(long, default_value = "3600")]
+    pub jwt_pk_refresh: u64,
+
+    /// How to authenticate key requests: using JWTs signed by the IRMA server, or using session
+    /// tokens, for IRMA servers without JWT signing configured.
+    #[clap(long, arg_enum, default_value = "jwt")]
+    pub auth: AuthMode,
+
+    /// Maximum time (in seconds) a client can wait on a session in a single request.
+    #[clap(long, default_value = "30")]
+    pub session_wait_timeout: u64,
+
+    /// Maximum number of clients that can wait on sessions at once.
+    #[clap(long, default_value = "1024")]
+    pub max_session_waiters: usize,
+
+    /// Path to the identity key, to serve public parameters signed by it.
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    pub identity_secret_path: Option<String>,
+
+    /// Path to the append-only log of public parameters, which is created if it does not exist.
+    ///
+    /// The current parameters are appended if they differ from the last entry.
+    #[clap(long, requires = "identity-secret-path", value_hint = ValueHint::FilePath)]
+    pub parameters_log: Option<String>,
+
+    /// Refuse to issue decryption keys for timestamps older than this number of days.
+    ///
+    /// The window is announced in the public parameters.
+    #[clap(long)]
+    pub key_validity_days: Option<u64>,
+}
+
+/// Run a key oracle that issues keys over a local Unix socket.
+///
+/// The oracle holds the master secret keys, so that the HTTP service does not have to.
+#[derive(Parser, Debug)]
+#[clap(name = "Oracle")]
+pub struct OracleOpts {
+    /// Path of the Unix socket to listen on.
+    #[clap(short, long, default_value = "./pkg.sock", value_hint = ValueHint::FilePath)]
+    pub socket: String,
+
+    /// Path to the IBE private key.
+    #[clap(long, default_value = "./pkg_ibe.sec", value_hint = ValueHint::FilePath)]
+    pub ibe_secret_path: String,
+
+    /// Path to the IBS private key.
+    #[clap(long, default_value = "./pkg_ibs.sec", value_hint = ValueHint::FilePath)]
+    pub ibs_secret_path: String,
+
+    /// Maximum time (in seconds) to wait for a request on a connection.
+    #[clap(long, default_value = "10")]
+    pub read_timeout: u64,
+
+    #[clap(flatten)]
+    pub passphrase: PassphraseOpts,
+}
+
+/// Change the passphrase of secret key files.
+///
+/// Plain secret key files are encrypted, unless `--decrypt` is given.
+#[derive(Parser, Debug)]
+#[clap(name = "Rewrap")]
+pub struct RewrapOpts {
+    /// Paths to the secret key files.
+    #[clap(default_values = &["./pkg_ibe.sec", "./pkg_ibs.sec"], value_hint = ValueHint::FilePath)]
+    pub paths: Vec<String>,
+
+    #[clap(flatten)]
+    pub passphrase: PassphraseOpts,
+
+    /// Path to a file containing the new passphrase.
+    ///
+    /// If not given, the new passphrase is read from the `PKG_NEW_PASSPHRASE` environment
+    /// variable, or prompted for.
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    pub new_passphrase_file: Option<String>,
+
+    /// Store the secret keys unencrypted instead.
+    #[clap(long, conflicts_with = "new-passphrase-file")]
+    pub decrypt: bool,
+}
+
+/// Back up the master secret keys using Shamir secret sharing.
+#[derive(Parser, Debug)]
+#[clap(name = "Backup")]
+pub struct BackupOpts {
+    #[clap(subcommand)]
+    pub cmd: BackupCommand,
+}
+
+#[derive(Parser, Debug)]
+pub enum BackupCommand {
+    Split(SplitOpts),
+    Combine(CombineOpts),
+}
+
+/// Split the master secret keys into shares, of which a threshold is required to recover them.
+#[derive(Parser, Debug)]
+#[clap(name = "Split")]
+pub struct SplitOpts {
+    /// Number of shares required to recover the secret keys.
+    #[clap(short, long)]
+    pub threshold: u8,
+
+    /// Total number of shares.
+    #[clap(short = 'n', long)]
+    pub shares: u8,
+
+    /// Directory to write the shares to.
+    #[clap(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+    pub out_dir: String,
+
+    /// Path to the IBE private key.
+    #[clap(long, default_value = "./pkg_ibe.sec", value_hint = ValueHint::FilePath)]
+    pub ibe_secret_path: String,
+
+    /// Path to the IBE public key.
+    #[clap(long, default_value = "./pkg_ibe.pub", value_hint = ValueHint::FilePath)]
+    pub ibe_public_path: String,
+
+    /// Path to the IBS private key.
+    #[clap(long, default_value = "./pkg_ibs.sec", value_hint = ValueHint::FilePath)]
+    pub ibs_secret_path: String,
+
+    /// Path to the IBS public key.
+    #[clap(long, default_value = "./pkg_ibs.pub", value_hint = ValueHint::FilePath)]
+    pub ibs_public_path: String,
+
+    #[clap(flatten)]
+    pub passphrase: PassphraseOpts,
+}
+
+/// Recover the master secret keys from shares.
+///
+/// The recovered keys are checked against the public keys before they are written.
+#[derive(Parser, Debug)]
+#[clap(name = "Combine")]
+pub struct CombineOpts {
+    /// Paths to the share files.
+    #[clap(required = true, value_hint = ValueHint::FilePath)]
+    pub share_paths: Vec<String>,
+
+    /// Path to store the IBE private key.
+    #[clap(long, default_value = "./pkg_ibe.sec", value_hint = ValueHint::FilePath)]
+    pub ibe_secret_path: String,
+
+    /// Path to the IBE public key.
+    #[clap(long, default_value = "./pkg_ibe.pub", value_hint = ValueHint::FilePath)]
+    pub ibe_public_path: String,
+
+    /// Path to store the IBS private key.
+    #[clap(long, default_value = "./pkg_ibs.sec", value_hint = ValueHint::FilePath)]
+    pub ibs_secret_path: String,
+
+    /// Path to the IBS public key.
+    #[clap(long, default_value = "./pkg_ibs.pub", value_hint = ValueHint::FilePath)]
+    pub ibs_public_path: String,
+
+    /// Encrypt the recovered secret keys using a passphrase.
+    #[clap(long)]
+    pub encrypt: bool,
+
+    #[clap(flatten)]
+    pub passphrase: PassphraseOpts,
 }
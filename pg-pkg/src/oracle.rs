@@ -0,0 +1,95 @@
+use std::fs::{DirBuilder, Permissions};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_rt::net::UnixListener;
+
+use crate::issuer::{socket::serve, LocalIssuer};
+use crate::keyfile::Passphrase;
+use crate::opts::OracleOpts;
+use crate::util::{cgwkv_read_sk, gg_read_sk};
+use crate::PKGError;
+
+#[actix_rt::main]
+pub async fn exec(oracle_opts: OracleOpts) -> Result<(), PKGError> {
+    let OracleOpts {
+        socket,
+        ibe_secret_path,
+        ibs_secret_path,
+        read_timeout,
+        passphrase,
+    } = oracle_opts;
+
+    let mut passphrase = Passphrase::unlock(&passphrase);
+    let ibe_sk = cgwkv_read_sk(&ibe_secret_path, &mut passphrase)?;
+    let ibs_sk = gg_read_sk(&ibs_secret_path, &mut passphrase)?;
+
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+
+    let listener = bind_private(Path::new(&socket))?;
+
+    log::info!("key oracle listening on {socket}");
+
+    serve(
+        listener,
+        Arc::new(LocalIssuer::new(ibe_sk, ibs_sk)),
+        Duration::from_secs(read_timeout),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Binds a socket at `path` that only its owner may connect to.
+///
+/// The socket is bound inside a directory that only the owner can access, so that nobody can
+/// connect before its permissions are restricted. It is then linked at `path`, which fails if
+/// `path` already exists, like binding does.
+fn bind_private(path: &Path) -> std::io::Result<UnixListener> {
+    let name = path.file_name().ok_or(std::io::ErrorKind::InvalidInput)?;
+    let dir = path.with_file_name(format!(
+        ".{}.{}",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let tmp = dir.join("socket");
+    let res = UnixListener::bind(&tmp).and_then(|listener| {
+        std::fs::set_permissions(&tmp, Permissions::from_mode(0o600))?;
+        std::fs::hard_link(&tmp, path)?;
+        Ok(listener)
+    });
+
+    let _ = std::fs::remove_file(&tmp);
+    std::fs::remove_dir(&dir)?;
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn test_bind_private() {
+        let path = std::env::temp_dir().join(format!("pkg-test-{}.sock", rand::random::<u64>()));
+        let listener = bind_private(&path).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // The socket still accepts connections at its final path.
+        let (connected, accepted) =
+            futures::join!(actix_rt::net::UnixStream::connect(&path), listener.accept());
+        assert!(connected.is_ok() && accepted.is_ok());
+
+        // Like binding, an existing path is not replaced.
+        assert!(bind_private(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::keyfile::{self, Passphrase, NEW_PASSPHRASE_ENV};
+use crate::opts::RewrapOpts;
+use crate::util::replace_owned;
+use crate::PKGError;
+
+pub fn exec(rewrap_opts: &RewrapOpts) -> Result<(), PKGError> {
+    let RewrapOpts {
+        paths,
+        passphrase,
+        new_passphrase_file,
+        decrypt,
+    } = rewrap_opts;
+
+    let mut old = Passphrase::unlock(passphrase);
+    let mut new = Passphrase::new_from(new_passphrase_file.clone(), NEW_PASSPHRASE_ENV);
+
+    // Decrypt all files before writing any, such that a wrong passphrase changes nothing.
+    let plain = paths
+        .iter()
+        .map(|path| keyfile::read_secret(path, &mut old))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Likewise, seal all files before replacing any.
+    let contents = plain
+        .into_iter()
+        .map(|plain| match decrypt {
+            true => Ok(plain),
+            false => Ok(Zeroizing::new(keyfile::seal(&plain, new.get()?)?)),
+        })
+        .collect::<Result<Vec<_>, PKGError>>()?;
+
+    for (path, contents) in paths.iter().zip(contents) {
+        replace_owned(Path::new(path), &contents)?;
+    }
+
+    println!("The following keys were rewrapped:\n{}", paths.join("\n"));
+
+    Ok(())
+}
@@ -9,9 +9,13 @@ use actix_web::{
     App, HttpServer,
 };
 
-use crate::middleware::irma::{IrmaAuth, IrmaAuthType};
+use crate::issuer::{socket::SocketIssuer, KeyIssuer, LocalIssuer};
+use crate::keyfile::Passphrase;
+use crate::middleware::irma::{IrmaAuth, IrmaAuthType, TokenSessions};
+use crate::middleware::jwt_keys::JwtKeys;
 use crate::middleware::metrics::collect_metrics;
 use crate::opts::*;
+use crate::transparency::{sign_parameters, SignedParametersData};
 use crate::util::*;
 use crate::{handlers, PKGError};
 
@@ -20,7 +24,10 @@ use pg_core::artifacts::*;
 use pg_core::kem::cgw_kv::CGWKV;
 
 use lazy_static::lazy_static;
-use prometheus::{register_int_counter_vec, IntCounterVec};
+use prometheus::{register_int_counter_vec, register_int_gauge, IntCounterVec, IntGauge};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 
 lazy_static! {
     pub(crate) static ref POSTGUARD_CLIENTS: IntCounterVec = register_int_counter_vec!(
@@ -36,6 +43,115 @@ lazy_static! {
         ]
     )
     .expect("could not initialize metrics");
+    pub(crate) static ref JWT_PK_READY: IntGauge = register_int_gauge!(
+        "postguard_jwt_public_key_ready",
+        "Whether a public key to verify IRMA JWTs is available (1) or not (0)."
+    )
+    .expect("could not initialize metrics");
+}
+
+/// Settings that restrict for which identities the PKG issues keys.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IssuanceSettings {
+    /// Refuse to issue keys for attributes from the demo scheme.
+    pub production: bool,
+
+    /// Refuse to issue user secret keys for timestamps older than this (in seconds).
+    pub key_validity: Option<u64>,
+}
+
+/// Readiness of the service, shared between workers.
+#[derive(Debug, Default)]
+pub struct Readiness {
+    /// Whether a public key to verify IRMA JWTs is required.
+    pub jwt_required: bool,
+
+    /// Whether the public key to verify IRMA JWTs has been retrieved.
+    pub jwt_pk: AtomicBool,
+}
+
+impl Readiness {
+    /// The initial readiness for an authentication method.
+    pub fn new(auth: IrmaAuthType) -> Self {
+        Self {
+            jwt_required: auth == IrmaAuthType::Jwt,
+            jwt_pk: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Authentication of key requests, shared between workers.
+#[derive(Debug, Clone)]
+enum KeyAuth {
+    Jwt(Arc<JwtKeys>),
+    Token(Arc<TokenSessions>),
+}
+
+/// Registers the routes that issue keys, depending on the authentication method.
+fn key_routes(cfg: &mut web::ServiceConfig, irma: &str, key_auth: &KeyAuth) {
+    match key_auth {
+        KeyAuth::Jwt(jwt_keys) => {
+            let auth =
+                IrmaAuth::new(irma.to_string(), IrmaAuthType::Jwt).with_jwt_keys(jwt_keys.clone());
+
+            cfg.service(
+                resource("/jwt/{token}")
+                    .app_data(Data::new(irma.to_string()))
+                    .route(web::get().to(handlers::jwt)),
+            )
+            .service(
+                resource("/key/{timestamp}")
+                    .wrap(auth.clone())
+                    .route(web::get().to(handlers::key)),
+            )
+            .service(
+                resource("/sign/key")
+                    .wrap(auth)
+                    .route(web::post().to(handlers::signing_key)),
+            );
+        }
+        KeyAuth::Token(sessions) => {
+            let auth = IrmaAuth::new(irma.to_string(), IrmaAuthType::Token)
+                .with_token_sessions(sessions.clone());
+
+            cfg.app_data(Data::from(sessions.clone()))
+                .service(
+                    resource("/key/{timestamp}/{token}")
+                        .wrap(auth.clone())
+                        .route(web::get().to(handlers::key)),
+                )
+                .service(
+                    resource("/sign/key/{token}")
+                        .wrap(auth)
+                        .route(web::post().to(handlers::signing_key)),
+                );
+        }
+    }
+}
+
+/// Registers the routes of the signed parameters and their log, if available.
+pub(crate) fn transparency_routes(
+    cfg: &mut web::ServiceConfig,
+    signed_pd: Option<&SignedParametersData>,
+) {
+    let signed_pd = match signed_pd {
+        Some(signed_pd) => signed_pd,
+        None => return,
+    };
+
+    cfg.service(
+        resource("/parameters/signed")
+            .app_data(Data::new(signed_pd.signed.clone()))
+            .route(web::get().to(handlers::parameters)),
+    );
+
+    if let Some(log) = &signed_pd.log {
+        cfg.service(
+            resource("/parameters/log")
+                .app_data(Data::new(log.clone()))
+                .route(web::get().to(handlers::parameters)),
+        );
+    }
 }
 
 /// Precomputed parameter data.
@@ -61,15 +177,66 @@ pub async fn exec(server_opts: ServerOpts) -> Result<(), PKGError> {
         ibe_public_path,
         ibs_secret_path,
         ibs_public_path,
+        production,
+        key_issuer_socket,
+        key_issuer_timeout,
+        passphrase,
+        irma_jwt_pk,
+        jwt_pk_refresh,
+        auth,
+        session_wait_timeout,
+        max_session_waiters,
+        identity_secret_path,
+        parameters_log,
+        key_validity_days,
     } = server_opts;
 
-    let (ibe_pk, ibe_sk) = cgwkv_read_key_pair(&ibe_public_path, &ibe_secret_path)?;
-    let (ibs_pk, ibs_sk) = gg_read_key_pair(&ibs_public_path, &ibs_secret_path)?;
+    let key_validity = key_validity_days.map(|days| days * 24 * 60 * 60);
+
+    let auth = match auth {
+        AuthMode::Jwt => IrmaAuthType::Jwt,
+        AuthMode::Token => IrmaAuthType::Token,
+    };
+
+    let mut passphrase = Passphrase::unlock(&passphrase);
+
+    // Either delegate to an external key oracle, or keep the secret keys in memory.
+    let (ibe_pk, ibs_pk, issuer): (_, _, Arc<dyn KeyIssuer>) = match key_issuer_socket {
+        Some(socket) => (
+            cgwkv_read_pk(&ibe_public_path)?,
+            gg_read_pk(&ibs_public_path)?,
+            Arc::new(
+                SocketIssuer::new(socket).with_timeout(Duration::from_secs(key_issuer_timeout)),
+            ),
+        ),
+        None => {
+            let (ibe_pk, ibe_sk) =
+                cgwkv_read_key_pair(&ibe_public_path, &ibe_secret_path, &mut passphrase)?;
+            let (ibs_pk, ibs_sk) =
+                gg_read_key_pair(&ibs_public_path, &ibs_secret_path, &mut passphrase)?;
+
+            (ibe_pk, ibs_pk, Arc::new(LocalIssuer::new(ibe_sk, ibs_sk)))
+        }
+    };
+
+    // The identity key is only needed to sign the parameters once, and is not kept.
+    let signed_pd = match &identity_secret_path {
+        Some(path) => Some(sign_parameters(
+            &identity_read_sk(path, &mut passphrase)?,
+            path,
+            ibe_pk,
+            ibs_pk.clone(),
+            key_validity,
+            parameters_log.as_deref(),
+        )?),
+        None => None,
+    };
 
     let ibe_pd = ParametersData::new(
         &Parameters::<PublicKey<CGWKV>> {
             format_version: 0x00,
             public_key: PublicKey(ibe_pk),
+            key_validity,
         },
         Some(&ibe_public_path),
     )?;
@@ -78,12 +245,63 @@ pub async fn exec(server_opts: ServerOpts) -> Result<(), PKGError> {
         &Parameters::<VerifyingKey> {
             format_version: 0x00,
             public_key: VerifyingKey(ibs_pk),
+            key_validity: None,
         },
         Some(&ibs_public_path),
     )?;
 
+    let settings = IssuanceSettings {
+        production,
+        key_validity,
+    };
+    let info = handlers::server_info(
+        &settings,
+        auth,
+        &ibe_pd,
+        &ibs_pd,
+        signed_pd.as_ref().map(|s| s.fingerprint.as_str()),
+    );
+    let readiness = Arc::new(Readiness::new(auth));
+
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
+    if let Some(signed_pd) = &signed_pd {
+        log::info!(
+            "signing parameters using identity key with fingerprint {}",
+            signed_pd.fingerprint
+        );
+    }
+
+    let key_auth = match auth {
+        IrmaAuthType::Jwt => {
+            let pinned = irma_jwt_pk
+                .iter()
+                .map(std::fs::read)
+                .collect::<Result<Vec<_>, _>>()?;
+            let jwt_keys = Arc::new(
+                JwtKeys::new(&irma)
+                    .with_pinned(pinned)?
+                    .with_readiness(readiness.clone()),
+            );
+
+            actix_rt::spawn(
+                jwt_keys
+                    .clone()
+                    .refresh_periodically(Duration::from_secs(jwt_pk_refresh)),
+            );
+
+            KeyAuth::Jwt(jwt_keys)
+        }
+        IrmaAuthType::Token => KeyAuth::Token(Arc::new(TokenSessions::default())),
+    };
+
+    let waiter = Arc::new(handlers::SessionWaiter::new(
+        &irma,
+        Duration::from_secs(session_wait_timeout),
+        max_session_waiters,
+        auth == IrmaAuthType::Jwt,
+    ));
+
     HttpServer::new(move || {
         App::new()
             .wrap(
@@ -106,10 +324,24 @@ pub async fn exec(server_opts: ServerOpts) -> Result<(), PKGError> {
                     .max_age(86400),
             )
             .service(resource("/metrics").route(web::get().to(handlers::metrics)))
+            .service(resource("/healthz").route(web::get().to(handlers::healthz)))
+            .service(
+                resource("/readyz")
+                    .app_data(Data::from(readiness.clone()))
+                    .app_data(Data::from(issuer.clone()))
+                    .route(web::get().to(handlers::readyz)),
+            )
             .service(
                 scope("/v2")
                     .wrap_fn(collect_metrics)
                     .app_data(Data::new(web::JsonConfig::default().limit(1024 * 4096)))
+                    .app_data(Data::new(settings))
+                    .app_data(Data::from(issuer.clone()))
+                    .service(
+                        resource("/info")
+                            .app_data(Data::new(info.clone()))
+                            .route(web::get().to(handlers::info)),
+                    )
                     .service(
                         resource("/parameters")
                             .app_data(Data::new(ibe_pd.clone()))
@@ -120,6 +352,7 @@ pub async fn exec(server_opts: ServerOpts) -> Result<(), PKGError> {
                             .app_data(Data::new(ibs_pd.clone()))
                             .route(web::get().to(handlers::parameters)),
                     )
+                    .configure(|cfg| transparency_routes(cfg, signed_pd.as_ref()))
                     .service(
                         scope("/{_:(irma|request)}")
                             .service(
@@ -128,22 +361,11 @@ pub async fn exec(server_opts: ServerOpts) -> Result<(), PKGError> {
                                     .route(web::post().to(handlers::start)),
                             )
                             .service(
-                                resource("/jwt/{token}")
-                                    .app_data(Data::new(irma.clone()))
-                                    .route(web::get().to(handlers::jwt)),
-                            )
-                            .service(
-                                resource("/key/{timestamp}")
-                                    .app_data(Data::new(ibe_sk))
-                                    .wrap(IrmaAuth::new(irma.clone(), IrmaAuthType::Jwt))
-                                    .route(web::get().to(handlers::key::<CGWKV>)),
+                                resource("/wait/{token}")
+                                    .app_data(Data::from(waiter.clone()))
+                                    .route(web::get().to(handlers::wait)),
                             )
-                            .service(
-                                resource("/sign/key")
-                                    .app_data(Data::new(ibs_sk.clone()))
-                                    .wrap(IrmaAuth::new(irma.clone(), IrmaAuthType::Jwt))
-                                    .route(web::post().to(handlers::signing_key)),
-                            ),
+                            .configure(|cfg| key_routes(cfg, &irma, &key_auth)),
                     ),
             )
     })
@@ -159,15 +381,21 @@ pub async fn exec(server_opts: ServerOpts) -> Result<(), PKGError> {
 pub(crate) mod tests {
     use super::*;
 
-    use actix_http::Request;
+    use actix_http::{Request, StatusCode};
     use actix_web::dev::{Service, ServiceResponse};
     use actix_web::{test, web, App, Error};
 
+    use crate::middleware::irma_mock::{MockIrma, MockState};
     use crate::middleware::irma_noauth::NoAuth;
     use irma::{ProofStatus, SessionStatus};
-    use pg_core::api::{KeyResponse, Parameters, SigningKeyRequest, SigningKeyResponse};
+    use pg_core::api::{
+        IrmaAuthRequest, KeyResponse, Parameters, ServerInfo, SessionWaitResponse,
+        SigningKeyRequest, SigningKeyResponse,
+    };
+    use pg_core::client::rust::{SealerMemoryConfig, UnsealerMemoryConfig};
+    use pg_core::client::{Sealer, Unsealer};
     use pg_core::ibs::gg;
-    use pg_core::identity::{Attribute, Policy};
+    use pg_core::identity::{Attribute, EncryptionPolicy, Policy};
     use pg_core::kem::IBKEM;
 
     use rand::thread_rng;
@@ -186,16 +414,30 @@ pub(crate) mod tests {
         <CGWKV as IBKEM>::Sk,
         gg::PublicKey,
         gg::SecretKey,
+    ) {
+        setup_with(IssuanceSettings::default()).await
+    }
+
+    pub(crate) async fn setup_with(
+        settings: IssuanceSettings,
+    ) -> (
+        impl Service<Request, Response = ServiceResponse, Error = Error>,
+        <CGWKV as IBKEM>::Pk,
+        <CGWKV as IBKEM>::Sk,
+        gg::PublicKey,
+        gg::SecretKey,
     ) {
         let mut rng = thread_rng();
 
         let (ibe_pk, ibe_sk) = CGWKV::setup(&mut rng);
         let (ibs_pk, ibs_sk) = gg::setup(&mut rng);
+        let issuer: Arc<dyn KeyIssuer> = Arc::new(LocalIssuer::new(ibe_sk, ibs_sk.clone()));
 
         let pd = ParametersData::new(
             &Parameters::<PublicKey<CGWKV>> {
                 format_version: 0x00,
                 public_key: PublicKey(ibe_pk),
+                key_validity: settings.key_validity,
             },
             None,
         )
@@ -205,18 +447,29 @@ pub(crate) mod tests {
             &Parameters::<VerifyingKey> {
                 format_version: 0x00,
                 public_key: VerifyingKey(ibs_pk.clone()),
+                key_validity: None,
             },
             None,
         )
         .unwrap();
 
+        let info = handlers::server_info(&settings, IrmaAuthType::Jwt, &pd, &pds, None);
+
         // Create a simple setup with a pk endpoint and a key service without authentication.
         let app = test::init_service(
             App::new()
                 .service(resource("/metrics").route(web::get().to(handlers::metrics)))
+                .service(resource("/healthz").route(web::get().to(handlers::healthz)))
                 .service(
                     scope("/v2")
                         .wrap_fn(collect_metrics)
+                        .app_data(Data::new(settings))
+                        .app_data(Data::from(issuer))
+                        .service(
+                            resource("/info")
+                                .app_data(Data::new(info))
+                                .route(web::get().to(handlers::info)),
+                        )
                         .service(
                             resource("/parameters")
                                 .app_data(Data::new(pd))
@@ -229,13 +482,11 @@ pub(crate) mod tests {
                         )
                         .service(
                             resource("/key/{timestamp}")
-                                .app_data(Data::new(ibe_sk))
                                 .wrap(NoAuth::Decryption)
-                                .route(web::get().to(handlers::key::<CGWKV>)),
+                                .route(web::get().to(handlers::key)),
                         )
                         .service(
                             resource("/sign/key")
-                                .app_data(Data::new(ibs_sk.clone()))
                                 .wrap(NoAuth::Signing)
                                 .route(web::post().to(handlers::signing_key)),
                         ),
@@ -282,6 +533,133 @@ pub(crate) mod tests {
         assert_eq!(params.format_version, 0x00);
     }
 
+    #[actix_web::test]
+    async fn test_signed_parameters() {
+        use pg_core::transparency::{IdentitySecretKey, ParametersLog, SignedParameters};
+
+        let mut rng = thread_rng();
+        let identity = IdentitySecretKey::generate(&mut rng);
+        let fingerprint = identity.public_key().fingerprint();
+        let log_path = std::env::temp_dir().join(format!("pkg-log-{}.json", rand::random::<u64>()));
+        let log_path = log_path.to_str().unwrap();
+
+        let (ibe_pk, _) = CGWKV::setup(&mut rng);
+        let (ibs_pk, _) = gg::setup(&mut rng);
+
+        let mut seen = vec![];
+        for ibe_pk in [ibe_pk, ibe_pk, CGWKV::setup(&mut rng).0] {
+            let signed_pd =
+                sign_parameters(&identity, "", ibe_pk, ibs_pk.clone(), None, Some(log_path))
+                    .unwrap();
+            assert_eq!(signed_pd.fingerprint, fingerprint);
+
+            let app =
+                test::init_service(App::new().service(
+                    scope("/v2").configure(|cfg| transparency_routes(cfg, Some(&signed_pd))),
+                ))
+                .await;
+
+            let req = test::TestRequest::get()
+                .uri("/v2/parameters/signed")
+                .to_request();
+            let signed: SignedParameters = test::call_and_read_body_json(&app, req).await;
+            let statement = signed.verify(Some(&fingerprint)).unwrap();
+            assert_eq!(statement.public_key.0, ibe_pk);
+
+            let req = test::TestRequest::get()
+                .uri("/v2/parameters/log")
+                .to_request();
+            let log: ParametersLog = test::call_and_read_body_json(&app, req).await;
+            statement.check_log(Some(&log), seen.last()).unwrap();
+
+            seen.push(statement);
+        }
+
+        let sizes: Vec<_> = seen.iter().map(|s| s.log.as_ref().unwrap().size).collect();
+        assert_eq!(sizes, [1, 1, 2]);
+
+        std::fs::remove_file(log_path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_get_info() {
+        let (app, _, _, _, _) = default_setup().await;
+
+        let resp = test::TestRequest::get()
+            .uri("/healthz")
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::TestRequest::get()
+            .uri("/v2/parameters")
+            .send_request(&app)
+            .await;
+        let etag = resp
+            .headers()
+            .get("etag")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let req = test::TestRequest::get().uri("/v2/info").to_request();
+        let info: ServerInfo = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(
+            info.wire_versions,
+            vec![
+                pg_core::consts::VERSION_V3,
+                pg_core::consts::VERSION_V4,
+                pg_core::consts::VERSION_V5,
+                pg_core::consts::VERSION_V6,
+                pg_core::consts::VERSION_V7
+            ]
+        );
+        assert_eq!(format!("\"{}\"", info.fingerprints.encryption), etag);
+        assert!(!info.production);
+        assert!(info.session_wait);
+    }
+
+    #[actix_web::test]
+    async fn test_readiness() {
+        let readiness = Arc::new(Readiness::new(IrmaAuthType::Jwt));
+        let issuer: Arc<dyn KeyIssuer> = Arc::new(SocketIssuer::new("/nonexistent/pkg.sock"));
+
+        let app = test::init_service(
+            App::new().service(
+                resource("/readyz")
+                    .app_data(Data::from(readiness.clone()))
+                    .app_data(Data::from(issuer))
+                    .route(web::get().to(handlers::readyz)),
+            ),
+        )
+        .await;
+
+        let resp = test::TestRequest::get()
+            .uri("/readyz")
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["checks"]["jwtPublicKey"], false);
+        assert_eq!(body["checks"]["keys"], false);
+
+        // Once the JWT public key is retrieved, only the key oracle is missing.
+        readiness
+            .jwt_pk
+            .store(true, std::sync::atomic::Ordering::Release);
+
+        let resp = test::TestRequest::get()
+            .uri("/readyz")
+            .send_request(&app)
+            .await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["checks"]["jwtPublicKey"], true);
+        assert_eq!(body["ready"], false);
+    }
+
     #[actix_web::test]
     async fn test_get_usk() {
         let (app, _, _, _, _) = default_setup().await;
@@ -291,6 +669,7 @@ pub(crate) mod tests {
         let pol = Policy {
             timestamp: ts,
             con: vec![Attribute::new("testattribute", Some("testvalue"))],
+            not_before: None,
         };
 
         let req = test::TestRequest::get()
@@ -305,6 +684,131 @@ pub(crate) mod tests {
         assert_eq!(key_response.proof_status, Some(ProofStatus::Valid));
     }
 
+    #[actix_web::test]
+    async fn test_get_usk_key_validity() {
+        let (app, _, _, _, _) = setup_with(IssuanceSettings {
+            production: false,
+            key_validity: Some(24 * 60 * 60),
+        })
+        .await;
+
+        let req = test::TestRequest::get().uri("/v2/parameters").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["keyValidity"], 24 * 60 * 60);
+
+        let ts = now();
+        for (timestamp, status) in [
+            (ts, StatusCode::OK),
+            (ts - 2 * 24 * 60 * 60, StatusCode::FORBIDDEN),
+        ] {
+            let pol = Policy {
+                timestamp,
+                con: vec![Attribute::new("testattribute", Some("testvalue"))],
+                not_before: None,
+            };
+
+            let req = test::TestRequest::get()
+                .uri(&format!("/v2/key/{timestamp}"))
+                .set_json(pol)
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_get_usk_embargo() {
+        let (app, _, _, _, _) = default_setup().await;
+
+        let ts = now();
+        let pol = Policy {
+            timestamp: ts,
+            con: vec![Attribute::new("testattribute", Some("testvalue"))],
+            not_before: None,
+        };
+
+        for (not_before, status) in [
+            (ts - 1, StatusCode::OK),
+            (ts + 24 * 60 * 60, StatusCode::FORBIDDEN),
+        ] {
+            let req = test::TestRequest::get()
+                .uri(&format!("/v2/key/{ts}?notBefore={not_before}"))
+                .set_json(pol.clone())
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_get_usk_production() {
+        let (app, _, _, _, _) = setup_with(IssuanceSettings {
+            production: true,
+            key_validity: None,
+        })
+        .await;
+
+        let ts = now();
+
+        let pol = Policy {
+            timestamp: ts,
+            con: vec![Attribute::new("testattribute", Some("testvalue"))],
+            not_before: None,
+        };
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/v2/key/{ts}"))
+            .set_json(pol)
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let pol_demo = Policy {
+            timestamp: ts,
+            con: vec![Attribute::new(
+                "irma-demo.gemeente.personalData.fullname",
+                Some("Alice"),
+            )],
+            not_before: None,
+        };
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/v2/key/{ts}"))
+            .set_json(pol_demo)
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_get_usk_signing_production() {
+        let (app, _, _, _, _) = setup_with(IssuanceSettings {
+            production: true,
+            key_validity: None,
+        })
+        .await;
+
+        let skr = SigningKeyRequest {
+            pub_sign_id: vec![Attribute::new(
+                "irma-demo.gemeente.personalData.fullname",
+                Some("Alice"),
+            )],
+            priv_sign_id: None,
+        };
+
+        let req = test::TestRequest::post()
+            .uri("/v2/sign/key")
+            .set_json(skr)
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
     #[actix_web::test]
     async fn test_get_usk_signing() {
         let (app, _, _, _, _) = default_setup().await;
@@ -399,6 +903,7 @@ pub(crate) mod tests {
         let pol = Policy {
             timestamp: ts,
             con: vec![Attribute::new("testattribute", Some("testvalue"))],
+            not_before: None,
         };
 
         let id = pol.derive_kem::<CGWKV>().unwrap();
@@ -430,6 +935,104 @@ pub(crate) mod tests {
         assert_eq!(ss1, ss3);
     }
 
+    #[actix_web::test]
+    async fn test_round_kem_socket_issuer() {
+        let mut rng = thread_rng();
+        let (ibe_pk, ibe_sk) = CGWKV::setup(&mut rng);
+        let (_, ibs_sk) = gg::setup(&mut rng);
+
+        let path = std::env::temp_dir().join(format!("pkg-test-{}.sock", rand::random::<u64>()));
+        let listener = actix_rt::net::UnixListener::bind(&path).unwrap();
+        let oracle = Arc::new(LocalIssuer::new(ibe_sk, ibs_sk));
+        actix_rt::spawn(crate::issuer::socket::serve(
+            listener,
+            oracle,
+            std::time::Duration::from_secs(10),
+        ));
+
+        let issuer = SocketIssuer::new(&path);
+
+        let pol = Policy {
+            timestamp: now(),
+            con: vec![Attribute::new("testattribute", Some("testvalue"))],
+            not_before: None,
+        };
+
+        let id = pol.derive_kem::<CGWKV>().unwrap();
+        let (ct, ss1) = CGWKV::encaps(&ibe_pk, &id, &mut rng);
+
+        // Make sure a USK extracted by the oracle can decapsulate.
+        let usk = issuer.extract_usk(&pol).await.unwrap();
+        let ss2 = CGWKV::decaps(None, &usk.0, &ct).unwrap();
+        assert_eq!(ss1, ss2);
+
+        assert!(issuer.signing_key(&pol).await.is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+
+        // Without an oracle, issuing keys fails gracefully.
+        assert!(matches!(
+            issuer.extract_usk(&pol).await,
+            Err(crate::Error::IssuerError)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_socket_issuer_timeout() {
+        let path = std::env::temp_dir().join(format!("pkg-test-{}.sock", rand::random::<u64>()));
+        let listener = actix_rt::net::UnixListener::bind(&path).unwrap();
+
+        // Accept connections, but never respond.
+        actix_rt::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let issuer = SocketIssuer::new(&path).with_timeout(std::time::Duration::from_millis(100));
+        let pol = Policy {
+            timestamp: now(),
+            con: vec![Attribute::new("testattribute", Some("testvalue"))],
+            not_before: None,
+        };
+
+        assert!(matches!(
+            issuer.extract_usk(&pol).await,
+            Err(crate::Error::IssuerError)
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_oracle_read_timeout() {
+        use tokio::io::AsyncReadExt;
+
+        let (_, ibe_sk) = CGWKV::setup(&mut thread_rng());
+        let (_, ibs_sk) = gg::setup(&mut thread_rng());
+
+        let path = std::env::temp_dir().join(format!("pkg-test-{}.sock", rand::random::<u64>()));
+        let listener = actix_rt::net::UnixListener::bind(&path).unwrap();
+        let oracle = Arc::new(LocalIssuer::new(ibe_sk, ibs_sk));
+        actix_rt::spawn(crate::issuer::socket::serve(
+            listener,
+            oracle,
+            std::time::Duration::from_millis(100),
+        ));
+
+        // A connection that never sends a request is closed by the oracle.
+        let mut stream = actix_rt::net::UnixStream::connect(&path).await.unwrap();
+        let read = actix_rt::time::timeout(
+            std::time::Duration::from_secs(5),
+            stream.read(&mut [0u8; 1]),
+        )
+        .await;
+        assert!(matches!(read, Ok(Ok(0))));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[actix_web::test]
     async fn test_round_kem_wrong_policy() {
         let mut rng = thread_rng();
@@ -440,6 +1043,7 @@ pub(crate) mod tests {
         let pol = Policy {
             timestamp: ts,
             con: vec![Attribute::new("testattribute", Some("testvalue"))],
+            not_before: None,
         };
 
         let id = pol.derive_kem::<CGWKV>().unwrap();
@@ -469,6 +1073,7 @@ pub(crate) mod tests {
         let pol_wrong = Policy {
             timestamp: ts,
             con: vec![Attribute::new("testattribute", Some("anothervalue"))],
+            not_before: None,
         };
 
         let req_usk_wrong = test::TestRequest::get()
@@ -483,4 +1088,341 @@ pub(crate) mod tests {
         let ss4 = CGWKV::decaps(None, &key_response_wrong.key.unwrap().0, &ct).unwrap();
         assert_ne!(ss1, ss4);
     }
+
+    /// Sets up the IRMA routes, authenticated against a mock IRMA server.
+    async fn irma_setup(
+        mock: &MockIrma,
+        key_auth: KeyAuth,
+    ) -> (
+        impl Service<Request, Response = ServiceResponse, Error = Error>,
+        <CGWKV as IBKEM>::Pk,
+        gg::PublicKey,
+    ) {
+        let mut rng = thread_rng();
+        let (ibe_pk, ibe_sk) = CGWKV::setup(&mut rng);
+        let (ibs_pk, ibs_sk) = gg::setup(&mut rng);
+        let issuer: Arc<dyn KeyIssuer> = Arc::new(LocalIssuer::new(ibe_sk, ibs_sk));
+        let url = mock.url.clone();
+
+        let app = test::init_service(
+            App::new().service(
+                scope("/v2")
+                    .app_data(Data::new(IssuanceSettings::default()))
+                    .app_data(Data::from(issuer))
+                    .service(
+                        scope("/{_:(irma|request)}")
+                            .service(
+                                resource("/start")
+                                    .app_data(Data::new(url.clone()))
+                                    .route(web::post().to(handlers::start)),
+                            )
+                            .configure(|cfg| key_routes(cfg, &url, &key_auth)),
+                    ),
+            ),
+        )
+        .await;
+
+        (app, ibe_pk, ibs_pk)
+    }
+
+    /// Starts a session for `con` and returns its token.
+    async fn start_session(
+        app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+        con: &[Attribute],
+    ) -> String {
+        let req_start = test::TestRequest::post()
+            .uri("/v2/irma/start")
+            .set_json(IrmaAuthRequest {
+                con: con.to_vec(),
+                validity: Some(60),
+            })
+            .to_request();
+        let session: serde_json::Value = test::call_and_read_body_json(app, req_start).await;
+
+        session["token"].as_str().unwrap().to_string()
+    }
+
+    /// Retrieves the signed session result of a session.
+    async fn session_jwt(
+        app: &impl Service<Request, Response = ServiceResponse, Error = Error>,
+        token: &str,
+    ) -> String {
+        let req_jwt = test::TestRequest::get()
+            .uri(&format!("/v2/irma/jwt/{token}"))
+            .to_request();
+        let jwt = test::call_and_read_body(app, req_jwt).await;
+
+        String::from_utf8(jwt.to_vec()).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_end_to_end() {
+        let mut rng = thread_rng();
+        let mock = MockIrma::start();
+        let jwt_keys = Arc::new(JwtKeys::new(&mock.url));
+        let (app, ibe_pk, ibs_pk) = irma_setup(&mock, KeyAuth::Jwt(jwt_keys)).await;
+
+        // The sender retrieves a signing key.
+        let alice = vec![Attribute::new(
+            "pbdf.sidn-pbdf.email.email",
+            Some("alice@example.com"),
+        )];
+        let token = start_session(&app, &alice).await;
+        mock.set_state(&token, MockState::Valid);
+        let jwt = session_jwt(&app, &token).await;
+
+        let req_sign = test::TestRequest::post()
+            .uri("/v2/irma/sign/key")
+            .insert_header(("Authorization", format!("Bearer {jwt}")))
+            .set_json(SigningKeyRequest {
+                pub_sign_id: alice.clone(),
+                priv_sign_id: None,
+            })
+            .to_request();
+        let sign_response: SigningKeyResponse = test::call_and_read_body_json(&app, req_sign).await;
+        let pub_sign_key = sign_response.pub_sign_key.unwrap();
+        assert_eq!(pub_sign_key.policy.con, alice);
+
+        // The sender seals a message for the recipient.
+        let ts = now();
+        let bob = vec![Attribute::new(
+            "pbdf.sidn-pbdf.email.email",
+            Some("bob@example.com"),
+        )];
+        let policies = EncryptionPolicy::from([(
+            "bob".to_string(),
+            Policy {
+                timestamp: ts,
+                con: bob.clone(),
+                not_before: None,
+            },
+        )]);
+
+        let sealed = Sealer::<_, SealerMemoryConfig>::new(
+            &PublicKey(ibe_pk),
+            &policies,
+            &pub_sign_key,
+            &mut rng,
+        )
+        .unwrap()
+        .seal(b"hello bob")
+        .unwrap();
+
+        // The recipient retrieves a decryption key, once the session is done.
+        let token = start_session(&app, &bob).await;
+        let key_request = |jwt: &str| {
+            test::TestRequest::get()
+                .uri(&format!("/v2/irma/key/{ts}"))
+                .insert_header(("Authorization", format!("Bearer {jwt}")))
+                .to_request()
+        };
+
+        let jwt = session_jwt(&app, &token).await;
+        let key_response: KeyResponse<UserSecretKey<CGWKV>> =
+            test::call_and_read_body_json(&app, key_request(&jwt)).await;
+        assert_eq!(key_response.status, SessionStatus::Connected);
+        assert!(key_response.key.is_none());
+
+        mock.set_state(&token, MockState::Valid);
+        let jwt = session_jwt(&app, &token).await;
+        let key_response: KeyResponse<UserSecretKey<CGWKV>> =
+            test::call_and_read_body_json(&app, key_request(&jwt)).await;
+
+        let unsealer =
+            Unsealer::<_, UnsealerMemoryConfig>::new(&sealed, &VerifyingKey(ibs_pk)).unwrap();
+        let (plain, verified) = unsealer.unseal("bob", &key_response.key.unwrap()).unwrap();
+        assert_eq!(plain, b"hello bob");
+        assert_eq!(verified.public.con, alice);
+
+        // A session with an invalid proof yields no keys.
+        let token = start_session(&app, &bob).await;
+        mock.set_state(&token, MockState::InvalidProof);
+        let jwt = session_jwt(&app, &token).await;
+        let key_response: KeyResponse<UserSecretKey<CGWKV>> =
+            test::call_and_read_body_json(&app, key_request(&jwt)).await;
+        assert_eq!(key_response.proof_status, Some(ProofStatus::Invalid));
+        assert!(key_response.key.is_none());
+
+        // An expired session result is rejected.
+        let token = start_session(&app, &alice).await;
+        mock.set_state(&token, MockState::Expired);
+        let jwt = session_jwt(&app, &token).await;
+        let err = test::try_call_service(&app, key_request(&jwt))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_token_auth() {
+        let mut rng = thread_rng();
+        let mock = MockIrma::start();
+        let key_auth = KeyAuth::Token(Arc::new(TokenSessions::default()));
+        let (app, ibe_pk, _) = irma_setup(&mock, key_auth).await;
+
+        let con = vec![Attribute::new("testattribute", Some("testvalue"))];
+        let token = start_session(&app, &con).await;
+        let token = token.as_str();
+
+        let ts = now();
+        let key_request = || {
+            test::TestRequest::get()
+                .uri(&format!("/v2/irma/key/{ts}/{token}"))
+                .to_request()
+        };
+
+        // The session is still pending.
+        let key_response: KeyResponse<UserSecretKey<CGWKV>> =
+            test::call_and_read_body_json(&app, key_request()).await;
+        assert_eq!(key_response.status, SessionStatus::Connected);
+        assert!(key_response.key.is_none());
+
+        // The session is done, but the proof is invalid.
+        mock.set_state(token, MockState::InvalidProof);
+        let key_response: KeyResponse<UserSecretKey<CGWKV>> =
+            test::call_and_read_body_json(&app, key_request()).await;
+        assert_eq!(key_response.proof_status, Some(ProofStatus::Invalid));
+        assert!(key_response.key.is_none());
+
+        // Neither are signing keys.
+        let req_sign = test::TestRequest::post()
+            .uri(&format!("/v2/irma/sign/key/{token}"))
+            .set_json(SigningKeyRequest {
+                pub_sign_id: con.clone(),
+                priv_sign_id: None,
+            })
+            .to_request();
+        let sign_response: SigningKeyResponse = test::call_and_read_body_json(&app, req_sign).await;
+        assert_eq!(sign_response.proof_status, Some(ProofStatus::Invalid));
+        assert!(sign_response.pub_sign_key.is_none());
+
+        // The session is done and the proof is valid.
+        mock.set_state(token, MockState::Valid);
+        let key_response: KeyResponse<UserSecretKey<CGWKV>> =
+            test::call_and_read_body_json(&app, key_request()).await;
+        assert_eq!(key_response.status, SessionStatus::Done);
+        assert_eq!(key_response.proof_status, Some(ProofStatus::Valid));
+
+        let id = Policy {
+            timestamp: ts,
+            con,
+            not_before: None,
+        }
+        .derive_kem::<CGWKV>()
+        .unwrap();
+        let (ct, ss1) = CGWKV::encaps(&ibe_pk, &id, &mut rng);
+        let ss2 = CGWKV::decaps(None, &key_response.key.unwrap().0, &ct).unwrap();
+        assert_eq!(ss1, ss2);
+
+        // A cancelled session yields no key.
+        mock.set_state(token, MockState::Cancelled);
+        let key_response: KeyResponse<UserSecretKey<CGWKV>> =
+            test::call_and_read_body_json(&app, key_request()).await;
+        assert_eq!(key_response.status, SessionStatus::Cancelled);
+        assert!(key_response.key.is_none());
+
+        // A timed out session yields no key.
+        mock.set_state(token, MockState::Timeout);
+        let key_response: KeyResponse<UserSecretKey<CGWKV>> =
+            test::call_and_read_body_json(&app, key_request()).await;
+        assert_eq!(key_response.status, SessionStatus::Timeout);
+        assert!(key_response.key.is_none());
+
+        // Unknown sessions are not found.
+        let req_unknown = test::TestRequest::get()
+            .uri(&format!("/v2/irma/key/{ts}/unknown"))
+            .to_request();
+        let err = test::try_call_service(&app, req_unknown).await.unwrap_err();
+        assert_eq!(err.as_response_error().status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_wait() {
+        let mock = MockIrma::start();
+        let waiter = |max_waiters| {
+            Data::new(handlers::SessionWaiter::new(
+                &mock.url,
+                Duration::from_millis(1200),
+                max_waiters,
+                true,
+            ))
+        };
+
+        let app = test::init_service(
+            App::new().service(
+                scope("/v2/irma")
+                    .service(
+                        resource("/start")
+                            .app_data(Data::new(mock.url.clone()))
+                            .route(web::post().to(handlers::start)),
+                    )
+                    .service(
+                        resource("/wait/{token}")
+                            .app_data(waiter(2))
+                            .route(web::get().to(handlers::wait)),
+                    )
+                    .service(
+                        resource("/busy/{token}")
+                            .app_data(waiter(0))
+                            .route(web::get().to(handlers::wait)),
+                    ),
+            ),
+        )
+        .await;
+
+        let con = vec![Attribute::new("testattribute", Some("testvalue"))];
+        let token = start_session(&app, &con).await;
+        let wait_request = |route: &str| {
+            test::TestRequest::get()
+                .uri(&format!("/v2/irma/{route}/{token}"))
+                .to_request()
+        };
+
+        // The session does not finish before the timeout. Both requests share a single poller.
+        let (res1, res2): (SessionWaitResponse, SessionWaitResponse) = futures::join!(
+            test::call_and_read_body_json(&app, wait_request("wait")),
+            test::call_and_read_body_json(&app, wait_request("wait")),
+        );
+        for res in [res1, res2] {
+            assert_eq!(res.status, SessionStatus::Connected);
+            assert!(res.jwt.is_none());
+        }
+        assert!(mock.status_requests(&token) <= 4);
+
+        // The session finishes while waiting.
+        let (m, t) = (mock.clone(), token.clone());
+        actix_rt::spawn(async move {
+            actix_rt::time::sleep(Duration::from_millis(200)).await;
+            m.set_state(&t, MockState::Valid);
+        });
+
+        let res: SessionWaitResponse =
+            test::call_and_read_body_json(&app, wait_request("wait")).await;
+        assert_eq!(res.status, SessionStatus::Done);
+
+        let jwt_keys = JwtKeys::new(&mock.url);
+        let claims = jwt_keys
+            .decode::<serde_json::Value>(
+                &res.jwt.unwrap(),
+                &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256),
+            )
+            .await
+            .unwrap()
+            .claims;
+        assert_eq!(claims["token"], token);
+
+        // Too many clients are waiting.
+        let res = test::call_service(&app, wait_request("busy")).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Unknown sessions are not found.
+        let req_unknown = test::TestRequest::get()
+            .uri("/v2/irma/wait/unknown")
+            .to_request();
+        let res = test::call_service(&app, req_unknown).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
 }
@@ -0,0 +1,87 @@
+//! Signed public parameters and their log, see [`pg_core::transparency`].
+
+use pg_core::artifacts::{PublicKey, VerifyingKey};
+use pg_core::ibs::gg;
+use pg_core::kem::{cgw_kv::CGWKV, IBKEM};
+use pg_core::transparency::{IdentitySecretKey, ParametersLog, ParametersStatement};
+
+use std::path::Path;
+
+use crate::server::ParametersData;
+use crate::util::{current_time_u64, replace_owned};
+use crate::PKGError;
+
+/// Precomputed signed parameters and log.
+#[derive(Debug, Clone)]
+pub struct SignedParametersData {
+    /// The signed parameters.
+    pub signed: ParametersData,
+
+    /// The log of public parameters, if kept.
+    pub log: Option<ParametersData>,
+
+    /// The fingerprint of the identity key.
+    pub fingerprint: String,
+}
+
+/// Reads the log at `path`, appends the parameters of `statement` if they changed and writes it
+/// back.
+fn update_log(path: &Path, statement: &ParametersStatement) -> Result<ParametersLog, PKGError> {
+    let mut log: ParametersLog = match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| PKGError::Setup(format!("could not read parameters log: {e}")))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ParametersLog::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let since = current_time_u64().map_err(|e| PKGError::Setup(e.to_string()))?;
+    let appended = log
+        .append(statement, since)
+        .map_err(|e| PKGError::Setup(format!("could not append to parameters log: {e}")))?;
+
+    if appended {
+        let bytes = serde_json::to_vec_pretty(&log)
+            .map_err(|e| PKGError::Setup(format!("could not write parameters log: {e}")))?;
+        replace_owned(path, &bytes)?;
+    }
+
+    Ok(log)
+}
+
+/// Signs the public parameters using the identity key, after updating the log, if any.
+pub(crate) fn sign_parameters(
+    identity: &IdentitySecretKey,
+    identity_path: &str,
+    ibe_pk: <CGWKV as IBKEM>::Pk,
+    ibs_pk: gg::PublicKey,
+    key_validity: Option<u64>,
+    log_path: Option<&str>,
+) -> Result<SignedParametersData, PKGError> {
+    let mut statement = ParametersStatement {
+        format_version: 0x00,
+        public_key: PublicKey(ibe_pk),
+        verifying_key: VerifyingKey(ibs_pk),
+        key_validity,
+        log: None,
+    };
+
+    let log = match log_path {
+        Some(path) => {
+            let log = update_log(Path::new(path), &statement)?;
+            statement.log = log.head();
+
+            Some(ParametersData::new(&log, Some(path))?)
+        }
+        None => None,
+    };
+
+    let signed = identity
+        .sign(&statement)
+        .map_err(|e| PKGError::Setup(format!("could not sign parameters: {e}")))?;
+
+    Ok(SignedParametersData {
+        signed: ParametersData::new(&signed, Some(log_path.unwrap_or(identity_path)))?,
+        log,
+        fingerprint: identity.public_key().fingerprint(),
+    })
+}
@@ -4,15 +4,20 @@ use actix_web::dev::ServiceRequest;
 use actix_web::http::header::EntityTag;
 
 use pg_core::kem::{cgw_kv::CGWKV, IBKEM};
+use pg_core::transparency::IdentitySecretKey;
 use pg_core::Compress;
 
 use crate::error::PKGError;
+use crate::keyfile::{read_secret, Passphrase};
 use crate::server::ParametersData;
 
 use arrayref::array_ref;
 use core::hash::Hasher;
 use paste::paste;
 use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
 use std::path::Path;
 use std::str::FromStr;
 use std::time::SystemTime;
@@ -46,6 +51,48 @@ pub fn open_ct<T>(x: subtle::CtOption<T>) -> Option<T> {
     }
 }
 
+/// Creates a new file that is only accessible by its owner.
+fn create_owned<P: AsRef<Path>>(path: P) -> std::io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+/// Writes a new file that is only accessible by its owner.
+pub(crate) fn write_owned<P: AsRef<Path>, C: AsRef<[u8]>>(
+    path: P,
+    contents: C,
+) -> std::io::Result<()> {
+    create_owned(path)?.write_all(contents.as_ref())
+}
+
+/// Replaces a file by writing to a temporary file first, so that it is never left half-written.
+///
+/// The new contents and the rename are synced to disk before returning.
+pub(crate) fn replace_owned(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    // A temporary file left behind by an interrupted replacement is never used.
+    match std::fs::remove_file(&tmp) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => (),
+    }
+
+    let mut file = create_owned(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
 pub fn current_time_u64() -> Result<u64, crate::Error> {
     let n = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -87,27 +134,35 @@ impl ParametersData {
 macro_rules! read_keypair {
     ($scheme: ident) => {
         paste! {
-            pub(crate) fn [<$scheme:lower _read_key_pair>](pk_path: impl AsRef<Path>, sk_path: impl AsRef<Path>) -> Result<(<$scheme as IBKEM>::Pk, <$scheme as IBKEM>::Sk), PKGError> {
+            pub(crate) fn [<$scheme:lower _read_pk>](pk_path: impl AsRef<Path>) -> Result<<$scheme as IBKEM>::Pk, PKGError> {
                 const PK_LENGTH: usize = $scheme::PK_BYTES;
-                const SK_LENGTH: usize = $scheme::SK_BYTES;
 
-                let pk_bytes = std::fs::read(pk_path).unwrap();
+                let pk_bytes = std::fs::read(pk_path)?;
                 if pk_bytes.len() != PK_LENGTH {
                     return Err(PKGError::Setup("wrong pk length".to_string()));
                 }
 
                 let pk_bytes = array_ref![&pk_bytes, 0, PK_LENGTH];
-                let pk = open_ct(<$scheme as IBKEM>::Pk::from_bytes(pk_bytes)).ok_or(PKGError::Setup("could not read pk".to_string()))?;
+                open_ct(<$scheme as IBKEM>::Pk::from_bytes(pk_bytes)).ok_or(PKGError::Setup("could not read pk".to_string()))
+            }
+
+            pub(crate) fn [<$scheme:lower _read_sk>](sk_path: impl AsRef<Path>, passphrase: &mut Passphrase) -> Result<<$scheme as IBKEM>::Sk, PKGError> {
+                [<$scheme:lower _sk_from_bytes>](&read_secret(sk_path, passphrase)?)
+            }
+
+            pub(crate) fn [<$scheme:lower _sk_from_bytes>](sk_bytes: &[u8]) -> Result<<$scheme as IBKEM>::Sk, PKGError> {
+                const SK_LENGTH: usize = $scheme::SK_BYTES;
 
-                let sk_bytes = std::fs::read(sk_path).unwrap();
                 if sk_bytes.len() != SK_LENGTH {
                     return Err(PKGError::Setup("wrong sk length".to_string()));
                 }
 
-                let sk_bytes = array_ref![&sk_bytes, 0, SK_LENGTH];
-                let sk = open_ct(<$scheme as IBKEM>::Sk::from_bytes(sk_bytes)).ok_or(PKGError::Setup("could not read sk".to_string()))?;
+                let sk_bytes = array_ref![sk_bytes, 0, SK_LENGTH];
+                open_ct(<$scheme as IBKEM>::Sk::from_bytes(sk_bytes)).ok_or(PKGError::Setup("could not read sk".to_string()))
+            }
 
-                Ok((pk, sk))
+            pub(crate) fn [<$scheme:lower _read_key_pair>](pk_path: impl AsRef<Path>, sk_path: impl AsRef<Path>, passphrase: &mut Passphrase) -> Result<(<$scheme as IBKEM>::Pk, <$scheme as IBKEM>::Sk), PKGError> {
+                Ok(([<$scheme:lower _read_pk>](pk_path)?, [<$scheme:lower _read_sk>](sk_path, passphrase)?))
             }
         }
     };
@@ -115,17 +170,62 @@ macro_rules! read_keypair {
 
 read_keypair!(CGWKV);
 
+pub(crate) fn gg_read_pk(
+    pk_path: impl AsRef<Path>,
+) -> Result<pg_core::ibs::gg::PublicKey, PKGError> {
+    let pk_bytes = std::fs::read(pk_path)?;
+
+    bincode::deserialize(&pk_bytes)
+        .map_err(|e| PKGError::Setup(format!("could not deserialize ibs pk: {e}")))
+}
+
+pub(crate) fn gg_read_sk(
+    sk_path: impl AsRef<Path>,
+    passphrase: &mut Passphrase,
+) -> Result<pg_core::ibs::gg::SecretKey, PKGError> {
+    gg_sk_from_bytes(&read_secret(sk_path, passphrase)?)
+}
+
+pub(crate) fn gg_sk_from_bytes(sk_bytes: &[u8]) -> Result<pg_core::ibs::gg::SecretKey, PKGError> {
+    bincode::deserialize(sk_bytes)
+        .map_err(|e| PKGError::Setup(format!("could not deserialize ibs sk: {e}")))
+}
+
 pub(crate) fn gg_read_key_pair(
     pk_path: impl AsRef<Path>,
     sk_path: impl AsRef<Path>,
+    passphrase: &mut Passphrase,
 ) -> Result<(pg_core::ibs::gg::PublicKey, pg_core::ibs::gg::SecretKey), PKGError> {
-    let pk_bytes = std::fs::read(pk_path)?;
-    let pk: pg_core::ibs::gg::PublicKey = bincode::deserialize(&pk_bytes)
-        .map_err(|e| PKGError::Setup(format!("could not deserialize ibs pk: {e}")))?;
+    Ok((gg_read_pk(pk_path)?, gg_read_sk(sk_path, passphrase)?))
+}
+
+pub(crate) fn identity_read_sk(
+    sk_path: impl AsRef<Path>,
+    passphrase: &mut Passphrase,
+) -> Result<IdentitySecretKey, PKGError> {
+    IdentitySecretKey::from_bytes(&read_secret(sk_path, passphrase)?)
+        .map_err(|e| PKGError::Setup(format!("could not read identity key: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let sk_bytes = std::fs::read(sk_path)?;
-    let sk: pg_core::ibs::gg::SecretKey = bincode::deserialize(&sk_bytes)
-        .map_err(|e| PKGError::Setup(format!("could not deserialize ibs sk: {e}")))?;
+    #[test]
+    fn test_replace_owned() {
+        let dir = std::env::temp_dir().join(format!("pg-pkg-replace-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("key");
 
-    Ok((pk, sk))
+        write_owned(&path, b"old").unwrap();
+
+        // A stale temporary file from an interrupted replacement does not get in the way.
+        write_owned(dir.join("key.tmp"), b"stale").unwrap();
+        replace_owned(&path, b"new").unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert!(!dir.join("key.tmp").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -6,11 +6,14 @@
 )]
 //! PostGuard wasm API.
 
+pub mod parameters;
+
 use pg_core::artifacts::{PublicKey, SigningKeyExt, UserSecretKey, VerifyingKey};
 use pg_core::client::web::stream::{StreamSealerConfig, StreamUnsealerConfig};
 use pg_core::client::web::{SealerMemoryConfig, UnsealerMemoryConfig};
 use pg_core::client::{Header, Sealer, Unsealer};
-use pg_core::identity::{EncryptionPolicy, HiddenPolicy};
+use pg_core::error::Error;
+use pg_core::identity::{EncryptionPolicy, HiddenPolicy, HintPolicy};
 use pg_core::kem::cgw_kv::CGWKV;
 
 use wasm_bindgen::prelude::*;
@@ -29,6 +32,9 @@ interface ISealOptions {
   policy: EncryptionPolicy;
   pubSignKey: ISigningKey;
   privSignKey?: ISigningKey;
+  hints?: IHintPolicy;
+  anonymous?: boolean;
+  privateSender?: boolean;
 }
 
 export type EncryptionPolicy = { [recipient: string]: IPolicy };
@@ -41,9 +47,21 @@ interface ISigningKey {
 interface IPolicy {
   con: AttributeCon;
   ts: number;
+  nb?: number;
 }
 
 export type AttributeCon = { t: string; v?: string }[];
+
+type HintRule =
+  | "hide"
+  | "revealDomain"
+  | { maskLast: number }
+  | { revealLast: number };
+
+interface IHintPolicy {
+  rules?: { [attributeType: string]: HintRule };
+  fallback?: HintRule;
+}
 "#;
 
 #[wasm_bindgen]
@@ -66,6 +84,20 @@ pub struct SealOptions {
     ///
     /// Only recipients specified by the `EncryptionPolicy` can see this.
     pub priv_sign_key: Option<SigningKeyExt>,
+
+    /// The rules used to hide the attribute values of the recipients in the header.
+    ///
+    /// Defaults to revealing part of only a few attribute types, such as mobile numbers.
+    #[serde(default)]
+    pub hints: Option<HintPolicy>,
+
+    /// Whether to replace the recipient identifiers in the header by random labels.
+    #[serde(default)]
+    pub anonymous: bool,
+
+    /// Whether to only include the public signing identity in the encrypted payload.
+    #[serde(default)]
+    pub private_sender: bool,
 }
 
 /// A StreamUnsealer is used to decrypt and verify data in a streaming manner.
@@ -90,6 +122,18 @@ fn get_recipients(header: &Header) -> Result<JsValue, JsValue> {
     Ok(pol)
 }
 
+// Helper to find the recipient for which a USK was retrieved.
+fn identify(header: &Header, recipient_id: &str, usk: JsValue) -> Result<String, JsValue> {
+    let usk: UserSecretKey<CGWKV> = serde_wasm_bindgen::from_value(usk)?;
+    let policy = &header
+        .recipients
+        .get(recipient_id)
+        .ok_or_else(|| Error::UnknownIdentifier(recipient_id.to_string()))?
+        .policy;
+
+    Ok(header.identify(policy, &usk)?)
+}
+
 /// Seals the contents of a `Uint8Array` into a `Uint8Array` using
 /// the given master public key and policies.
 ///
@@ -112,6 +156,9 @@ pub async fn js_seal(
         policy,
         pub_sign_key,
         priv_sign_key,
+        hints,
+        anonymous,
+        private_sender,
     } = serde_wasm_bindgen::from_value(options.into())?;
 
     let mut sealer = Sealer::<_, SealerMemoryConfig>::new(&mpk, &policy, &pub_sign_key, &mut rng)?;
@@ -120,6 +167,18 @@ pub async fn js_seal(
         sealer = sealer.with_priv_signing_key(priv_sign_key);
     }
 
+    if anonymous {
+        sealer = sealer.with_anonymous_recipients();
+    }
+
+    if private_sender {
+        sealer = sealer.with_private_sender();
+    }
+
+    if let Some(hints) = hints {
+        sealer = sealer.with_hint_policy(&hints);
+    }
+
     let res = sealer.seal(&plain).await?;
 
     Ok(res)
@@ -153,6 +212,9 @@ pub async fn js_stream_seal(
         policy,
         pub_sign_key,
         priv_sign_key,
+        hints,
+        anonymous,
+        private_sender,
     } = serde_wasm_bindgen::from_value(options.into())?;
 
     let read = ReadableStream::from_raw(readable);
@@ -165,6 +227,18 @@ pub async fn js_stream_seal(
         sealer = sealer.with_priv_signing_key(priv_sign_key);
     }
 
+    if anonymous {
+        sealer = sealer.with_anonymous_recipients();
+    }
+
+    if private_sender {
+        sealer = sealer.with_private_sender();
+    }
+
+    if let Some(hints) = hints {
+        sealer = sealer.with_hint_policy(&hints);
+    }
+
     sealer.seal(&mut stream, &mut sink).await?;
 
     Ok(())
@@ -214,6 +288,14 @@ impl StreamUnsealer {
         Ok(out)
     }
 
+    /// Finds the recipient for which `usk` was retrieved, given the identifier of any recipient
+    /// with the same hidden policy.
+    ///
+    /// Use this to find the identifier to unseal with if the recipients are anonymous.
+    pub fn identify(&self, recipient_id: String, usk: JsValue) -> Result<String, JsValue> {
+        identify(&self.0.header, &recipient_id, usk)
+    }
+
     /// Inspects the header for hidden policies in the header.
     ///
     /// The user should use this to retrieve a `UserSecretKey` via the PKG.
@@ -222,6 +304,9 @@ impl StreamUnsealer {
     }
 
     /// Returns the verified public identity of the sender.
+    ///
+    /// Returns `undefined` if the sender is private, in which case the public identity is only
+    /// included in the result of unsealing.
     pub fn public_identity(&self) -> Result<JsValue, JsValue> {
         Ok(serde_wasm_bindgen::to_value(&self.0.pub_id)?)
     }
@@ -250,6 +335,14 @@ impl MemoryUnsealer {
         Ok(arr)
     }
 
+    /// Finds the recipient for which `usk` was retrieved, given the identifier of any recipient
+    /// with the same hidden policy.
+    ///
+    /// Use this to find the identifier to unseal with if the recipients are anonymous.
+    pub fn identify(&self, recipient_id: String, usk: JsValue) -> Result<String, JsValue> {
+        identify(&self.0.header, &recipient_id, usk)
+    }
+
     /// Inspects the header for hidden policies in the header.
     /// The user should use this to retrieve a `UserSecretKey` via the PKG.
     pub fn inspect_header(&self) -> Result<JsValue, JsValue> {
@@ -257,6 +350,9 @@ impl MemoryUnsealer {
     }
 
     /// Returns the verified public identity of the sender.
+    ///
+    /// Returns `undefined` if the sender is private, in which case the public identity is only
+    /// included in the result of unsealing.
     pub fn public_identity(&self) -> Result<JsValue, JsValue> {
         Ok(serde_wasm_bindgen::to_value(&self.0.pub_id)?)
     }
@@ -0,0 +1,252 @@
+//! Retrieval of the public parameters of a PKG, cached in `localStorage`.
+//!
+//! Cached parameters are revalidated using `If-None-Match` and `If-Modified-Since`, and are used
+//! as-is when the PKG cannot be reached. If the fingerprint of the identity key of the PKG is
+//! given, only parameters signed by this key and consistent with the log of the PKG, if any, are
+//! accepted. Otherwise, the first parameters seen for a PKG URL are trusted: if the PKG later
+//! serves a different public key, an error is thrown and the cached parameters are kept, until
+//! they are explicitly forgotten using [`js_forget_parameters`].
+
+use pg_core::transparency::{ParametersLog, ParametersStatement, SignedParameters};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Headers, RequestInit, Response, Storage, Window};
+
+/// The paths of the parameters, relative to the PKG URL.
+const PATHS: [&str; 3] = [
+    "v2/parameters",
+    "v2/sign/parameters",
+    "v2/parameters/signed",
+];
+
+/// Parameters as last received from the PKG, along with their validators.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Cached {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
+
+fn window() -> Result<Window, JsValue> {
+    web_sys::window().ok_or_else(|| JsError::new("no global window").into())
+}
+
+fn storage_key(pkg_url: &str, path: &str) -> String {
+    format!("pg-parameters:{pkg_url}/{path}")
+}
+
+fn public_key(body: &str) -> Option<Value> {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .map(|v| v["publicKey"].clone())
+}
+
+fn js_error(e: impl ToString) -> JsValue {
+    JsError::new(&e.to_string()).into()
+}
+
+/// Retrieves parameters, revalidating cached parameters.
+///
+/// Fresh parameters are only cached once `validate` accepts them, given the cached ones.
+async fn fetch_cached(
+    window: &Window,
+    storage: Option<&Storage>,
+    pkg_url: &str,
+    path: &str,
+    validate: impl FnOnce(Option<&str>, &str) -> Result<(), JsValue>,
+) -> Result<String, JsValue> {
+    let key = storage_key(pkg_url, path);
+    let cached: Option<Cached> = storage
+        .and_then(|s| s.get_item(&key).ok().flatten())
+        .and_then(|s| serde_json::from_str(&s).ok());
+
+    let headers = Headers::new()?;
+    if let Some(cached) = &cached {
+        if let Some(etag) = &cached.etag {
+            headers.set("If-None-Match", etag)?;
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            headers.set("If-Modified-Since", last_modified)?;
+        }
+    }
+
+    let mut init = RequestInit::new();
+    init.headers(&headers);
+
+    let url = format!("{pkg_url}/{path}");
+    let res = JsFuture::from(window.fetch_with_str_and_init(&url, &init)).await;
+
+    let res: Response = match (res, &cached) {
+        // Fall back to the cached parameters if the PKG cannot be reached.
+        (Err(_), Some(cached)) => return Ok(cached.body.clone()),
+        (res, _) => res?.dyn_into()?,
+    };
+
+    if let (304, Some(cached)) = (res.status(), &cached) {
+        return Ok(cached.body.clone());
+    }
+
+    if !res.ok() {
+        return Err(JsError::new(&format!("could not retrieve {url}: {}", res.status())).into());
+    }
+
+    let etag = res.headers().get("ETag")?;
+    let last_modified = res.headers().get("Last-Modified")?;
+    let body = JsFuture::from(res.text()?)
+        .await?
+        .as_string()
+        .unwrap_or_default();
+
+    validate(cached.as_ref().map(|cached| cached.body.as_str()), &body)?;
+
+    if let Some(storage) = storage {
+        let fresh = Cached {
+            etag,
+            last_modified,
+            body: body.clone(),
+        };
+        let fresh = serde_json::to_string(&fresh).map_err(js_error)?;
+        storage.set_item(&key, &fresh)?;
+    }
+
+    Ok(body)
+}
+
+/// Trust on first use: the public key of a PKG is not supposed to change.
+fn trust_on_first_use(url: String) -> impl FnOnce(Option<&str>, &str) -> Result<(), JsValue> {
+    move |cached, body| match cached {
+        Some(cached) if public_key(cached) != public_key(body) => Err(js_error(format!(
+            "the public key at {url} differs from the one seen before, \
+             the PKG may be compromised or impersonated"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Retrieves the log of public parameters of a PKG, if it keeps one and can be reached.
+async fn fetch_log(window: &Window, pkg_url: &str) -> Option<ParametersLog> {
+    let url = format!("{pkg_url}/v2/parameters/log");
+    let res: Response = JsFuture::from(window.fetch_with_str(&url))
+        .await
+        .ok()?
+        .dyn_into()
+        .ok()?;
+
+    if !res.ok() {
+        return None;
+    }
+
+    let body = JsFuture::from(res.text().ok()?).await.ok()?.as_string()?;
+    serde_json::from_str(&body).ok()
+}
+
+/// Retrieves the parameters signed by the identity key with `fingerprint`.
+async fn fetch_signed(
+    window: &Window,
+    storage: Option<&Storage>,
+    pkg_url: &str,
+    fingerprint: &str,
+) -> Result<ParametersStatement, JsValue> {
+    let verify = |body: &str| -> Result<ParametersStatement, JsValue> {
+        serde_json::from_str::<SignedParameters>(body)
+            .map_err(js_error)?
+            .verify(Some(fingerprint))
+            .map_err(js_error)
+    };
+
+    let log = fetch_log(window, pkg_url).await;
+    let body = fetch_cached(window, storage, pkg_url, PATHS[2], |cached, body| {
+        let previous = cached.and_then(|cached| verify(cached).ok());
+
+        verify(body)?
+            .check_log(log.as_ref(), previous.as_ref())
+            .map_err(js_error)
+    })
+    .await?;
+
+    // Cached parameters are used as-is, so verify them regardless.
+    verify(&body)
+}
+
+/// Retrieves the master public key and the verifying key of a PKG.
+///
+/// Returns an object `{ publicKey, verifyingKey }`. The keys are cached in `localStorage`, and
+/// used when the PKG cannot be reached.
+///
+/// # Arguments
+///
+/// * `pkg_url` - The URL of the PKG.
+/// * `fingerprint` - The fingerprint of the identity key of the PKG, optional.
+///
+/// # Errors
+///
+/// Throws if the parameters are not signed by the identity key with the given fingerprint, or
+/// inconsistent with the log of the PKG. Without fingerprint, throws if the PKG serves a
+/// different public key than before for the same URL. Both might indicate that the PKG is
+/// compromised or impersonated.
+#[wasm_bindgen(js_name = fetchParameters)]
+pub async fn js_fetch_parameters(
+    pkg_url: String,
+    fingerprint: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let window = window()?;
+    let storage = window.local_storage()?;
+    let pkg_url = pkg_url.trim_end_matches('/');
+
+    let out = match fingerprint {
+        Some(fingerprint) => {
+            let statement = fetch_signed(&window, storage.as_ref(), pkg_url, &fingerprint).await?;
+
+            json!({
+                "publicKey": statement.public_key,
+                "verifyingKey": statement.verifying_key,
+            })
+        }
+        None => {
+            let [mpk_path, vk_path, _] = PATHS;
+            let mpk = fetch_cached(
+                &window,
+                storage.as_ref(),
+                pkg_url,
+                mpk_path,
+                trust_on_first_use(format!("{pkg_url}/{mpk_path}")),
+            )
+            .await?;
+            let vk = fetch_cached(
+                &window,
+                storage.as_ref(),
+                pkg_url,
+                vk_path,
+                trust_on_first_use(format!("{pkg_url}/{vk_path}")),
+            )
+            .await?;
+
+            json!({
+                "publicKey": public_key(&mpk),
+                "verifyingKey": public_key(&vk),
+            })
+        }
+    };
+
+    Ok(out.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+/// Forgets the cached parameters of a PKG, such that the next parameters retrieved are trusted.
+///
+/// Only use this after making sure that a change of the public key of the PKG is legitimate.
+#[wasm_bindgen(js_name = forgetParameters)]
+pub fn js_forget_parameters(pkg_url: String) -> Result<(), JsValue> {
+    let pkg_url = pkg_url.trim_end_matches('/');
+
+    if let Some(storage) = window()?.local_storage()? {
+        for path in PATHS {
+            storage.remove_item(&storage_key(pkg_url, path))?;
+        }
+    }
+
+    Ok(())
+}
@@ -82,6 +82,9 @@ mod mem {
             policy: setup.policy.clone(),
             pub_sign_key: setup.signing_keys[0].clone(),
             priv_sign_key: Some(setup.signing_keys[1].clone()),
+            hints: None,
+            anonymous: false,
+            private_sender: false,
         };
 
         let js_options = serde_wasm_bindgen::to_value(&options).unwrap();
@@ -134,6 +137,9 @@ mod mem {
             policy: setup.policy.clone(),
             pub_sign_key: setup.signing_keys[0].clone(),
             priv_sign_key: Some(setup.signing_keys[1].clone()),
+            hints: None,
+            anonymous: false,
+            private_sender: false,
         };
 
         let js_options = serde_wasm_bindgen::to_value(&options).unwrap();
@@ -274,6 +280,9 @@ mod stream {
             policy: setup.policy.clone(),
             pub_sign_key: setup.signing_keys[0].clone(),
             priv_sign_key: Some(setup.signing_keys[1].clone()),
+            hints: None,
+            anonymous: false,
+            private_sender: false,
         };
 
         let js_options = serde_wasm_bindgen::to_value(&options).unwrap();
@@ -343,6 +352,9 @@ mod stream {
             policy: setup.policy.clone(),
             pub_sign_key: setup.signing_keys[0].clone(),
             priv_sign_key: None,
+            hints: None,
+            anonymous: false,
+            private_sender: false,
         };
 
         let js_options = serde_wasm_bindgen::to_value(&options).unwrap();